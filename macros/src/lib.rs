@@ -0,0 +1,311 @@
+//! Procedural macros for jsosso:
+//! Json literals that are parsed with jsosso's grammar at compile time.
+//!
+//! - `json_lit!` turns a string literal with Json text into a `Json` value;
+//!   `${expr}` placeholders insert Rust expressions as values or keys.
+//! - `include_json!` does the same for a Json file,
+//!   so that fixtures are checked when the code that uses them is built.
+//!
+//! Syntax errors are compiler errors pointing at the string literal
+//! with line and column of the error in the Json text.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Literal, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Expr, LitStr};
+use jsosso::Json;
+use jsosso::error::Error;
+use pacosso::ParseError;
+
+/// Parses the Json text in a string literal at compile time
+/// and expands to an expression that builds the equivalent `Json` value.
+///
+/// Outside of Json strings, `${expr}` inserts the Rust expression `expr`:
+/// as a value, it is converted with `Json::from`,
+/// as a key, with `to_string`.
+/// Within Json strings, `${` has no special meaning.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::json;
+/// use jsosso_macros::json_lit;
+///
+/// let id = 4711;
+/// let name = "unit";
+///
+/// assert_eq!(
+///     json_lit!(r#"{"id": ${id}, ${name}: "C", "tags": ["${not}", ${-1.5}]}"#),
+///     json!({"id": 4711, "unit": "C", "tags": ["${not}", (-1.5)]})
+/// );
+/// ```
+#[proc_macro]
+pub fn json_lit(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    match expand(&lit.value(), true) {
+        Ok(ts) => ts.into(),
+        Err(e) => syn::Error::new(lit.span(), e).to_compile_error().into(),
+    }
+}
+
+/// Reads and parses a Json file at compile time
+/// and expands to an expression that builds the equivalent `Json` value.
+/// The path is relative to the directory of the manifest (`Cargo.toml`)
+/// of the crate that uses the macro.
+/// The file is plain Json, i.e. without placeholders.
+/// Changing the file rebuilds the code that includes it.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso_macros::include_json;
+///
+/// let j = include_json!("../rsc/test/pass1.json");
+/// assert_eq!(j, Json::from_file("../rsc/test/pass1.json").unwrap());
+/// ```
+#[proc_macro]
+pub fn include_json(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = Path::new(&dir).join(lit.value());
+
+    let r = fs::read_to_string(&path)
+           .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+           .and_then(|text| expand(&text, false)
+           .map_err(|e| format!("{}: {}", path.display(), e)));
+
+    match r {
+        Ok(ts) => {
+            let p = path.to_string_lossy();
+            quote!({
+                const _: &[u8] = ::std::include_bytes!(#p);
+                #ts
+            }).into()
+        },
+        Err(e) => syn::Error::new(lit.span(), e).to_compile_error().into(),
+    }
+}
+
+// Parses 'text' and generates the code for the Json value.
+pub(crate) fn expand(text: &str, placeholders: bool) -> Result<TokenStream2, String> {
+    let s = if placeholders {
+        substitute(text)?
+    } else {
+        Substitution::none(text)
+    };
+
+    let j = match Json::from_slice(s.text.as_bytes()) {
+        Ok(j) => j,
+        Err(Error::Parse(ParseError::Failed(msg, cur))) => {
+            let (l, c) = line_and_column(text, s.original(cur.stream as usize));
+            return Err(format!("invalid Json at line {}, column {}: {}", l, c, msg));
+        },
+        Err(e) => return Err(format!("invalid Json: {}", e)),
+    };
+
+    Ok(generate(&j, &s.holes))
+}
+
+// Json text with the placeholders replaced by strings.
+pub(crate) struct Substitution {
+    pub(crate) text: String,
+    // the expressions by the values of their strings
+    pub(crate) holes: HashMap<String, Expr>,
+    // for each placeholder: start in 'text', its length there,
+    // start in the original text and its length there
+    spans: Vec<(usize, usize, usize, usize)>,
+}
+
+impl Substitution {
+    fn none(text: &str) -> Substitution {
+        Substitution {
+            text: text.to_string(),
+            holes: HashMap::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    // Maps a position in the substituted text to the original text.
+    pub(crate) fn original(&self, pos: usize) -> usize {
+        let mut delta: isize = 0;
+        for (start, len, orig, olen) in &self.spans {
+            if pos < *start {
+                break;
+            }
+            if pos < start + len {
+                return *orig;
+            }
+            delta += *olen as isize - *len as isize;
+        }
+        (pos as isize + delta) as usize
+    }
+}
+
+// Replaces the placeholders outside of Json strings by strings
+// that start with a prefix of NUL characters that does not occur in 'text'.
+pub(crate) fn substitute(text: &str) -> Result<Substitution, String> {
+    let mut n = 1;
+    while text.contains(&"\\u0000".repeat(n)) || text.contains(&"\0".repeat(n)) {
+        n += 1;
+    }
+
+    let mut s = Substitution::none("");
+    let b = text.as_bytes();
+    let mut string = false;
+    let mut escape = false;
+    let mut last = 0;
+    let mut i = 0;
+    while i < b.len() {
+        if string {
+            match b[i] {
+                _ if escape => escape = false,
+                b'\\' => escape = true,
+                b'"' => string = false,
+                _ => (),
+            }
+            i += 1;
+            continue;
+        }
+        if b[i] == b'"' {
+            string = true;
+            i += 1;
+            continue;
+        }
+        if !text[i ..].starts_with("${") {
+            i += 1;
+            continue;
+        }
+
+        let end = match placeholder_end(&text[i + 2 ..]) {
+            Some(e) => i + 2 + e,
+            None => {
+                let (l, c) = line_and_column(text, i);
+                return Err(format!("unclosed placeholder at line {}, column {}", l, c));
+            },
+        };
+        let e = match syn::parse_str::<Expr>(&text[i + 2 .. end]) {
+            Ok(e) => e,
+            Err(e) => {
+                let (l, c) = line_and_column(text, i);
+                return Err(format!("invalid placeholder at line {}, column {}: {}", l, c, e));
+            },
+        };
+
+        let k = s.holes.len();
+        let marker = format!("\"{}{}\"", "\\u0000".repeat(n), k);
+        s.text.push_str(&text[last .. i]);
+        s.spans.push((s.text.len(), marker.len(), i, end + 1 - i));
+        s.text.push_str(&marker);
+        s.holes.insert(format!("{}{}", "\0".repeat(n), k), e);
+
+        i = end + 1;
+        last = i;
+    }
+    s.text.push_str(&text[last ..]);
+    Ok(s)
+}
+
+// Finds the closing brace of a placeholder,
+// skipping nested braces and Rust strings.
+fn placeholder_end(t: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut string = false;
+    let mut escape = false;
+    for (i, c) in t.char_indices() {
+        if string {
+            match c {
+                _ if escape => escape = false,
+                '\\' => escape = true,
+                '"' => string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match c {
+            '"' => string = true,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+// Lines and columns count from 1; columns count characters.
+pub(crate) fn line_and_column(text: &str, pos: usize) -> (usize, usize) {
+    let mut pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    let before = &text[.. pos];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(k) => before[k + 1 ..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
+
+fn generate(j: &Json, holes: &HashMap<String, Expr>) -> TokenStream2 {
+    match j {
+        Json::Null => quote!(::jsosso::Json::Null),
+        Json::Boolean(b) => quote!(::jsosso::Json::Boolean(#b)),
+        Json::Number(n) => {
+            let n = number(*n);
+            quote!(::jsosso::Json::Number(#n))
+        },
+        Json::String(s) => match holes.get(s) {
+            Some(e) => quote!(::jsosso::Json::from(#e)),
+            None => quote!(::jsosso::Json::String(::std::string::String::from(#s))),
+        },
+        Json::Array(v) => {
+            let es = v.iter().map(|e| generate(e, holes));
+            quote!(::jsosso::Json::Array(::std::vec![#(#es),*]))
+        },
+        Json::Object(m) if m.is_empty() => {
+            quote!(::jsosso::Json::Object(::std::boxed::Box::default()))
+        },
+        Json::Object(m) => {
+            // in the order of the text would be nicer, but the map has no order
+            let mut ks: Vec<&String> = m.keys().collect();
+            ks.sort();
+            let n = ks.len();
+            let vs = ks.iter().map(|k| generate(&m[*k], holes));
+            let ks = ks.iter().map(|k| match holes.get(*k) {
+                Some(e) => quote!(::std::string::ToString::to_string(&(#e))),
+                None => quote!(::std::string::String::from(#k)),
+            });
+            // the map must not shadow variables of the placeholders
+            let m = Ident::new("object", Span::mixed_site());
+            quote!({
+                let mut #m = ::std::collections::HashMap::with_capacity(#n);
+                #( #m.insert(#ks, #vs); )*
+                ::jsosso::Json::Object(::std::boxed::Box::new(#m))
+            })
+        },
+    }
+}
+
+fn number(n: f64) -> TokenStream2 {
+    if n.is_infinite() {
+        return if n > 0.0 {
+            quote!(::std::f64::INFINITY)
+        } else {
+            quote!(::std::f64::NEG_INFINITY)
+        };
+    }
+    let l = Literal::f64_suffixed(n.abs());
+    if n.is_sign_negative() {
+        quote!(-#l)
+    } else {
+        quote!(#l)
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,64 @@
+use super::*;
+
+#[test]
+fn test_expand_errors() {
+    assert!(match expand("[1, 2", false) {
+        Err(e) => e.starts_with("invalid Json at line 1, column 6: end of file"),
+        Ok(_) => panic!("unexpected success"),
+    });
+    assert!(match expand("{\n  \"a\": 1,\n  \"b\": tru\n}", false) {
+        Err(e) => e.starts_with("invalid Json at line 3, column"),
+        Ok(_) => panic!("unexpected success"),
+    });
+    assert!(match expand("[1] 2", false) {
+        Err(e) => e.contains("trailing characters"),
+        Ok(_) => panic!("unexpected success"),
+    });
+    // placeholders are not allowed in files
+    assert!(expand("[${x}]", false).is_err());
+}
+
+#[test]
+fn test_placeholder_errors() {
+    assert!(match expand("[${x]", true) {
+        Err(e) => e == "unclosed placeholder at line 1, column 2",
+        Ok(_) => panic!("unexpected success"),
+    });
+    assert!(match expand("\n [${1 +}]", true) {
+        Err(e) => e.starts_with("invalid placeholder at line 2, column 3"),
+        Ok(_) => panic!("unexpected success"),
+    });
+    // the error after a placeholder is reported in the original text
+    assert!(match expand("{\"a\": ${vec![1, 2].len()}, \"b\": ]}", true) {
+        Err(e) => e.starts_with("invalid Json at line 1, column 33:"),
+        Ok(_) => panic!("unexpected success"),
+    });
+}
+
+#[test]
+fn test_substitute() {
+    let s = substitute(r#"{"a": ${x}, ${k}: [${f("}")}, "${no}"]}"#).unwrap();
+    assert_eq!(s.holes.len(), 3);
+    assert_eq!(s.text, r#"{"a": "\u00000", "\u00001": ["\u00002", "${no}"]}"#);
+    assert!(s.holes.contains_key("\u{0}0"));
+
+    // positions before, within and after placeholders
+    assert_eq!(s.original(3), 3);
+    assert_eq!(s.original(8), 6);
+    assert_eq!(s.original(16), 11);
+    assert_eq!(s.original(17), 12);
+
+    // the prefix does not occur in the text
+    let s = substitute(r#"["\u0000", ${x}]"#).unwrap();
+    assert_eq!(s.text, r#"["\u0000", "\u0000\u00000"]"#);
+    assert!(s.holes.contains_key("\u{0}\u{0}0"));
+}
+
+#[test]
+fn test_line_and_column() {
+    assert_eq!(line_and_column("abc", 0), (1, 1));
+    assert_eq!(line_and_column("abc", 2), (1, 3));
+    assert_eq!(line_and_column("a\nbc", 3), (2, 2));
+    assert_eq!(line_and_column("ä\nöü", 5), (2, 2));
+    assert_eq!(line_and_column("abc", 10), (1, 4));
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use jsosso::{json, Json};
+use jsosso_macros::{include_json, json_lit};
+
+#[test]
+fn test_json_lit() {
+    assert_eq!(json_lit!("null"), Json::Null);
+    assert_eq!(json_lit!("true"), Json::Boolean(true));
+    assert_eq!(json_lit!("[]"), json!([]));
+    assert_eq!(json_lit!("{}"), json!({}));
+    assert_eq!(json_lit!(" [1, -2.5, 1e3, -0, 1e400, -1e400] "), Json::Array(vec![
+        Json::Number(1.0),
+        Json::Number(-2.5),
+        Json::Number(1000.0),
+        Json::Number(-0.0),
+        Json::Number(f64::INFINITY),
+        Json::Number(f64::NEG_INFINITY),
+    ]));
+    assert!(matches!(json_lit!("-0"), Json::Number(n) if n == 0.0 && n.is_sign_negative()));
+    assert_eq!(json_lit!("0.1"), Json::Number(0.1));
+    assert_eq!(json_lit!("123456789012345678901234567890"), Json::Number(123456789012345678901234567890.0));
+    assert_eq!(json_lit!("\"tab:\\t, quote: \\\", \\u00e9\\ud83d\\ude00\""), Json::from("tab:\t, quote: \", é😀"));
+    assert_eq!(
+        json_lit!(r#"{
+            "Id": 1,
+            "Name": "Doe",
+            "Location": [39.23, -8.88],
+            "registered": true,
+            "nested": {"a": [null, {"b": false}]}
+        }"#),
+        json!({
+            "Id": 1,
+            "Name": "Doe",
+            "Location": [39.23, -8.88],
+            "registered": true,
+            "nested": {"a": [null, {"b": false}]}
+        })
+    );
+}
+
+#[test]
+fn test_json_lit_placeholders() {
+    let x = 2.5;
+    let name = String::from("key");
+    let inner = json!([1, 2]);
+    let m: HashMap<i32, &str> = [(1, "one")].into_iter().collect();
+
+    assert_eq!(json_lit!("${x}"), Json::Number(2.5));
+    assert_eq!(json_lit!("${ -x * 2.0 }"), Json::Number(-5.0));
+    assert_eq!(
+        json_lit!(r#"{${name}: ${inner.clone()}, "${x}": "${x}", "m": ${m[&1]}, ${format!("{}-{}", name, 1)}: ${ { let y = 1; y + 1 } }}"#),
+        json!({"key": [1, 2], "${x}": "${x}", "m": "one", "key-1": 2})
+    );
+    // placeholders are evaluated
+    let mut n = 0;
+    let j = json_lit!("[${{ n += 1; n }}, ${{ n += 1; n }}]");
+    assert_eq!(j, json!([1, 2]));
+    assert_eq!(n, 2);
+}
+
+#[test]
+fn test_include_json() {
+    for (j, f) in [
+        (include_json!("../rsc/test/arbitrary.json"), "arbitrary.json"),
+        (include_json!("../rsc/test/oeis.json"), "oeis.json"),
+        (include_json!("../rsc/test/oeis2.json"), "oeis2.json"),
+        (include_json!("../rsc/test/pass1.json"), "pass1.json"),
+        (include_json!("../rsc/schema/order.json"), "../schema/order.json"),
+    ] {
+        assert_eq!(j, Json::from_file(format!("../rsc/test/{}", f)).unwrap(), "{}", f);
+    }
+}
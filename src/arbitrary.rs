@@ -1,8 +1,15 @@
 use super::*;
-use std::str;
-use rand;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use super::serializing::SerializeOpts;
+use rand::{self, Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 /// Generates a random Json value.
+/// The values are drawn from the thread-local random number generator;
+/// use `make_seeded` to get reproducible values.
 ///
 /// Example:
 ///
@@ -14,13 +21,15 @@ use rand;
 /// use pacosso::{Stream, Opts};
 ///
 /// for _ in 0 .. 10 {
-///     let original = make_arbitrary();
+///     // the seed reproduces the value if the test fails
+///     let seed = rand::random::<u64>();
+///     let original = make_seeded(seed);
 ///     let mut v = Vec::new();
 ///
 ///     assert!(match original.serialize(&mut v) {
 ///         Ok(_)  => true,
 ///         Err(_) => false,
-///     });
+///     }, "seed: {}", seed);
 ///
 ///     let mut input = Cursor::new(v);
 ///     let mut s = Stream::new(Opts::default()
@@ -30,128 +39,2085 @@ use rand;
 ///
 ///     let mycopy = match parse(&mut s) {
 ///         Ok(j) => j,
-///         Err(e) => panic!("unexpected error: {:?} at {}", e, s.position()),
+///         Err(e) => panic!("unexpected error: {:?} at {} (seed: {})", e, s.position(), seed),
 ///     };
 ///
-///     assert_eq!(original, mycopy);
+///     assert_eq!(original, mycopy, "seed: {}", seed);
 /// }
 /// 
 /// ```
 pub fn make_arbitrary() -> Json {
-    make_value(0, 0, 0)
+    make_arbitrary_with(&mut rand::thread_rng())
 }
 
 /// Generates a random Json value with at most 'n' elements.
 /// For n = 0, the function behaves exactly like `make_arbitrary()`.
 pub fn make_n_arbitrary(n: usize) -> Json {
-    make_value(0, n, 0)
+    make_n_arbitrary_with(&mut rand::thread_rng(), n)
 }
 
-fn make_value(level: usize, max: usize, have: usize) -> Json {
+/// Generates a random Json value drawing from the random number generator 'rng'.
+pub fn make_arbitrary_with<R: Rng + ?Sized>(rng: &mut R) -> Json {
+    make_n_arbitrary_with_config(rng, 0, &GeneratorConfig::default())
+}
 
-    let x = rand::random::<u8>()%100;
+/// Generates a random Json value with at most 'n' elements
+/// drawing from the random number generator 'rng'.
+pub fn make_n_arbitrary_with<R: Rng + ?Sized>(rng: &mut R, n: usize) -> Json {
+    make_n_arbitrary_with_config(rng, n, &GeneratorConfig::default())
+}
 
-    if x < 5 {
-        return Json::Null;
+/// Generates a random Json value with at most 'n' elements
+/// drawing from the random number generator 'rng'
+/// according to the generator configuration 'c'.
+pub fn make_n_arbitrary_with_config<R: Rng + ?Sized>(rng: &mut R, n: usize, c: &GeneratorConfig) -> Json {
+    make_value(rng, c, 0, n, 0)
+}
+
+/// Generates a random Json value from 'seed'.
+/// The same seed always yields the same value.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::arbitrary::make_seeded;
+///
+/// assert_eq!(make_seeded(42), make_seeded(42));
+/// ```
+pub fn make_seeded(seed: u64) -> Json {
+    make_arbitrary_with(&mut StdRng::seed_from_u64(seed))
+}
+
+/// Generates a random Json value with at most 'n' elements from 'seed'.
+/// The same seed and 'n' always yield the same value.
+pub fn make_n_seeded(n: usize, seed: u64) -> Json {
+    make_n_arbitrary_with(&mut StdRng::seed_from_u64(seed), n)
+}
+
+/// Generates a random Json value with at most 'n' elements from 'seed'
+/// according to the generator configuration 'c'.
+/// The same seed, 'n' and configuration always yield the same value.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::arbitrary::*;
+///
+/// let c = GeneratorConfig::stress();
+/// for seed in 0 .. 10 {
+///     let original = make_n_seeded_with_config(50, seed, &c);
+///     let v = original.serialize_to_vec(&Default::default());
+///     let mycopy = Json::from_slice(&v);
+///     assert!(matches!(mycopy, Ok(ref j) if *j == original), "seed: {}", seed);
+/// }
+/// ```
+pub fn make_n_seeded_with_config(n: usize, seed: u64, c: &GeneratorConfig) -> Json {
+    make_n_arbitrary_with_config(&mut StdRng::seed_from_u64(seed), n, c)
+}
+
+/// Configures the random Json value generator.
+///
+/// The default configuration generates the values
+/// described in `make_arbitrary`:
+/// numbers in \[0, 1), strings of digits, uppercase letters and some punctuation
+/// and containers nested at most 3 levels deep.
+/// `GeneratorConfig::stress()` provides a configuration
+/// that covers the cases hard to parse and to serialize.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::arbitrary::*;
+///
+/// let c = GeneratorConfig::default()
+///         .set_weights([0, 0, 1, 0, 0, 0])
+///         .set_numbers(Numbers::Integers);
+///
+/// for seed in 0 .. 10 {
+///     assert!(match make_n_seeded_with_config(10, seed, &c) {
+///         Json::Number(n) => n.fract() == 0.0,
+///         _ => false,
+///     });
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    /// Containers nested deeper than `depth` are replaced by null.
+    /// The default is 3.
+    pub depth: usize,
+    /// The maximum number of elements in an array.
+    /// The default is 99.
+    pub array_len: usize,
+    /// The maximum number of members in an object.
+    /// The default is 24.
+    pub object_len: usize,
+    /// The relative weights of null, booleans, numbers, strings, arrays and objects
+    /// in this order. The default is \[5, 10, 15, 20, 25, 25\].
+    pub weights: [u32; 6],
+    /// The characters strings are made of.
+    /// The default is `Alphabet::Simple`.
+    pub alphabet: Alphabet,
+    /// The minimum and maximum number of characters in a string.
+    /// The default is (2, 26).
+    pub string_len: (usize, usize),
+    /// The distribution of numbers.
+    /// The default is `Numbers::Unit`.
+    pub numbers: Numbers,
+}
+
+/// The characters of generated strings.
+/// Each alphabet includes the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Digits, uppercase letters and the punctuation between them,
+    /// without the backslash.
+    Simple,
+    /// Printable ASCII characters including quotes and backslashes.
+    Ascii,
+    /// Printable ASCII characters and control characters,
+    /// i.e. all characters that must or may be escaped.
+    Escapes,
+    /// Any Unicode character including those outside the Basic Multilingual Plane,
+    /// which are escaped as surrogate pairs.
+    Unicode,
+}
+
+/// The distribution of generated numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Numbers {
+    /// Numbers in \[0, 1).
+    Unit,
+    /// Integers that are exactly representable,
+    /// i.e. in \[-2^53, 2^53\].
+    Integers,
+    /// All kinds of finite numbers:
+    /// negative numbers, integers, large and small exponents,
+    /// subnormal numbers and the extremes of `f64`.
+    Mixed,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> GeneratorConfig {
+        GeneratorConfig {
+            depth: 3,
+            array_len: 99,
+            object_len: 24,
+            weights: [5, 10, 15, 20, 25, 25],
+            alphabet: Alphabet::Simple,
+            string_len: (2, 26),
+            numbers: Numbers::Unit,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    /// A configuration that exercises the hard cases:
+    /// Unicode strings with escapes of up to 200 characters,
+    /// all kinds of numbers and containers nested 4 levels deep.
+    pub fn stress() -> GeneratorConfig {
+        GeneratorConfig::default()
+            .set_depth(4)
+            .set_breadth(10, 10)
+            .set_alphabet(Alphabet::Unicode)
+            .set_string_len(0, 200)
+            .set_numbers(Numbers::Mixed)
+    }
+
+    /// Sets the maximum nesting depth of containers.
+    pub fn set_depth(self, d: usize) -> GeneratorConfig {
+        GeneratorConfig {
+            depth: d,
+            ..self
+        }
     }
 
-    if x < 15 {
-        return make_boolean(); 
+    /// Sets the maximum number of elements in arrays and objects.
+    pub fn set_breadth(self, array: usize, object: usize) -> GeneratorConfig {
+        GeneratorConfig {
+            array_len: array,
+            object_len: object,
+            ..self
+        }
     }
 
-    if x < 30 {
-        return make_number();
+    /// Sets the relative weights of null, booleans, numbers, strings, arrays and objects.
+    /// Types with weight 0 are not generated; if all weights are 0, only null is generated.
+    pub fn set_weights(self, w: [u32; 6]) -> GeneratorConfig {
+        GeneratorConfig {
+            weights: w,
+            ..self
+        }
     }
 
-    if x < 50 {
-       return make_string();
+    /// Sets the alphabet of strings.
+    pub fn set_alphabet(self, a: Alphabet) -> GeneratorConfig {
+        GeneratorConfig {
+            alphabet: a,
+            ..self
+        }
     }
 
-    if x < 75 {
-       return make_array(level, max, have);
+    /// Sets the minimum and maximum number of characters in strings.
+    pub fn set_string_len(self, min: usize, max: usize) -> GeneratorConfig {
+        GeneratorConfig {
+            string_len: (min, max.max(min)),
+            ..self
+        }
     }
 
-    make_object(level, max, have)
+    /// Sets the distribution of numbers.
+    pub fn set_numbers(self, n: Numbers) -> GeneratorConfig {
+        GeneratorConfig {
+            numbers: n,
+            ..self
+        }
+    }
 }
 
-fn make_boolean() -> Json {
-    let x = rand::random::<u8>()%2;
+
+fn make_value<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig, level: usize, max: usize, have: usize) -> Json {
+
+    let total: u32 = c.weights.iter().sum();
+    if total == 0 {
+        return Json::Null;
+    }
+
+    let mut x = rng.gen_range(0 .. total);
+    let mut k = 0;
+    for w in c.weights {
+        if x < w {
+            break;
+        }
+        x -= w;
+        k += 1;
+    }
+
+    match k {
+        0 => Json::Null,
+        1 => make_boolean(rng),
+        2 => make_number(rng, c),
+        3 => make_string(rng, c),
+        4 => make_array(rng, c, level, max, have),
+        _ => make_object(rng, c, level, max, have),
+    }
+}
+
+fn make_boolean<R: Rng + ?Sized>(rng: &mut R) -> Json {
+    let x = rng.gen::<u8>()%2;
     Json::Boolean(x == 0)
 }
 
-fn make_number() -> Json {
-    Json::Number(rand::random::<f64>())
+fn make_number<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig) -> Json {
+    Json::Number(match c.numbers {
+        Numbers::Unit => rng.gen::<f64>(),
+        Numbers::Integers => random_integer(rng),
+        Numbers::Mixed => random_mixed(rng),
+    })
 }
 
-fn make_string() -> Json {
-    Json::String(random_string())
+const EXACT: i64 = 1 << 53;
+
+fn random_integer<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    // small integers are the common case
+    if rng.gen::<bool>() {
+        rng.gen_range(-1000 .. 1000) as f64
+    } else {
+        rng.gen_range(-EXACT ..= EXACT) as f64
+    }
 }
 
-fn random_string() -> String {
-    let x = (rand::random::<u8>()%25)+2;
+fn random_mixed<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    const EDGES: [f64; 12] = [
+        0.0, -0.0, 1.0, -1.0,
+        f64::MAX, f64::MIN, f64::MIN_POSITIVE, f64::EPSILON,
+        5e-324, 1e21, 1e-7, 9007199254740992.0,
+    ];
 
-    let mut v = Vec::new();
+    match rng.gen_range(0 .. 7) {
+        0 => rng.gen::<f64>(),
+        1 => -rng.gen::<f64>() * 10f64.powi(rng.gen_range(0 .. 10)),
+        2 => random_integer(rng),
+        // any finite number, most of them with large exponents
+        3 => loop {
+            let f = f64::from_bits(rng.gen::<u64>());
+            if f.is_finite() {
+                break f;
+            }
+        },
+        // subnormal numbers
+        4 => {
+            let f = f64::from_bits(rng.gen::<u64>() & 0x000f_ffff_ffff_ffff);
+            if rng.gen::<bool>() { -f } else { f }
+        },
+        5 => {
+            let f = rng.gen::<f64>() * 10f64.powi(rng.gen_range(-30 ..= 30));
+            if rng.gen::<bool>() { -f } else { f }
+        },
+        _ => EDGES[rng.gen_range(0 .. EDGES.len())],
+    }
+}
+
+fn make_string<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig) -> Json {
+    Json::String(random_string(rng, c))
+}
+
+fn random_string<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig) -> String {
+    let (min, max) = c.string_len;
+    let x = rng.gen_range(min ..= max.max(min));
+
+    let mut s = String::with_capacity(x);
     for _ in 0 .. x {
-        let b = rand::random::<u8>()%42;
-        let c = b+48;
-        if c == b'\\' {
-           v.push(b'.');
-        } else {
-           v.push(c);
-        }
+        s.push(random_char(rng, c.alphabet));
     }
+    s
+}
+
+fn random_char<R: Rng + ?Sized>(rng: &mut R, a: Alphabet) -> char {
+    let k = match a {
+        Alphabet::Simple => 0,
+        Alphabet::Ascii => 1,
+        Alphabet::Escapes => rng.gen_range(1 ..= 2),
+        Alphabet::Unicode => rng.gen_range(1 ..= 4),
+    };
 
-    let s = match str::from_utf8(&v) {
-        Ok(x) => x,
-        Err(e) => panic!("internal error: {:?}", e), // cannot happen
+    let x = match k {
+        0 => {
+            let c = rng.gen::<u8>()%42 + 48;
+            if c == b'\\' { b'.' as u32 } else { c as u32 }
+        },
+        1 => rng.gen_range(0x20 ..= 0x7e),
+        2 => {
+            let c = rng.gen_range(0 ..= 0x20);
+            if c == 0x20 { 0x7f } else { c }
+        },
+        // the BMP without surrogates
+        3 => {
+            let c = rng.gen_range(0x80 .. 0xf800);
+            if c >= 0xd800 { c + 0x800 } else { c }
+        },
+        _ => rng.gen_range(0x10000 ..= 0x10ffff),
     };
 
-    s.to_string()
+    match char::from_u32(x) {
+        Some(c) => c,
+        None => panic!("internal error: invalid character {:x}", x), // cannot happen
+    }
 }
 
-fn make_array(level: usize, max: usize, have: usize) -> Json {
-    if level > 2 {
+fn make_array<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig, level: usize, max: usize, have: usize) -> Json {
+    if level >= c.depth {
         return Json::Null;
     }
 
-    let mut n = have;
-
-    let x = rand::random::<usize>()%100;
+    let x = rng.gen_range(0 ..= c.array_len);
 
     let mut v = Vec::with_capacity(x);
-    for _ in 0 .. x {
+    for n in have .. have + x {
         if n >= max {
             break;
         }
-        let j = make_value(level+1, max, n);
+        let j = make_value(rng, c, level+1, max, n);
         v.push(j); 
-        n += 1;
     }
     
     Json::Array(v)
 }
 
-fn make_object(level: usize, max: usize, have: usize) -> Json {
-    if level > 2 {
+fn make_object<R: Rng + ?Sized>(rng: &mut R, c: &GeneratorConfig, level: usize, max: usize, have: usize) -> Json {
+    if level >= c.depth {
         return Json::Null;
     }
 
-    let mut n = have;
-
-    let x = rand::random::<u8>()%25;
+    let x = rng.gen_range(0 ..= c.object_len);
 
     let mut m = HashMap::new();
-    for _ in 0 .. x {
+    for n in have .. have + x {
         if n >= max {
             break;
         }
-        let s = random_string();
-        let j = make_value(level+1, max, n);
+        let s = random_string(rng, c);
+        let j = make_value(rng, c, level+1, max, n);
         let _ = m.insert(s, j);
-        n += 1;
     }
 
     Json::Object(Box::new(m))
 }
 
+/// Returns an iterator over simpler variants of Json value 'j', the simplest first,
+/// for shrinking failing test cases in property-based testing.
+/// The variants are made lazily, one at a time.
+/// Repeatedly shrinking a value ends with values that have no variants.
+///
+/// The variants are
+/// - null for booleans, numbers, strings and containers,
+/// - `false` for `true`,
+/// - 0, 1 or -1, the integral part, the half and the absolute value of numbers,
+/// - the empty string, the first half, the string without its first or last character
+///   and the string with all characters that are not ASCII letters or digits replaced by 'a',
+/// - the elements of containers and the keys of objects, the empty container,
+///   the first half of the elements, the container without one of its elements,
+///   the container with one of its elements shrunk
+///   and the object with one of its keys shrunk like a string.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::json;
+/// use jsosso::arbitrary::shrink;
+///
+/// assert_eq!(shrink(&json!(true)).collect::<Vec<_>>(), vec![json!(null), json!(false)]);
+/// assert_eq!(shrink(&json!(null)).count(), 0);
+/// assert!(shrink(&json!([1, "x"])).any(|j| j == json!([1])));
+/// assert!(shrink(&json!([1, "x"])).any(|j| j == json!(["x"])));
+/// ```
+pub fn shrink(j: &Json) -> Shrink {
+    Shrink::new(j.clone())
+}
+
+/// The iterator over the simpler variants of a Json value returned by `shrink`.
+#[derive(Debug, Clone)]
+pub struct Shrink {
+    value: Json,
+    // the variants of scalars
+    scalars: std::vec::IntoIter<Json>,
+    // the keys of objects in order
+    keys: Vec<String>,
+    stage: usize,
+    index: usize,
+    // the variants of the element at 'index'
+    inner: Option<Box<Shrink>>,
+    // the variants of the key at 'index'
+    key: Option<std::vec::IntoIter<Json>>,
+}
+
+impl Shrink {
+    fn new(j: Json) -> Shrink {
+        let mut keys = Vec::new();
+        let scalars = match &j {
+            Json::Null => Vec::new(),
+            Json::Boolean(b) => if *b {
+                vec![Json::Null, Json::Boolean(false)]
+            } else {
+                vec![Json::Null]
+            },
+            Json::Number(n) => shrink_number(*n),
+            Json::String(s) => shrink_string(s),
+            Json::Array(_) => Vec::new(),
+            Json::Object(m) => {
+                keys = m.keys().cloned().collect();
+                keys.sort();
+                Vec::new()
+            },
+        };
+        Shrink {
+            value: j,
+            scalars: scalars.into_iter(),
+            keys,
+            stage: 0,
+            index: 0,
+            inner: None,
+            key: None,
+        }
+    }
+
+    fn next_array(&mut self) -> Option<Json> {
+        let a = match &self.value {
+            Json::Array(a) if !a.is_empty() => a,
+            // the empty container shrinks to null only
+            _ if self.stage == 0 => {
+                self.stage = 1;
+                return Some(Json::Null);
+            },
+            _ => return None,
+        };
+        let n = a.len();
+        loop {
+            match self.stage {
+                // null
+                0 => {
+                    self.stage = 1;
+                    return Some(Json::Null);
+                },
+                // the elements
+                1 => {
+                    if self.index < n {
+                        self.index += 1;
+                        if a[self.index - 1] != Json::Null {
+                            return Some(a[self.index - 1].clone());
+                        }
+                        continue;
+                    }
+                    self.stage = 2;
+                    self.index = 0;
+                    return Some(Json::Array(Vec::new()));
+                },
+                // the first half
+                2 => {
+                    self.stage = 3;
+                    if n > 2 {
+                        return Some(Json::Array(a[.. n / 2].to_vec()));
+                    }
+                },
+                // without one of the elements
+                3 => {
+                    if n > 1 && self.index < n {
+                        let mut b = a.clone();
+                        b.remove(self.index);
+                        self.index += 1;
+                        return Some(Json::Array(b));
+                    }
+                    self.stage = 4;
+                    self.index = 0;
+                },
+                // one element shrunk
+                _ => {
+                    if self.index >= n {
+                        return None;
+                    }
+                    let i = self.index;
+                    let inner = self.inner.get_or_insert_with(|| Box::new(Shrink::new(a[i].clone())));
+                    match inner.next() {
+                        Some(x) => {
+                            let mut b = a.clone();
+                            b[i] = x;
+                            return Some(Json::Array(b));
+                        },
+                        None => {
+                            self.inner = None;
+                            self.index += 1;
+                        },
+                    }
+                },
+            }
+        }
+    }
+
+    fn next_object(&mut self) -> Option<Json> {
+        let m = match &self.value {
+            Json::Object(m) if !m.is_empty() => m,
+            // the empty container shrinks to null only
+            _ if self.stage == 0 => {
+                self.stage = 1;
+                return Some(Json::Null);
+            },
+            _ => return None,
+        };
+        let n = self.keys.len();
+        loop {
+            match self.stage {
+                // null
+                0 => {
+                    self.stage = 1;
+                    return Some(Json::Null);
+                },
+                // the values, then the keys as strings
+                1 => {
+                    if self.index < n {
+                        let v = &m[&self.keys[self.index]];
+                        self.index += 1;
+                        if *v != Json::Null {
+                            return Some(v.clone());
+                        }
+                        continue;
+                    }
+                    if self.index < 2 * n {
+                        self.index += 1;
+                        return Some(Json::String(self.keys[self.index - n - 1].clone()));
+                    }
+                    self.stage = 2;
+                    self.index = 0;
+                    return Some(Json::Object(Box::default()));
+                },
+                // without one of the members
+                2 => {
+                    if n > 1 && self.index < n {
+                        let mut o = m.clone();
+                        o.remove(&self.keys[self.index]);
+                        self.index += 1;
+                        return Some(Json::Object(o));
+                    }
+                    self.stage = 3;
+                    self.index = 0;
+                },
+                // one key shrunk
+                3 => {
+                    if self.index >= n {
+                        self.stage = 4;
+                        self.index = 0;
+                        continue;
+                    }
+                    let k = &self.keys[self.index];
+                    let key = self.key.get_or_insert_with(|| shrink_string(k).into_iter());
+                    match key.next() {
+                        Some(Json::String(x)) if !m.contains_key(&x) => {
+                            let mut o = m.clone();
+                            let v = o.remove(k).unwrap_or(Json::Null);
+                            o.insert(x, v);
+                            return Some(Json::Object(o));
+                        },
+                        Some(_) => continue,
+                        None => {
+                            self.key = None;
+                            self.index += 1;
+                        },
+                    }
+                },
+                // one value shrunk
+                _ => {
+                    if self.index >= n {
+                        return None;
+                    }
+                    let k = &self.keys[self.index];
+                    let inner = self.inner.get_or_insert_with(|| Box::new(Shrink::new(m[k].clone())));
+                    match inner.next() {
+                        Some(x) => {
+                            let mut o = m.clone();
+                            o.insert(k.clone(), x);
+                            return Some(Json::Object(o));
+                        },
+                        None => {
+                            self.inner = None;
+                            self.index += 1;
+                        },
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Iterator for Shrink {
+    type Item = Json;
+
+    fn next(&mut self) -> Option<Json> {
+        match self.value {
+            Json::Array(_) => self.next_array(),
+            Json::Object(_) => self.next_object(),
+            _ => self.scalars.next(),
+        }
+    }
+}
+
+fn shrink_number(n: f64) -> Vec<Json> {
+    let mut v = vec![Json::Null];
+    let mut ns = vec![0.0, n.signum(), n.trunc(), (n / 2.0).trunc(), n.abs()];
+    if !n.is_finite() {
+        ns.push(f64::MAX.copysign(n));
+    }
+    for m in ns {
+        // strictly simpler: smaller, integral where 'n' is not or positive where 'n' is negative
+        let simpler = m.is_finite() && (m.abs() < n.abs()
+                                        || (m.fract() == 0.0 && n.fract() != 0.0)
+                                        || (m > 0.0 && m == -n));
+        let x = Json::Number(m);
+        if simpler && !v.contains(&x) {
+            v.push(x);
+        }
+    }
+    v
+}
+
+fn shrink_string(s: &str) -> Vec<Json> {
+    let mut v = vec![Json::Null];
+    if s.is_empty() {
+        return v;
+    }
+    let cs: Vec<char> = s.chars().collect();
+    let n = cs.len();
+    let ss: [String; 5] = [
+        String::new(),
+        cs[.. n / 2].iter().collect(),
+        cs[1 ..].iter().collect(),
+        cs[.. n - 1].iter().collect(),
+        cs.iter().map(|c| if c.is_ascii_alphanumeric() { *c } else { 'a' }).collect(),
+    ];
+    for x in ss {
+        let x = Json::String(x);
+        if x != Json::String(s.to_string()) && !v.contains(&x) {
+            v.push(x);
+        }
+    }
+    v
+}
+
+/// The error of the schema-driven generator `make_from_schema`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// The location of the offending subschema as Json pointer.
+    pub path: String,
+    /// The reason why no value could be generated.
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "schema at '{}': {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError { }
+
+/// Generates a random Json value that conforms to the JSON Schema 'schema'
+/// drawing from the random number generator 'rng'.
+/// Where the schema leaves room, the generator configuration 'c' applies:
+/// values without a schema, the string alphabet,
+/// the maximum number of array elements and additional object members
+/// and the nesting depth beyond which optional content is left out.
+///
+/// The generator understands
+/// - `type`, `enum`, `const`, `anyOf`, `oneOf` and local `$ref`s
+///   (`#/definitions/...` or `#/$defs/...`),
+/// - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum` and `multipleOf`,
+/// - `minLength`, `maxLength`, `pattern`
+///   and the formats `date-time`, `date`, `time`, `email`, `uuid`, `ipv4` and `uri`,
+/// - `items`, `prefixItems`, `additionalItems`, `minItems`, `maxItems` and `uniqueItems`,
+/// - `properties`, `required`, `additionalProperties`, `propertyNames`,
+///   `minProperties` and `maxProperties`.
+///
+/// Other keywords are ignored, except `allOf` and `not`,
+/// which yield an error as do schemas that cannot be satisfied.
+/// Patterns may use literals, `.`, character classes, `\d`, `\w`, `\s`,
+/// groups with alternatives and quantifiers; they are matched as a whole.
+/// `oneOf` is treated like `anyOf`, i.e. the generated value
+/// may satisfy more than one of the alternatives.
+///
+/// Example:
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use jsosso::{json, Json};
+/// use jsosso::arbitrary::*;
+///
+/// let schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "id":    {"type": "integer", "minimum": 1, "maximum": 100},
+///         "sku":   {"type": "string", "pattern": "^[A-Z]{3}-[0-9]{4}$"},
+///         "state": {"enum": ["new", "paid", "shipped"]}
+///     },
+///     "required": ["id", "sku", "state"],
+///     "additionalProperties": false
+/// });
+///
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let c = GeneratorConfig::default();
+/// for _ in 0 .. 10 {
+///     let j = make_from_schema(&mut rng, &schema, &c).unwrap();
+///     let m = match j {
+///         Json::Object(m) => m,
+///         v => panic!("unexpected value: {:?}", v),
+///     };
+///     assert_eq!(m.len(), 3);
+///     assert!(matches!(m["id"], Json::Number(n) if n.fract() == 0.0 && (1.0 ..= 100.0).contains(&n)));
+///     assert!(matches!(&m["sku"], Json::String(s) if s.len() == 8 && &s[3 .. 4] == "-"));
+///     assert!(matches!(&m["state"], Json::String(s) if s == "new" || s == "paid" || s == "shipped"));
+/// }
+///
+/// assert!(make_from_schema(&mut rng, &json!({"type": "string", "minLength": 5, "maxLength": 2}), &c).is_err());
+/// ```
+pub fn make_from_schema<R: Rng + ?Sized>(rng: &mut R, schema: &Json, c: &GeneratorConfig) -> Result<Json, SchemaError> {
+    let mut g = SchemaGen {
+        rng,
+        c,
+        root: schema,
+    };
+    g.value(schema, "", 0)
+}
+
+// Subschemas are followed at most this deep, counting $refs.
+const MAX_SCHEMA_DEPTH: usize = 64;
+
+// The number of elements of values that have no schema.
+const ANY_ELEMENTS: usize = 10;
+
+// The maximum number of extra repetitions of unbounded quantifiers in patterns.
+const MAX_REPEAT: usize = 5;
+
+// The number of attempts to satisfy length bounds and unique items.
+const ATTEMPTS: usize = 100;
+
+struct SchemaGen<'a, R: Rng + ?Sized> {
+    rng: &'a mut R,
+    c: &'a GeneratorConfig,
+    root: &'a Json,
+}
+
+fn schema_error<T>(path: &str, msg: &str) -> Result<T, SchemaError> {
+    Err(SchemaError {
+        path: if path.is_empty() { "#".to_string() } else { format!("#{}", path) },
+        message: msg.to_string(),
+    })
+}
+
+fn get_number(m: &HashMap<String, Json>, k: &str) -> Option<f64> {
+    match m.get(k) {
+        Some(Json::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn get_usize(m: &HashMap<String, Json>, k: &str) -> Option<usize> {
+    get_number(m, k).filter(|n| *n >= 0.0).map(|n| n as usize)
+}
+
+// Draws a number from [lo, hi]; rand panics if the width of the range
+// is not finite, as in [-1e308, 1e308], so such a range is split in halves.
+fn float_in<R: Rng + ?Sized>(rng: &mut R, lo: f64, hi: f64) -> f64 {
+    if (hi - lo).is_finite() {
+        return rng.gen_range(lo ..= hi);
+    }
+    let mid = lo / 2.0 + hi / 2.0;
+    if rng.gen() {
+        rng.gen_range(lo ..= mid)
+    } else {
+        rng.gen_range(mid ..= hi)
+    }
+}
+
+impl<'a, R: Rng + ?Sized> SchemaGen<'a, R> {
+    fn value(&mut self, s: &'a Json, path: &str, level: usize) -> Result<Json, SchemaError> {
+        if level > MAX_SCHEMA_DEPTH {
+            return schema_error(path, "schema nested too deeply");
+        }
+
+        let m = match s {
+            Json::Boolean(true) => return Ok(self.any(level)),
+            Json::Boolean(false) => return schema_error(path, "schema 'false' has no instances"),
+            Json::Object(m) => m,
+            _ => return schema_error(path, "schema must be an object or a boolean"),
+        };
+
+        if let Some(r) = m.get("$ref") {
+            let t = self.resolve(r, path)?;
+            return self.value(t, &format!("{}/$ref", path), level + 1);
+        }
+
+        if let Some(j) = m.get("const") {
+            return Ok(j.clone());
+        }
+
+        if let Some(e) = m.get("enum") {
+            return match e {
+                Json::Array(v) if !v.is_empty() => Ok(v[self.rng.gen_range(0 .. v.len())].clone()),
+                _ => schema_error(path, "enum must be a non-empty array"),
+            };
+        }
+
+        for k in ["allOf", "not"] {
+            if m.contains_key(k) {
+                return schema_error(path, &format!("unsupported keyword {}", k));
+            }
+        }
+
+        for k in ["anyOf", "oneOf"] {
+            if let Some(a) = m.get(k) {
+                return match a {
+                    Json::Array(v) if !v.is_empty() => {
+                        let i = self.rng.gen_range(0 .. v.len());
+                        self.value(&v[i], &format!("{}/{}/{}", path, k, i), level + 1)
+                    },
+                    _ => schema_error(path, &format!("{} must be a non-empty array", k)),
+                };
+            }
+        }
+
+        let t = match m.get("type") {
+            Some(Json::String(t)) => t.as_str(),
+            Some(Json::Array(v)) if !v.is_empty() => match &v[self.rng.gen_range(0 .. v.len())] {
+                Json::String(t) => t.as_str(),
+                _ => return schema_error(path, "type must be a string or an array of strings"),
+            },
+            Some(_) => return schema_error(path, "type must be a string or an array of strings"),
+            None => infer_type(m),
+        };
+
+        match t {
+            "null" => Ok(Json::Null),
+            "boolean" => Ok(make_boolean(self.rng)),
+            "integer" => self.number(m, path, true),
+            "number" => self.number(m, path, false),
+            "string" => self.string(m, path),
+            "array" => self.array(m, path, level),
+            "object" => self.object(m, path, level),
+            "any" => Ok(self.any(level)),
+            t => schema_error(path, &format!("unknown type '{}'", t)),
+        }
+    }
+
+    // a value without schema
+    fn any(&mut self, level: usize) -> Json {
+        let max = if level < self.c.depth { ANY_ELEMENTS } else { 0 };
+        make_value(self.rng, self.c, level.min(self.c.depth), max, 0)
+    }
+
+    // resolves a reference relative to the root schema
+    fn resolve(&self, r: &Json, path: &str) -> Result<&'a Json, SchemaError> {
+        let r = match r {
+            Json::String(r) => r,
+            _ => return schema_error(path, "$ref must be a string"),
+        };
+        let p = match r.strip_prefix('#') {
+            Some(p) => p,
+            None => return schema_error(path, &format!("unsupported $ref '{}'", r)),
+        };
+
+        let mut j = self.root;
+        for t in p.split('/').skip(1) {
+            let t = t.replace("~1", "/").replace("~0", "~");
+            let n = match j {
+                Json::Object(m) => m.get(&t),
+                Json::Array(v) => t.parse::<usize>().ok().and_then(|i| v.get(i)),
+                _ => None,
+            };
+            j = match n {
+                Some(n) => n,
+                None => return schema_error(path, &format!("unresolvable $ref '{}'", r)),
+            };
+        }
+        Ok(j)
+    }
+
+    fn number(&mut self, m: &HashMap<String, Json>, path: &str, integer: bool) -> Result<Json, SchemaError> {
+        let mut lo = get_number(m, "minimum");
+        let mut hi = get_number(m, "maximum");
+        let mut lo_excl = false;
+        let mut hi_excl = false;
+
+        // draft 4 has boolean exclusive bounds, later drafts numbers
+        match m.get("exclusiveMinimum") {
+            Some(Json::Boolean(b)) => lo_excl = *b,
+            Some(Json::Number(n)) if lo.is_none_or(|l| *n >= l) => {
+                lo = Some(*n);
+                lo_excl = true;
+            },
+            _ => (),
+        }
+        match m.get("exclusiveMaximum") {
+            Some(Json::Boolean(b)) => hi_excl = *b,
+            Some(Json::Number(n)) if hi.is_none_or(|h| *n <= h) => {
+                hi = Some(*n);
+                hi_excl = true;
+            },
+            _ => (),
+        }
+
+        let step = match get_number(m, "multipleOf") {
+            Some(s) if s > 0.0 => Some(s),
+            Some(_) => return schema_error(path, "multipleOf must be greater than 0"),
+            None if integer => Some(1.0),
+            None => None,
+        };
+
+        if lo.is_none() && hi.is_none() && step.is_none() {
+            return Ok(make_number(self.rng, self.c));
+        }
+
+        // a range of 1000 around a single bound
+        let (lo, hi) = match (lo, hi) {
+            (Some(l), Some(h)) => (l, h),
+            (Some(l), None) => (l, l + 1000.0),
+            (None, Some(h)) => (h - 1000.0, h),
+            (None, None) if integer => (-1000.0, 1000.0),
+            (None, None) => (0.0, 1000.0),
+        };
+        if !lo.is_finite() || !hi.is_finite() {
+            return schema_error(path, "minimum and maximum must be finite");
+        }
+
+        match step {
+            Some(s) => {
+                if integer && s.fract() != 0.0 {
+                    return schema_error(path, "unsupported fractional multipleOf for integers");
+                }
+                let mut k0 = (lo / s).ceil();
+                let mut k1 = (hi / s).floor();
+                if lo_excl && k0 * s <= lo {
+                    k0 += 1.0;
+                }
+                if hi_excl && k1 * s >= hi {
+                    k1 -= 1.0;
+                }
+                if k0 > k1 || !k0.is_finite() || !k1.is_finite() {
+                    return schema_error(path, "no number between minimum and maximum");
+                }
+                let k = if k1 - k0 < EXACT as f64 {
+                    k0 + self.rng.gen_range(0 ..= (k1 - k0) as i64) as f64
+                } else {
+                    float_in(self.rng, k0, k1).round()
+                };
+                Ok(Json::Number(k * s))
+            },
+            None => {
+                if lo > hi || (lo == hi && (lo_excl || hi_excl)) {
+                    return schema_error(path, "no number between minimum and maximum");
+                }
+                for _ in 0 .. ATTEMPTS {
+                    let n = if lo == hi { lo } else { float_in(self.rng, lo, hi) };
+                    if (lo_excl && n <= lo) || (hi_excl && n >= hi) {
+                        continue;
+                    }
+                    return Ok(Json::Number(n));
+                }
+                schema_error(path, "no number between minimum and maximum")
+            },
+        }
+    }
+
+    fn string(&mut self, m: &HashMap<String, Json>, path: &str) -> Result<Json, SchemaError> {
+        let min = get_usize(m, "minLength");
+        let max = get_usize(m, "maxLength");
+        if let (Some(l), Some(h)) = (min, max) {
+            if l > h {
+                return schema_error(path, "minLength is greater than maxLength");
+            }
+        }
+        let fits = |s: &str| {
+            let n = s.chars().count();
+            min.is_none_or(|l| n >= l) && max.is_none_or(|h| n <= h)
+        };
+
+        let pattern = match m.get("pattern") {
+            Some(Json::String(p)) => match parse_pattern(p) {
+                Ok(p) => Some(p),
+                Err(e) => return schema_error(path, &format!("unsupported pattern '{}': {}", p, e)),
+            },
+            Some(_) => return schema_error(path, "pattern must be a string"),
+            None => None,
+        };
+        let format = match m.get("format") {
+            Some(Json::String(f)) if FORMATS.contains(&f.as_str()) => Some(f.as_str()),
+            _ => None,
+        };
+
+        if pattern.is_some() || format.is_some() {
+            for _ in 0 .. ATTEMPTS {
+                let s = match &pattern {
+                    Some(p) => {
+                        let mut s = String::new();
+                        self.pattern(p, &mut s);
+                        s
+                    },
+                    None => self.format(format.unwrap_or_default()),
+                };
+                if fits(&s) {
+                    return Ok(Json::String(s));
+                }
+            }
+            return schema_error(path, "no string of the pattern or format within minLength and maxLength");
+        }
+
+        let (l, h) = self.c.string_len;
+        let min = min.unwrap_or(l.min(max.unwrap_or(l)));
+        let max = max.unwrap_or(h.max(min));
+        let n = self.rng.gen_range(min ..= max);
+        let mut s = String::with_capacity(n);
+        for _ in 0 .. n {
+            s.push(random_char(self.rng, self.c.alphabet));
+        }
+        Ok(Json::String(s))
+    }
+
+    fn format(&mut self, f: &str) -> String {
+        let r = &mut *self.rng;
+        let date = |r: &mut R| format!("{:04}-{:02}-{:02}",
+                                       r.gen_range(1970 ..= 2099), r.gen_range(1 ..= 12), r.gen_range(1 ..= 28));
+        let time = |r: &mut R| format!("{:02}:{:02}:{:02}Z",
+                                       r.gen_range(0 .. 24), r.gen_range(0 .. 60), r.gen_range(0 .. 60));
+        let word = |r: &mut R, n: usize| -> String {
+            (0 .. r.gen_range(1 ..= n)).map(|_| r.gen_range(b'a' ..= b'z') as char).collect()
+        };
+        match f {
+            "date-time" => format!("{}T{}", date(r), time(r)),
+            "date" => date(r),
+            "time" => time(r),
+            "email" => format!("{}@{}.{}", word(r, 10), word(r, 10), word(r, 3)),
+            "uuid" => format!("{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+                              r.gen::<u32>(), r.gen::<u16>(), r.gen::<u16>() & 0xfff,
+                              0x8000 | (r.gen::<u16>() & 0x3fff), r.gen::<u64>() & 0xffff_ffff_ffff),
+            "ipv4" => format!("{}.{}.{}.{}", r.gen::<u8>(), r.gen::<u8>(), r.gen::<u8>(), r.gen::<u8>()),
+            "uri" => format!("https://{}.{}/{}", word(r, 10), word(r, 3), word(r, 10)),
+            _ => panic!("internal error: unknown format {}", f), // cannot happen
+        }
+    }
+
+    fn pattern(&mut self, p: &Pattern, s: &mut String) {
+        for n in p {
+            self.pattern_node(n, s);
+        }
+    }
+
+    fn pattern_node(&mut self, n: &PatternNode, s: &mut String) {
+        match n {
+            PatternNode::Char(c) => s.push(*c),
+            PatternNode::Class(rs) => {
+                let total: u32 = rs.iter().map(|(a, b)| *b as u32 - *a as u32 + 1).sum();
+                let mut x = self.rng.gen_range(0 .. total);
+                for (a, b) in rs {
+                    let w = *b as u32 - *a as u32 + 1;
+                    if x < w {
+                        s.push(char::from_u32(*a as u32 + x).unwrap_or(*a));
+                        break;
+                    }
+                    x -= w;
+                }
+            },
+            PatternNode::Group(alts) => {
+                let i = self.rng.gen_range(0 .. alts.len());
+                self.pattern(&alts[i], s);
+            },
+            PatternNode::Repeat(n, min, max) => {
+                let k = self.rng.gen_range(*min ..= *max);
+                for _ in 0 .. k {
+                    self.pattern_node(n, s);
+                }
+            },
+        }
+    }
+
+    fn array(&mut self, m: &'a HashMap<String, Json>, path: &str, level: usize) -> Result<Json, SchemaError> {
+        // the tuple in 'prefixItems' (2020-12) or 'items' (earlier drafts)
+        // and the schema of the remaining items
+        let (tuple, tpath, rest, rpath) = match (m.get("prefixItems"), m.get("items")) {
+            (Some(Json::Array(t)), r) => (&t[..], "prefixItems", r, "items"),
+            (None, Some(Json::Array(t))) => (&t[..], "items", m.get("additionalItems"), "additionalItems"),
+            (None, r) => (&[][..], "", r, "items"),
+            (Some(_), _) => return schema_error(path, "prefixItems must be an array"),
+        };
+        let open = !matches!(rest, Some(Json::Boolean(false)));
+
+        let min = get_usize(m, "minItems").unwrap_or(0);
+        let max = match get_usize(m, "maxItems") {
+            Some(h) => h,
+            None if !open => tuple.len(),
+            None => min.max(tuple.len()).max(self.c.array_len),
+        };
+        if min > max || (!open && min > tuple.len()) {
+            return schema_error(path, "no array within minItems and maxItems");
+        }
+
+        // beyond the configured depth, arrays are as short as possible
+        let n = if level < self.c.depth {
+            self.rng.gen_range(min ..= max)
+        } else {
+            min
+        };
+        let unique = matches!(m.get("uniqueItems"), Some(Json::Boolean(true)));
+
+        let mut v: Vec<Json> = Vec::with_capacity(n);
+        for i in 0 .. n {
+            let (s, p) = match tuple.get(i) {
+                Some(s) => (s, format!("{}/{}/{}", path, tpath, i)),
+                None => (rest.unwrap_or(&Json::Boolean(true)), format!("{}/{}", path, rpath)),
+            };
+            let mut j = self.value(s, &p, level + 1)?;
+            let mut k = 0;
+            while unique && v.contains(&j) {
+                k += 1;
+                if k == ATTEMPTS {
+                    return schema_error(&p, "cannot generate unique items");
+                }
+                j = self.value(s, &p, level + 1)?;
+            }
+            v.push(j);
+        }
+        Ok(Json::Array(v))
+    }
+
+    fn object(&mut self, m: &'a HashMap<String, Json>, path: &str, level: usize) -> Result<Json, SchemaError> {
+        let props: Option<&'a HashMap<String, Json>> = match m.get("properties") {
+            Some(Json::Object(p)) => Some(p),
+            Some(_) => return schema_error(path, "properties must be an object"),
+            None => None,
+        };
+        let required: Vec<&str> = match m.get("required") {
+            Some(Json::Array(v)) => v.iter().filter_map(|r| match r {
+                Json::String(r) => Some(r.as_str()),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+        let extra = m.get("additionalProperties");
+        let open = !matches!(extra, Some(Json::Boolean(false)));
+
+        let min = get_usize(m, "minProperties").unwrap_or(0);
+        let max = get_usize(m, "maxProperties").unwrap_or(usize::MAX);
+        if required.len() > max || min > max {
+            return schema_error(path, "no object within minProperties and maxProperties");
+        }
+
+        // the required properties and a random selection of the optional ones;
+        // beyond the configured depth, objects are as small as possible
+        let mut names: Vec<&str> = required.clone();
+        let mut optional: Vec<&str> = props.into_iter()
+                                           .flat_map(|p| p.keys())
+                                           .map(|k| k.as_str())
+                                           .filter(|k| !required.contains(k))
+                                           .collect();
+        optional.sort();
+        let mut rest = Vec::new();
+        for k in optional {
+            if names.len() < max && level < self.c.depth && self.rng.gen::<bool>() {
+                names.push(k);
+            } else {
+                rest.push(k);
+            }
+        }
+        while names.len() < min && !rest.is_empty() {
+            names.push(rest.remove(0));
+        }
+
+        // the number of additional properties
+        let mut n = if open && level < self.c.depth && matches!(extra, Some(Json::Object(_))) {
+            self.rng.gen_range(0 ..= self.c.object_len)
+        } else {
+            0
+        };
+        if names.len() + n < min {
+            if !open {
+                return schema_error(path, "no object within minProperties and maxProperties");
+            }
+            n = min - names.len();
+        }
+        let n = n.min(max - names.len());
+
+        let mut o = HashMap::new();
+        for k in names {
+            let (s, p) = match props.and_then(|p| p.get(k)) {
+                Some(s) => (s, format!("{}/properties/{}", path, k.replace('~', "~0").replace('/', "~1"))),
+                None => (extra.unwrap_or(&Json::Boolean(true)), format!("{}/additionalProperties", path)),
+            };
+            let j = self.value(s, &p, level + 1)?;
+            o.insert(k.to_string(), j);
+        }
+
+        // additional properties with names that are not taken
+        let want = o.len() + n;
+        let c = self.c.set_string_len(self.c.string_len.0.max(1), self.c.string_len.1);
+        let mut k = 0;
+        while o.len() < want {
+            k += 1;
+            if k > ATTEMPTS * n {
+                return schema_error(path, "cannot generate enough property names");
+            }
+            let key = match m.get("propertyNames") {
+                Some(s) => match self.value(s, &format!("{}/propertyNames", path), level + 1)? {
+                    Json::String(key) => key,
+                    _ => return schema_error(path, "propertyNames must describe strings"),
+                },
+                None => random_string(self.rng, &c),
+            };
+            if o.contains_key(&key) || props.is_some_and(|p| p.contains_key(&key)) {
+                continue;
+            }
+            let j = self.value(extra.unwrap_or(&Json::Boolean(true)), &format!("{}/additionalProperties", path), level + 1)?;
+            o.insert(key, j);
+        }
+
+        Ok(Json::Object(Box::new(o)))
+    }
+}
+
+// The type a schema without 'type' describes, judging by its keywords.
+fn infer_type(m: &HashMap<String, Json>) -> &'static str {
+    let has = |ks: &[&str]| ks.iter().any(|k| m.contains_key(*k));
+    if has(&["properties", "required", "additionalProperties", "propertyNames", "minProperties", "maxProperties"]) {
+        "object"
+    } else if has(&["items", "prefixItems", "additionalItems", "minItems", "maxItems", "uniqueItems"]) {
+        "array"
+    } else if has(&["minLength", "maxLength", "pattern", "format"]) {
+        "string"
+    } else if has(&["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf"]) {
+        "number"
+    } else {
+        "any"
+    }
+}
+
+const FORMATS: [&str; 7] = ["date-time", "date", "time", "email", "uuid", "ipv4", "uri"];
+
+// A regular expression reduced to what is needed to generate matching strings.
+type Pattern = Vec<PatternNode>;
+
+enum PatternNode {
+    Char(char),
+    Class(Vec<(char, char)>),
+    Group(Vec<Pattern>),
+    Repeat(Box<PatternNode>, usize, usize),
+}
+
+fn parse_pattern(p: &str) -> Result<Pattern, String> {
+    let cs: Vec<char> = p.chars().collect();
+    let mut i = 0;
+    let alts = parse_alternatives(&cs, &mut i)?;
+    if i < cs.len() {
+        return Err(format!("unbalanced ')' at {}", i));
+    }
+    Ok(vec![PatternNode::Group(alts)])
+}
+
+fn parse_alternatives(cs: &[char], i: &mut usize) -> Result<Vec<Pattern>, String> {
+    let mut alts = vec![Vec::new()];
+    while *i < cs.len() {
+        let c = cs[*i];
+        *i += 1;
+        let n = match c {
+            ')' => {
+                *i -= 1;
+                break;
+            },
+            '|' => {
+                alts.push(Vec::new());
+                continue;
+            },
+            // the pattern is generated as a whole, anchors are implied
+            '^' | '$' => continue,
+            '(' => {
+                if cs.get(*i) == Some(&'?') {
+                    if cs.get(*i + 1) != Some(&':') {
+                        return Err(format!("unsupported group at {}", *i - 1));
+                    }
+                    *i += 2;
+                }
+                let g = parse_alternatives(cs, i)?;
+                if cs.get(*i) != Some(&')') {
+                    return Err("missing ')'".to_string());
+                }
+                *i += 1;
+                PatternNode::Group(g)
+            },
+            '[' => parse_class(cs, i)?,
+            '.' => PatternNode::Class(vec![(' ', '~')]),
+            '\\' => parse_escape(cs, i)?,
+            '*' | '+' | '?' | '{' => return Err(format!("nothing to repeat at {}", *i - 1)),
+            c => PatternNode::Char(c),
+        };
+        let n = parse_quantifier(cs, i, n)?;
+        if let Some(a) = alts.last_mut() {
+            a.push(n);
+        }
+    }
+    Ok(alts)
+}
+
+fn parse_quantifier(cs: &[char], i: &mut usize, n: PatternNode) -> Result<PatternNode, String> {
+    let (min, max) = match cs.get(*i) {
+        Some('*') => (0, MAX_REPEAT),
+        Some('+') => (1, 1 + MAX_REPEAT),
+        Some('?') => (0, 1),
+        Some('{') => {
+            let end = match cs[*i ..].iter().position(|c| *c == '}') {
+                Some(e) => *i + e,
+                None => return Err("missing '}'".to_string()),
+            };
+            let q: String = cs[*i + 1 .. end].iter().collect();
+            let bad = || format!("invalid quantifier {{{}}}", q);
+            let (l, h) = match q.split_once(',') {
+                None => {
+                    let l = q.trim().parse::<usize>().map_err(|_| bad())?;
+                    (l, l)
+                },
+                Some((l, "")) => {
+                    let l = l.trim().parse::<usize>().map_err(|_| bad())?;
+                    (l, l + MAX_REPEAT)
+                },
+                Some((l, h)) => (l.trim().parse::<usize>().map_err(|_| bad())?,
+                                 h.trim().parse::<usize>().map_err(|_| bad())?),
+            };
+            if l > h {
+                return Err(bad());
+            }
+            *i = end;
+            (l, h)
+        },
+        _ => return Ok(n),
+    };
+    *i += 1;
+    // lazy quantifiers generate the same strings
+    if cs.get(*i) == Some(&'?') {
+        *i += 1;
+    }
+    Ok(PatternNode::Repeat(Box::new(n), min, max))
+}
+
+// character classes of escapes like \d
+fn class_escape(c: char) -> Option<Vec<(char, char)>> {
+    match c {
+        'd' => Some(vec![('0', '9')]),
+        'w' => Some(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+        's' => Some(vec![(' ', ' '), ('\t', '\t')]),
+        'D' => Some(vec![('a', 'z'), ('A', 'Z')]),
+        'W' => Some(vec![(' ', '/')]),
+        'S' => Some(vec![('!', '~')]),
+        _ => None,
+    }
+}
+
+fn escaped_char(cs: &[char], i: &mut usize) -> Result<char, String> {
+    let c = match cs.get(*i) {
+        Some(c) => *c,
+        None => return Err("incomplete escape".to_string()),
+    };
+    *i += 1;
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        'f' => Ok('\x0c'),
+        'v' => Ok('\x0b'),
+        'u' if *i + 4 <= cs.len() => {
+            let h: String = cs[*i .. *i + 4].iter().collect();
+            *i += 4;
+            u32::from_str_radix(&h, 16).ok()
+                .and_then(char::from_u32)
+                .ok_or(format!("invalid escape \\u{}", h))
+        },
+        c if c.is_ascii_alphanumeric() => Err(format!("unsupported escape \\{}", c)),
+        c => Ok(c),
+    }
+}
+
+fn parse_escape(cs: &[char], i: &mut usize) -> Result<PatternNode, String> {
+    if let Some(rs) = cs.get(*i).and_then(|c| class_escape(*c)) {
+        *i += 1;
+        return Ok(PatternNode::Class(rs));
+    }
+    escaped_char(cs, i).map(PatternNode::Char)
+}
+
+fn parse_class(cs: &[char], i: &mut usize) -> Result<PatternNode, String> {
+    if cs.get(*i) == Some(&'^') {
+        return Err("unsupported negated character class".to_string());
+    }
+    let mut rs = Vec::new();
+    let mut first = true;
+    loop {
+        let c = match cs.get(*i) {
+            Some(c) => *c,
+            None => return Err("missing ']'".to_string()),
+        };
+        *i += 1;
+        let a = match c {
+            ']' if !first => break,
+            '\\' => match cs.get(*i).and_then(|c| class_escape(*c)) {
+                Some(v) => {
+                    *i += 1;
+                    rs.extend(v);
+                    first = false;
+                    continue;
+                },
+                None => escaped_char(cs, i)?,
+            },
+            c => c,
+        };
+        first = false;
+        // a range unless '-' is the last character of the class
+        if cs.get(*i) == Some(&'-') && cs.get(*i + 1).is_some_and(|c| *c != ']') {
+            *i += 1;
+            let b = match cs[*i] {
+                '\\' => {
+                    *i += 1;
+                    escaped_char(cs, i)?
+                },
+                b => {
+                    *i += 1;
+                    b
+                },
+            };
+            if b < a {
+                return Err(format!("invalid range {}-{}", a, b));
+            }
+            rs.push((a, b));
+        } else {
+            rs.push((a, a));
+        }
+    }
+    Ok(PatternNode::Class(rs))
+}
+
+/// A targeted damage done to a serialized Json document by `mutate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// The document is cut off, possibly to nothing.
+    Truncation,
+    /// An opening or closing bracket is replaced by the other kind,
+    /// e.g. `[1}` or `{"a": 1]`.
+    BracketMismatch,
+    /// A string gets an invalid escape sequence like `\q` or `\u12g4`.
+    BadEscape,
+    /// A comma is inserted after an opening bracket, before a closing bracket,
+    /// next to another comma or before or after the whole document.
+    StrayComma,
+    /// A string gets a byte sequence that is not valid UTF-8,
+    /// e.g. a lone continuation byte, an overlong encoding or an encoded surrogate.
+    InvalidUtf8,
+    /// A number gets superfluous parts: leading zeros, a second fraction or exponent,
+    /// a dangling point, exponent or sign; its digits may go on for hundreds of places.
+    OverlongNumber,
+}
+
+impl Mutation {
+    /// All mutations.
+    pub const ALL: [Mutation; 6] = [
+        Mutation::Truncation,
+        Mutation::BracketMismatch,
+        Mutation::BadEscape,
+        Mutation::StrayComma,
+        Mutation::InvalidUtf8,
+        Mutation::OverlongNumber,
+    ];
+}
+
+/// The class of error a malformed document is expected to cause
+/// when it is parsed as a complete document, e.g. with `Json::from_slice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The document ends before the value is complete.
+    Eof,
+    /// A byte that cannot appear at its position,
+    /// i.e. the parser expected another token.
+    UnexpectedToken,
+    /// An escape sequence in a string is unknown or incomplete.
+    InvalidEscape,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// The document continues after a complete value.
+    TrailingCharacters,
+}
+
+impl ErrorClass {
+    /// Returns true if error 'e' belongs to this class.
+    pub fn matches(&self, e: &error::Error) -> bool {
+        let p = match e {
+            error::Error::Parse(p) => p,
+            error::Error::Io(_) | error::Error::TooLarge(_) => return false,
+        };
+        match self {
+            ErrorClass::Eof => p.is_eof(),
+            ErrorClass::UnexpectedToken => p.is_expected_token(),
+            ErrorClass::InvalidEscape => p.is_error_type("unknown escape sequence")
+                                      || p.is_error_type("hexadecimal expected"),
+            ErrorClass::InvalidUtf8 => p.is_utf8_error(),
+            ErrorClass::TrailingCharacters => p.is_error_type("trailing characters"),
+        }
+    }
+}
+
+/// A malformed Json document made by `mutate` or `make_malformed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Malformed {
+    /// The document.
+    pub text: Vec<u8>,
+    /// The mutation applied to the serialized value.
+    pub mutation: Mutation,
+    /// The byte offset in 'text' where the mutation was applied.
+    pub offset: usize,
+    /// The error class parsing 'text' is expected to cause.
+    pub expected: ErrorClass,
+}
+
+/// Serializes Json value 'j' and applies mutation 'm' to the text
+/// at a random position drawn from 'rng'.
+/// Values other than arrays and objects are wrapped into an array first,
+/// so that every mutation yields an invalid document.
+/// Returns `None` if the mutation does not apply,
+/// e.g. `BadEscape` to a value without strings.
+///
+/// Numbers that are just long or beyond the range of `f64` are valid Json
+/// (the parser rounds them, possibly to infinity),
+/// so `OverlongNumber` always adds a part that breaks the syntax.
+///
+/// Example:
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use jsosso::{json, Json};
+/// use jsosso::arbitrary::*;
+///
+/// let mut rng = StdRng::seed_from_u64(7);
+/// let j = json!({"name": "jsosso", "tags": [1, 2.5, true]});
+///
+/// for m in Mutation::ALL {
+///     let d = mutate(&mut rng, &j, m).unwrap();
+///     assert!(match Json::from_slice(&d.text) {
+///         Err(e) => d.expected.matches(&e),
+///         Ok(v) => panic!("unexpected value: {:?}", v),
+///     }, "{:?}: {}", m, String::from_utf8_lossy(&d.text));
+/// }
+///
+/// assert!(mutate(&mut rng, &json!([1, 2]), Mutation::BadEscape).is_none());
+/// ```
+pub fn mutate<R: Rng + ?Sized>(rng: &mut R, j: &Json, m: Mutation) -> Option<Malformed> {
+    let o = SerializeOpts::default().set_compact(rng.gen::<bool>());
+    let t = match j {
+        Json::Array(_) | Json::Object(_) => j.serialize_to_vec(&o),
+        _ => Json::Array(vec![j.clone()]).serialize_to_vec(&o),
+    };
+    let k = Landmarks::scan(&t);
+
+    let pick = |rng: &mut R, v: &[usize]| -> Option<usize> {
+        if v.is_empty() { None } else { Some(v[rng.gen_range(0 .. v.len())]) }
+    };
+    let splice = |at: usize, del: usize, ins: &[u8]| -> Vec<u8> {
+        let mut v = t[.. at].to_vec();
+        v.extend_from_slice(ins);
+        v.extend_from_slice(&t[at + del ..]);
+        v
+    };
+
+    let (text, offset, expected) = match m {
+        Mutation::Truncation => {
+            // at character boundaries; cuts within characters make UTF-8 errors
+            // and cuts within escape sequences make escape errors
+            // before trailing whitespace, which is not part of the value
+            let end = t.len() - t.iter().rev().take_while(|b| b.is_ascii_whitespace()).count();
+            let mut at = rng.gen_range(0 .. end);
+            while at > 0 && (t[at] & 0xc0) == 0x80 {
+                at -= 1;
+            }
+            if let Some((s, _)) = k.escapes.iter().find(|(s, e)| *s < at && at < *e) {
+                at = *s;
+            }
+            (t[.. at].to_vec(), at, ErrorClass::Eof)
+        },
+        Mutation::BracketMismatch => {
+            let at = pick(rng, &k.brackets)?;
+            let b = match t[at] {
+                b'[' => b'{',
+                b'{' => b'[',
+                b']' => b'}',
+                _ => b']',
+            };
+            (splice(at, 1, &[b]), at, ErrorClass::UnexpectedToken)
+        },
+        Mutation::BadEscape => {
+            const ESCAPES: [&[u8]; 10] = [
+                b"\\q", b"\\x41", b"\\U0041", b"\\a", b"\\0", b"\\'", b"\\ug", b"\\u1g", b"\\u12g4", b"\\uzzzz",
+            ];
+            let at = pick(rng, &k.strings)? + 1;
+            let e = ESCAPES[rng.gen_range(0 .. ESCAPES.len())];
+            (splice(at, 0, e), at, ErrorClass::InvalidEscape)
+        },
+        Mutation::StrayComma => {
+            let mut v = k.brackets.iter().map(|b| if t[*b] == b'[' || t[*b] == b'{' { *b + 1 } else { *b }).collect::<Vec<_>>();
+            v.extend(k.commas.iter());
+            v.push(0);
+            v.push(t.len());
+            let at = pick(rng, &v)?;
+            let expected = if at == t.len() { ErrorClass::TrailingCharacters } else { ErrorClass::UnexpectedToken };
+            (splice(at, 0, b","), at, expected)
+        },
+        Mutation::InvalidUtf8 => {
+            const BYTES: [&[u8]; 8] = [
+                b"\xff", b"\x80", b"\xc3\x28", b"\xc0\xaf", b"\xe0\x80\xaf", b"\xed\xa0\x80", b"\xe2\x82", b"\xf4\x90\x80\x80",
+            ];
+            let at = pick(rng, &k.strings)? + 1;
+            let e = BYTES[rng.gen_range(0 .. BYTES.len())];
+            (splice(at, 0, e), at, ErrorClass::InvalidUtf8)
+        },
+        Mutation::OverlongNumber => {
+            let i = pick(rng, &(0 .. k.numbers.len()).collect::<Vec<_>>())?;
+            let (at, end) = k.numbers[i];
+            let digits = |rng: &mut R| -> String {
+                (0 .. rng.gen_range(1 ..= 300)).map(|_| rng.gen_range(b'0' ..= b'9') as char).collect()
+            };
+            let n = match rng.gen_range(0 .. 7) {
+                0 => format!("00{}", digits(rng)),
+                1 => format!("1{}.{}.{}", digits(rng), digits(rng), digits(rng)),
+                2 => format!("1.{}e{}e{}", digits(rng), digits(rng), digits(rng)),
+                3 => format!("{}.", digits(rng)),
+                4 => format!("1{}e", digits(rng)),
+                5 => format!("+{}", digits(rng)),
+                _ => format!("1{}-{}", digits(rng), digits(rng)),
+            };
+            (splice(at, end - at, n.as_bytes()), at, ErrorClass::UnexpectedToken)
+        },
+    };
+
+    Some(Malformed {
+        text,
+        mutation: m,
+        offset,
+        expected,
+    })
+}
+
+/// Generates a random value with at most 'n' elements according to configuration 'c'
+/// and applies a random mutation that applies to it.
+///
+/// Example:
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use jsosso::Json;
+/// use jsosso::arbitrary::*;
+///
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let c = GeneratorConfig::stress();
+/// for _ in 0 .. 100 {
+///     let d = make_malformed(&mut rng, 20, &c);
+///     assert!(matches!(Json::from_slice(&d.text), Err(e) if d.expected.matches(&e)), "{:?}", d);
+/// }
+/// ```
+pub fn make_malformed<R: Rng + ?Sized>(rng: &mut R, n: usize, c: &GeneratorConfig) -> Malformed {
+    let j = make_n_arbitrary_with_config(rng, n, c);
+    let mut ms = Mutation::ALL.to_vec();
+    loop {
+        let m = ms.swap_remove(rng.gen_range(0 .. ms.len()));
+        if let Some(d) = mutate(rng, &j, m) {
+            return d;
+        }
+        if ms.is_empty() {
+            panic!("internal error: no mutation applies to {:?}", j); // cannot happen
+        }
+    }
+}
+
+/// Like `make_malformed` but the value is reproducible from 'seed'.
+/// The text may still differ between runs,
+/// since object members are serialized in the order of the map.
+pub fn make_malformed_seeded(n: usize, seed: u64, c: &GeneratorConfig) -> Malformed {
+    make_malformed(&mut StdRng::seed_from_u64(seed), n, c)
+}
+
+// Positions in a serialized Json document.
+struct Landmarks {
+    // opening quotes
+    strings: Vec<usize>,
+    // brackets outside of strings
+    brackets: Vec<usize>,
+    // commas outside of strings
+    commas: Vec<usize>,
+    // start and end of numbers
+    numbers: Vec<(usize, usize)>,
+    // start and end of escape sequences
+    escapes: Vec<(usize, usize)>,
+}
+
+impl Landmarks {
+    fn scan(t: &[u8]) -> Landmarks {
+        let mut k = Landmarks {
+            strings: Vec::new(),
+            brackets: Vec::new(),
+            commas: Vec::new(),
+            numbers: Vec::new(),
+            escapes: Vec::new(),
+        };
+        let mut string = false;
+        let mut escape = false;
+        let mut number: Option<usize> = None;
+        for (i, b) in t.iter().enumerate() {
+            if string {
+                match b {
+                    _ if escape => escape = false,
+                    b'\\' => {
+                        escape = true;
+                        let n = if t.get(i + 1) == Some(&b'u') { 6 } else { 2 };
+                        k.escapes.push((i, i + n));
+                    },
+                    b'"' => string = false,
+                    _ => (),
+                }
+                continue;
+            }
+            match (b, number) {
+                (b'-' | b'0' ..= b'9' | b'.' | b'e' | b'E' | b'+', Some(_)) => continue,
+                (_, Some(s)) => {
+                    k.numbers.push((s, i));
+                    number = None;
+                },
+                _ => (),
+            }
+            match b {
+                b'"' => {
+                    string = true;
+                    k.strings.push(i);
+                },
+                b'[' | b']' | b'{' | b'}' => k.brackets.push(i),
+                b',' => k.commas.push(i),
+                b'-' | b'0' ..= b'9' => number = Some(i),
+                _ => (),
+            }
+        }
+        if let Some(s) = number {
+            k.numbers.push((s, t.len()));
+        }
+        k
+    }
+}
+
+/// The shape of the documents made by `make_sized`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// A flat object with many members whose values are short scalars.
+    Wide,
+    /// Arrays and objects nested into each other, up to `DEEP_LEVELS` levels,
+    /// each with a few scalar siblings.
+    Deep,
+    /// An array of numbers of all kinds.
+    Numbers,
+    /// An array of strings with escapes and non-ASCII characters.
+    Strings,
+}
+
+impl Profile {
+    /// All profiles.
+    pub const ALL: [Profile; 4] = [
+        Profile::Wide,
+        Profile::Deep,
+        Profile::Numbers,
+        Profile::Strings,
+    ];
+
+    /// The name of the profile, used in the file names of `write_corpus`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Wide => "wide",
+            Profile::Deep => "deep",
+            Profile::Numbers => "numbers",
+            Profile::Strings => "strings",
+        }
+    }
+}
+
+/// The maximum nesting of documents with `Profile::Deep`.
+/// The parser is recursive, so much deeper documents would exhaust the stack.
+pub const DEEP_LEVELS: usize = 256;
+
+/// The smallest size for which `make_sized` hits the size exactly.
+pub const SIZED_MIN: usize = 16;
+
+// room for the string that fills up a document
+const PAD: usize = 8;
+
+/// Generates a random Json value with profile 'p' that has exactly 'bytes' bytes
+/// when serialized compactly (see `SerializeOpts::set_compact`).
+/// Below `SIZED_MIN` bytes, the result is an empty array or object.
+///
+/// The elements are generated until the next one would not fit;
+/// the remaining bytes are filled with a string of ASCII letters.
+///
+/// Example:
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use jsosso::Json;
+/// use jsosso::arbitrary::*;
+/// use jsosso::serializing::SerializeOpts;
+///
+/// let mut rng = StdRng::seed_from_u64(3);
+/// for p in Profile::ALL {
+///     let j = make_sized(&mut rng, 10_000, p);
+///     let v = j.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+///     assert_eq!(v.len(), 10_000);
+///     assert_eq!(Json::from_slice(&v).unwrap(), j);
+/// }
+/// ```
+pub fn make_sized<R: Rng + ?Sized>(rng: &mut R, bytes: usize, p: Profile) -> Json {
+    if bytes < SIZED_MIN {
+        return match p {
+            Profile::Wide => Json::Object(Box::default()),
+            _ => Json::Array(Vec::new()),
+        };
+    }
+    match p {
+        Profile::Wide => sized_object(rng, bytes),
+        Profile::Deep => sized_deep(rng, bytes),
+        Profile::Numbers => sized_array(rng, bytes, |rng| Json::Number(random_mixed(rng))),
+        Profile::Strings => sized_array(rng, bytes, |rng| {
+            const ALPHABETS: [Alphabet; 3] = [Alphabet::Ascii, Alphabet::Escapes, Alphabet::Unicode];
+            let c = GeneratorConfig::default()
+                   .set_alphabet(ALPHABETS[rng.gen_range(0 .. ALPHABETS.len())])
+                   .set_string_len(0, 48);
+            Json::String(random_string(rng, &c))
+        }),
+    }
+}
+
+/// Like `make_sized` but the value is reproducible from 'seed'.
+pub fn make_sized_seeded(bytes: usize, seed: u64, p: Profile) -> Json {
+    make_sized(&mut StdRng::seed_from_u64(seed), bytes, p)
+}
+
+/// Writes a document made by `make_sized` for each of 'sizes' and 'profiles'
+/// to directory 'dir', which is created if it does not exist.
+/// The files are named after profile and size, e.g. `wide-1048576.json`,
+/// and contain the compact serialization of the documents,
+/// so they have exactly the requested size.
+/// Object members are sorted by key,
+/// so the files are reproducible byte for byte from 'seed'.
+/// Returns the paths of the files in the order they were written.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::arbitrary::*;
+///
+/// let dir = std::env::temp_dir().join(format!("jsosso-corpus-{}", std::process::id()));
+/// let fs = write_corpus(&dir, &[1000, 5000], &Profile::ALL, 42).unwrap();
+///
+/// assert_eq!(fs.len(), 8);
+/// assert_eq!(fs[0].file_name().unwrap(), "wide-1000.json");
+/// for f in fs {
+///     assert!(Json::from_file(&f).is_ok());
+/// }
+/// std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn write_corpus<P: AsRef<Path>>(dir: P, sizes: &[usize], profiles: &[Profile], seed: u64) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut v = Vec::new();
+    for p in profiles {
+        for bytes in sizes {
+            let j = make_sized(&mut rng, *bytes, *p);
+            let f = dir.join(format!("{}-{}.json", p.name(), bytes));
+            let mut w = io::BufWriter::new(fs::File::create(&f)?);
+            j.serialize_with_opts(&mut w, &SerializeOpts::default()
+                                          .set_compact(true)
+                                          .set_sorted_keys(true))?;
+            w.flush()?;
+            v.push(f);
+        }
+    }
+    Ok(v)
+}
+
+fn compact_len(j: &Json) -> usize {
+    j.serialize_to_vec(&SerializeOpts::default().set_compact(true)).len()
+}
+
+// short scalars like in records
+fn make_scalar<R: Rng + ?Sized>(rng: &mut R) -> Json {
+    match rng.gen_range(0 .. 8) {
+        0 => Json::Null,
+        1 => make_boolean(rng),
+        2 | 3 => Json::Number(random_integer(rng)),
+        4 => Json::Number(rng.gen_range(-1000.0 .. 1000.0)),
+        _ => {
+            let c = GeneratorConfig::default()
+                   .set_alphabet(Alphabet::Ascii)
+                   .set_string_len(0, 24);
+            Json::String(random_string(rng, &c))
+        },
+    }
+}
+
+// a string of 'n' ASCII letters
+fn padding<R: Rng + ?Sized>(rng: &mut R, n: usize) -> Json {
+    Json::String((0 .. n).map(|_| rng.gen_range(b'a' ..= b'z') as char).collect())
+}
+
+// keys of 'Simple' characters, which are neither 'z' nor '~' used below
+fn random_key<R: Rng + ?Sized>(rng: &mut R) -> String {
+    random_string(rng, &GeneratorConfig::default().set_string_len(1, 12))
+}
+
+// Adds scalars to 'v' while the size stays within 'target'.
+fn add_elements<R, F>(rng: &mut R, v: &mut Vec<Json>, have: &mut usize, target: usize, f: F)
+    where R: Rng + ?Sized,
+          F: Fn(&mut R) -> Json,
+{
+    loop {
+        let j = f(rng);
+        let l = compact_len(&j) + usize::from(!v.is_empty());
+        if *have + l > target {
+            return;
+        }
+        *have += l;
+        v.push(j);
+    }
+}
+
+// Adds members with scalar values to 'm' while the size stays within 'target'.
+fn add_members<R: Rng + ?Sized>(rng: &mut R, m: &mut HashMap<String, Json>, have: &mut usize, target: usize) {
+    loop {
+        let k = random_key(rng);
+        if m.contains_key(&k) {
+            continue;
+        }
+        let j = make_scalar(rng);
+        let l = k.len() + 3 + compact_len(&j) + usize::from(!m.is_empty());
+        if *have + l > target {
+            return;
+        }
+        *have += l;
+        m.insert(k, j);
+    }
+}
+
+fn sized_array<R, F>(rng: &mut R, bytes: usize, f: F) -> Json
+    where R: Rng + ?Sized,
+          F: Fn(&mut R) -> Json,
+{
+    let mut v = Vec::new();
+    let mut have = 2;
+    add_elements(rng, &mut v, &mut have, bytes - PAD, f);
+    let n = bytes - have - 2 - usize::from(!v.is_empty());
+    v.push(padding(rng, n));
+    Json::Array(v)
+}
+
+fn sized_object<R: Rng + ?Sized>(rng: &mut R, bytes: usize) -> Json {
+    let mut m = HashMap::new();
+    let mut have = 2;
+    add_members(rng, &mut m, &mut have, bytes - PAD);
+    // "~":"..."
+    let n = bytes - have - 6 - usize::from(!m.is_empty());
+    m.insert("~".to_string(), padding(rng, n));
+    Json::Object(Box::new(m))
+}
+
+// Nests from the inside out: every level gets its share of the bytes,
+// the outermost level gets the padding.
+fn sized_deep<R: Rng + ?Sized>(rng: &mut R, bytes: usize) -> Json {
+    let budget = bytes - PAD;
+    let levels = (budget / SIZED_MIN).clamp(1, DEEP_LEVELS);
+
+    let mut j = Json::Array(Vec::new());
+    let mut have = 2;
+    for i in 1 ..= levels {
+        let target = budget * i / levels;
+        j = if i % 2 == 0 {
+            // [..., child]
+            let mut v = Vec::new();
+            have += 2;
+            add_elements(rng, &mut v, &mut have, target.saturating_sub(1), make_scalar);
+            if !v.is_empty() {
+                have += 1;
+            }
+            v.push(j);
+            Json::Array(v)
+        } else {
+            // {..., "z": child}
+            let mut m = HashMap::new();
+            have += 6;
+            add_members(rng, &mut m, &mut have, target.saturating_sub(1));
+            if !m.is_empty() {
+                have += 1;
+            }
+            m.insert("z".to_string(), j);
+            Json::Object(Box::new(m))
+        };
+    }
 
+    match &mut j {
+        Json::Array(v) => {
+            let n = bytes - have - 3;
+            v.push(padding(rng, n));
+        },
+        Json::Object(m) => {
+            let n = bytes - have - 7;
+            m.insert("~".to_string(), padding(rng, n));
+        },
+        _ => panic!("internal error: deep document is not a container"), // cannot happen
+    }
+    j
+}
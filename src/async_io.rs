@@ -0,0 +1,192 @@
+use std::io;
+use std::str;
+use bytes::{BufMut, BytesMut};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+use super::*;
+use super::error;
+use super::parsing::{ParseOpts, parse_buffer_with, parse_complete_with_opts};
+use super::serializing::SerializeOpts;
+use super::push::{Delimiter, Step};
+
+/// Parses the next complete Json value from asynchronous reader 'r'.
+///
+/// The reader is read until the end of the value, which is then parsed
+/// as a whole. Bytes following the value remain in the reader,
+/// so that `parse_async` can be called again for the next value.
+/// Wrap an `AsyncRead` into a `tokio::io::BufReader` to parse from it.
+/// Numbers, booleans and null at the top level end with the first byte
+/// that cannot be part of them or with the end of the input.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::{Json, json};
+/// use jsosso::async_io::parse_async;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut input: &[u8] = b"{\"id\": 1} [true, null] 42";
+///
+/// assert_eq!(parse_async(&mut input).await.unwrap(), json!({"id": 1}));
+/// assert_eq!(parse_async(&mut input).await.unwrap(), json!([true, null]));
+/// assert_eq!(parse_async(&mut input).await.unwrap(), json!(42));
+/// assert!(parse_async(&mut input).await.unwrap_err().is_eof());
+/// # });
+/// ```
+pub async fn parse_async<R: AsyncBufRead + Unpin>(r: &mut R) -> error::Result<Json> {
+    parse_async_with_opts(r, &ParseOpts::default()).await
+}
+
+/// Like `parse_async`, but with explicit parser options 'o'.
+pub async fn parse_async_with_opts<R: AsyncBufRead + Unpin>(r: &mut R, o: &ParseOpts) -> error::Result<Json> {
+    let mut d = Delimiter::default();
+    let mut v = Vec::new();
+
+    while !d.is_done() {
+        let bs = r.fill_buf().await?;
+        if bs.is_empty() {
+            break; // the parser will tell whether the value is complete
+        }
+        let mut n = 0;
+        for b in bs {
+            match d.step(*b) {
+                Step::Skip => (),
+                Step::Take => v.push(*b),
+                Step::Stop => break,
+                Step::TakeStop => {
+                    v.push(*b);
+                    n += 1;
+                    break;
+                },
+            }
+            n += 1;
+        }
+        r.consume(n);
+    }
+
+    Ok(parse_buffer_with(&v, |s| parse_complete_with_opts(s, o))?)
+}
+
+/// Serializes Json value 'j' into asynchronous writer 'w'
+/// and returns the number of bytes written.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::json;
+/// use jsosso::async_io::serialize_async;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut v: Vec<u8> = Vec::new();
+/// serialize_async(&json!([1, 2]), &mut v).await.unwrap();
+/// assert_eq!(v, b"[\n  1, 2\n]\n");
+/// # });
+/// ```
+pub async fn serialize_async<W: AsyncWrite + Unpin>(j: &Json, w: &mut W) -> io::Result<usize> {
+    serialize_async_with_opts(j, w, &SerializeOpts::default()).await
+}
+
+/// Like `serialize_async`, but with explicit serializer options 'o'.
+pub async fn serialize_async_with_opts<W: AsyncWrite + Unpin>(j: &Json, w: &mut W, o: &SerializeOpts) -> io::Result<usize> {
+    let v = j.serialize_to_vec(o);
+    w.write_all(&v).await?;
+    w.flush().await?;
+    Ok(v.len())
+}
+
+/// The end-of-text character (ETX) that terminates messages
+/// in the protocol of the `stream2` example server.
+pub const ETX: u8 = 3;
+
+/// A message of the `stream2` protocol: a command name
+/// followed by two linebreaks and a Json payload.
+#[derive(Debug, PartialEq)]
+pub struct Command {
+    /// The name of the command.
+    pub name: String,
+    /// The payload of the command.
+    pub payload: Json,
+}
+
+/// Codec for the protocol of the `stream2` example server
+/// for use with `tokio_util::codec::Framed`, `FramedRead` or `FramedWrite`.
+/// Each message has the form `<command>\n\n<json><ETX>`.
+/// The payload is serialized compactly.
+///
+/// Example:
+///
+/// ```
+/// use bytes::BytesMut;
+/// use tokio_util::codec::{Decoder, Encoder};
+/// use jsosso::json;
+/// use jsosso::async_io::{EtxCodec, Command};
+///
+/// let mut codec = EtxCodec;
+/// let mut buf = BytesMut::new();
+/// codec.encode(Command { name: "Add".to_string(), payload: json!([1, 2]) }, &mut buf).unwrap();
+/// assert_eq!(&buf[..], b"Add\n\n[1,2]\x03");
+///
+/// let cmd = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(cmd.name, "Add");
+/// assert_eq!(cmd.payload, json!([1, 2]));
+/// assert!(buf.is_empty());
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EtxCodec;
+
+impl Decoder for EtxCodec {
+    type Item = Command;
+    type Error = error::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> error::Result<Option<Command>> {
+        let n = match src.iter().position(|b| *b == ETX) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        let frame = src.split_to(n + 1);
+        let frame = &frame[.. n];
+
+        let h = match frame.windows(2).position(|w| w == b"\n\n") {
+            Some(h) => h,
+            None => return Err(command_error(frame, "command expected")),
+        };
+        let name = match str::from_utf8(&frame[.. h]) {
+            Ok(s) => s.trim_start(),
+            Err(_) => return Err(command_error(frame, "utf8 error in command")),
+        };
+
+        Ok(Some(Command {
+            name: name.to_string(),
+            payload: Json::from_slice(&frame[h + 2 ..])?,
+        }))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> error::Result<Option<Command>> {
+        match self.decode(src)? {
+            Some(c) => Ok(Some(c)),
+            None if src.iter().all(|b| b.is_ascii_whitespace()) => Ok(None),
+            None => Err(command_error(src, "incomplete message")),
+        }
+    }
+}
+
+impl Encoder<Command> for EtxCodec {
+    type Error = error::Error;
+
+    fn encode(&mut self, cmd: Command, dst: &mut BytesMut) -> error::Result<()> {
+        let v = cmd.payload.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+        dst.reserve(cmd.name.len() + v.len() + 3);
+        dst.put_slice(cmd.name.as_bytes());
+        dst.put_slice(b"\n\n");
+        dst.put_slice(&v);
+        dst.put_u8(ETX);
+        Ok(())
+    }
+}
+
+fn command_error(frame: &[u8], msg: &str) -> error::Error {
+    match parse_buffer_with(frame, |s| s.fail(msg, Json::Null)) {
+        Err(e) => e.into(),
+        Ok(_) => panic!("internal error: failing parser succeeded"), // cannot happen
+    }
+}
@@ -2,13 +2,38 @@ use std::fs::File;
 use std::time::Instant;
 use std::ffi::OsString;
 use std::io::{self, Cursor};
+use std::env;
 use pacosso::{Stream, Opts};
-use jsosso::parsing::{parse};
-use jsosso::arbitrary::make_n_arbitrary;
+use jsosso::parsing::{parse, parse_complete, parse_many};
+use jsosso::arbitrary::{make_n_seeded, make_sized_seeded, write_corpus, Profile};
+use jsosso::serializing::SerializeOpts;
+use jsosso::ubjson;
+use jsosso::Json;
 
 const US: f64 = 1_000_000.0;
 
 fn main() {
+    // with a directory, we only write a corpus of generated documents;
+    // an optional seed reproduces a corpus written before
+    if let Some(dir) = env::args().nth(1) {
+        let seed = match env::args().nth(2) {
+            Some(s) => match s.parse::<u64>() {
+                Ok(n) => n,
+                Err(e) => panic!("invalid seed '{}': {:?}", s, e),
+            },
+            None => rand::random::<u64>(),
+        };
+        println!("seed: {}", seed);
+        let sizes = [1 << 10, 1 << 16, 1 << 20];
+        match write_corpus(&dir, &sizes, &Profile::ALL, seed) {
+            Ok(fs) => for f in fs {
+                println!("{}", f.display());
+            },
+            Err(e) => panic!("can't write corpus: {:?}", e),
+        }
+        return;
+    }
+
     let mut d: f64 = 0.0;
     let mut sz = 0;
 
@@ -50,7 +75,7 @@ fn main() {
     d /= 1000.0;
     println!("Duration stream (buf size: 8192): {:05}us", d as i64);
 
-    println!("");
+    println!();
     // oeis
     d = 0.0;
     for _ in 0 .. 1000 {
@@ -122,6 +147,118 @@ fn main() {
     d = x;
     let k = l as f64 / d;
     println!("Duration rand. (size: {:06}): {:05}us = {:05}MB/s", l, d as i64, k as i64);
+
+    println!();
+    // generated documents of 1MiB
+    for p in Profile::ALL {
+        let j = make_sized_seeded(1 << 20, 1, p);
+        let v = j.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+        d = 0.0;
+        for _ in 0 .. 10 {
+            let t = Instant::now();
+            let r = Json::from_slice(&v);
+            d += t.elapsed().as_secs_f64() * US;
+            assert!(matches!(r, Ok(ref r) if *r == j), "round trip failed for {}", p.name());
+        }
+        d /= 10.0;
+        let k = v.len() as f64 / d;
+        println!("Duration {:7} (size: {:06}): {:05}us = {:05}MB/s", p.name(), v.len(), d as i64, k as i64);
+    }
+
+    println!();
+    // serialization
+    let j = parse_file("rsc/test/arbitrary.json".into());
+    let o = SerializeOpts::default();
+    for (name, o) in [("ser.  ", o), ("compact", o.set_compact(true))] {
+        d = 0.0;
+        for _ in 0 .. 100 {
+            let (x, l) = run_serialize(&j, &o);
+            d += x;
+            sz = l;
+        }
+        d /= 100.0;
+        let k = sz as f64 / d;
+        println!("Duration {} (size: {:06}): {:05}us = {:05}MB/s", name, sz, d as i64, k as i64);
+
+        d = 0.0;
+        for _ in 0 .. 100 {
+            let t = Instant::now();
+            sz = j.serialize_to_vec(&o).len() as u64;
+            d += t.elapsed().as_secs_f64() * US;
+        }
+        d /= 100.0;
+        let k = sz as f64 / d;
+        println!("Duration to_vec  (size: {:06}): {:05}us = {:05}MB/s", sz, d as i64, k as i64);
+    }
+
+    println!();
+    // ubjson compared to compact text
+    let mut fs: Vec<_> = match std::fs::read_dir("rsc/test") {
+        Ok(d) => d.filter_map(|e| e.ok())
+                  .map(|e| e.path())
+                  .filter(|p| p.extension().is_some_and(|x| x == "json"))
+                  .collect(),
+        Err(e) => panic!("can't read directory: {:?}", e),
+    };
+    fs.sort();
+    for f in fs {
+        let j = parse_file(f.clone().into_os_string());
+        let name = f.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        compare_ubjson(&name, &j);
+    }
+}
+
+fn compare_ubjson(name: &str, j: &Json) {
+    let o = SerializeOpts::default().set_compact(true);
+    let txt = j.serialize_to_vec(&o);
+    let bin = ubjson::to_vec(j);
+    println!("Size {:9} text: {:06}, ubjson: {:06} = {:03}%",
+             name, txt.len(), bin.len(), bin.len() * 100 / txt.len().max(1));
+
+    let (mut et, mut eb, mut dt, mut db) = (0.0, 0.0, 0.0, 0.0);
+    for _ in 0 .. 100 {
+        let t = Instant::now();
+        let v = j.serialize_to_vec(&o);
+        et += t.elapsed().as_secs_f64() * US;
+        assert_eq!(v.len(), txt.len());
+
+        let t = Instant::now();
+        let v = ubjson::to_vec(j);
+        eb += t.elapsed().as_secs_f64() * US;
+        assert_eq!(v.len(), bin.len());
+
+        let t = Instant::now();
+        let r = Json::from_slice(&txt);
+        dt += t.elapsed().as_secs_f64() * US;
+        assert!(matches!(r, Ok(ref r) if r == j), "text round trip failed for {}", name);
+
+        let t = Instant::now();
+        let r = ubjson::from_slice(&bin);
+        db += t.elapsed().as_secs_f64() * US;
+        assert!(matches!(r, Ok(ref r) if r == j), "ubjson round trip failed for {}", name);
+    }
+    for (what, sz, d) in [("enc. text  ", txt.len(), et), ("enc. ubjson", bin.len(), eb),
+                          ("dec. text  ", txt.len(), dt), ("dec. ubjson", bin.len(), db)] {
+        let d = d / 100.0;
+        let k = sz as f64 / d;
+        println!("Duration {} (size: {:06}): {:05}us = {:05}MB/s", what, sz, d as i64, k as i64);
+    }
+}
+
+fn parse_file(f: OsString) -> Json {
+    match Json::from_file(&f) {
+        Ok(j) => j,
+        Err(e) => panic!("unexpected error: {} in {:?}", e, f),
+    }
+}
+
+fn run_serialize(j: &Json, o: &SerializeOpts) -> (f64, u64) {
+    let t = Instant::now();
+    let l = match j.serialize_with_opts(&mut io::sink(), o) {
+        Ok(l) => l,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    };
+    (t.elapsed().as_secs_f64() * US, l as u64)
 }
 
 fn run_with_file(f: OsString) -> (f64, u64) {
@@ -209,9 +346,11 @@ fn parse_and_serialize(f: OsString) -> (f64, u64) {
 }
 
 fn run_with_random(n: usize) -> (f64, usize) {
-    let v = match n_random(n) {
+    // the seed reproduces the values of a failed run
+    let seed = rand::random::<u64>();
+    let v = match n_random(n, seed) {
         Ok(v) => v,
-        Err(e) => panic!("cannot generate {} random: {:?}", n, e),
+        Err(e) => panic!("cannot generate {} random: {:?} (seed: {})", n, e, seed),
     };
     let l = v.len();
     let t = Instant::now();
@@ -219,36 +358,34 @@ fn run_with_random(n: usize) -> (f64, usize) {
     let mut s = Stream::new(Opts::default(), &mut input);
     let mut js = Vec::new();
     let mut rs = Vec::new();
-    loop {
-        match parse(&mut s) {
-            Ok(j) => {
-                let mut v2 = Vec::new();
-                let mut output = Cursor::new(&mut v2);
-                j.serialize(&mut output).unwrap();
-                js.push(j);
-                let mut input2 = Cursor::new(v2);
-                let mut s2 = Stream::new(Opts::default(), &mut input2);
-                match parse(&mut s2) {
-                    Ok(r) => rs.push(r),
-                    Err(e) => panic!("parse failed with error: {:?}", e),
-                }
-            },
-            Err(e) if e.is_eof() => break,
-            Err(e) => panic!("parse of serialized failed with error: {:?}", e),
+    for r in parse_many(&mut s) {
+        let j = match r {
+            Ok(j) => j,
+            Err(e) => panic!("parse of serialized failed with error: {:?} (seed: {})", e, seed),
+        };
+        let mut v2 = Vec::new();
+        let mut output = Cursor::new(&mut v2);
+        j.serialize(&mut output).unwrap();
+        js.push(j);
+        let mut input2 = Cursor::new(v2);
+        let mut s2 = Stream::new(Opts::default(), &mut input2);
+        match parse_complete(&mut s2) {
+            Ok(r) => rs.push(r),
+            Err(e) => panic!("parse failed with error: {:?} (seed: {})", e, seed),
         }
     }
     for (j, r) in js.into_iter().zip(rs) {
         // println!("comparing {:?} and {:?}", j, r);
-        assert_eq!(j, r);
+        assert_eq!(j, r, "seed: {}", seed);
     }
     
     (t.elapsed().as_secs_f64() * US, 2*l)
 }
 
-fn n_random(m: usize) -> io::Result<Vec<u8>> {
+fn n_random(m: usize, seed: u64) -> io::Result<Vec<u8>> {
     let mut v = Vec::new();
-    for _ in 0 .. m {
-        let j = make_n_arbitrary(10);
+    for i in 0 .. m {
+        let j = make_n_seeded(10, seed.wrapping_add(i as u64));
         // println!("{:?}", j);
         let _ = j.serialize(&mut v)?;
         v.push(b' ');
@@ -5,20 +5,26 @@ use std::net::TcpStream;
 use std::thread;
 use std::time;
 use std::env;
-use jsosso::arbitrary::{make_n_arbitrary};
+use jsosso::arbitrary::{make_n_arbitrary, make_from_schema, GeneratorConfig};
+use jsosso::msgpack;
+use jsosso::Json;
 
 // command line arguments
 #[derive(Debug)]
 struct Config {
     acks: bool,
+    msgpack: bool,
     port: String,
+    schema: Option<String>,
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
             acks: false,
+            msgpack: false,
             port: "6049".to_string(),
+            schema: None,
         }
     }
 }
@@ -27,8 +33,12 @@ fn usage(name: &str) -> String {
    format!(r"Usage {}: [OPTION]
             -a:
             --acks: expect acks from server,
+            -m:
+            --msgpack: send payloads as MessagePack,
             -p <port>:
-            --port <port>: connect to port 'port'.
+            --port <port>: connect to port 'port',
+            -s <file>:
+            --schema <file>: send payloads that conform to the JSON Schema in 'file'.
             "
        , name)
 }
@@ -39,14 +49,23 @@ fn main() {
     let mut cfg = Config::default();
     check_args(&args[1..],
                &mut cfg)
-               .expect(
-                 &usage(
-                   &args[0]));
+               .unwrap_or_else(|e| panic!("{}: {}", e,
+                 usage(
+                   &args[0])));
+
+    // payloads conform to the schema, if there is one
+    let schema = cfg.schema.as_ref().map(|f| match Json::from_file(f) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("cannot read schema {}: {}", f, e);
+            std::process::exit(1);
+        },
+    });
 
     // take a nap between the messages
     let nap = time::Duration::new(1, 0);
 
-    let mut ok = vec![0; 2]; // only with acks
+    let mut ack = Vec::new(); // only with acks
 
     let addr = format!("127.0.0.1:{}", cfg.port);
     let mut stream = match TcpStream::connect(&addr) {
@@ -60,7 +79,7 @@ fn main() {
     loop {
         println!("sleeping");
         thread::sleep(nap);
-        let mut v = match build_message() {
+        let mut v = match build_message(cfg.msgpack, schema.as_ref()) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -90,18 +109,31 @@ fn main() {
         }
 
         // otherwise wait for ack
-        loop {
-            match stream.read(&mut ok) {
-                Ok(s) => {
-                    println!("{} bytes read", s);
-                    break;
-                },
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => {
-                    eprintln!("error: {:?}", e);
-                    std::process::exit(1);
-                },
-            }
+        match read_ack(&mut stream, &mut ack) {
+            Ok(j) => println!("ack: {}", j),
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                std::process::exit(1);
+            },
+        }
+    }
+}
+
+// acks are Json objects terminated by ETX
+fn read_ack(stream: &mut TcpStream, ack: &mut Vec<u8>) -> io::Result<Json> {
+    let mut buf = [0; 256];
+    loop {
+        if let Some(n) = ack.iter().position(|b| *b == 3) {
+            let j = Json::from_slice(&ack[.. n])
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+            ack.drain(..= n);
+            return Ok(j);
+        }
+        match stream.read(&mut buf) {
+            Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+            Ok(n) => ack.extend_from_slice(&buf[.. n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
         }
     }
 }
@@ -117,6 +149,11 @@ fn check_args(args: &[String], cfg: &mut Config) -> Result<(), String> {
                    arg = None;
                    continue;
                 },
+                Some(1) => {
+                   cfg.schema = Some(s.to_string());
+                   arg = None;
+                   continue;
+                },
                 Some(n) => return Err(format!("unknown option number {}", n)),
                 _ => match s.as_ref() {
                        // acks
@@ -125,6 +162,10 @@ fn check_args(args: &[String], cfg: &mut Config) -> Result<(), String> {
                        "-ack" => cfg.acks = true,
                        "--ack" => cfg.acks = true,
 
+                       // msgpack
+                       "-m" => cfg.msgpack = true,
+                       "--msgpack" => cfg.msgpack = true,
+
                        // port
                        "port" => arg = Some(0),
                        "-port" => arg = Some(0),
@@ -132,6 +173,10 @@ fn check_args(args: &[String], cfg: &mut Config) -> Result<(), String> {
                        "--p" => arg = Some(0),
                        "-p" => arg = Some(0),
 
+                       // schema
+                       "-s" => arg = Some(1),
+                       "--schema" => arg = Some(1),
+
                        // error
                        _ => return Err(format!("unknown option '{}'", s)),
                 }
@@ -140,19 +185,30 @@ fn check_args(args: &[String], cfg: &mut Config) -> Result<(), String> {
         Ok(())
     };
 
-    if args.len() > 0 {
+    if !args.is_empty() {
         return parse(args, cfg);
     }
     Ok(())
 }
 
-fn build_message() -> io::Result<Vec<u8>> {
-    let j = make_n_arbitrary(10);
+fn build_message(binary: bool, schema: Option<&Json>) -> io::Result<Vec<u8>> {
+    let j = match schema {
+        Some(s) => {
+            let c = GeneratorConfig::default().set_breadth(10, 10);
+            make_from_schema(&mut rand::thread_rng(), s, &c)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?
+        },
+        None => make_n_arbitrary(10),
+    };
     let mut v = Vec::new();
     let s = "Add\n\n".as_bytes().to_vec();
     v.extend_from_slice(&s[..]);
     let mut jv = Vec::new();
-    j.serialize(&mut io::Cursor::new(&mut jv))?;
+    if binary {
+        msgpack::encode(&j, &mut jv)?;
+    } else {
+        j.serialize(&mut io::Cursor::new(&mut jv))?;
+    }
     v.extend_from_slice(&jv[..]);
     Ok(v)
 }
@@ -5,16 +5,19 @@ use std::str;
 use std::time;
 use pacosso::{Opts, ParseResult};
 use jsosso::parsing::{parse};
+use jsosso::msgpack;
 use jsosso::Json;
 
 fn main() {
    let args: Vec<String> = env::args().collect();
-   let port = if args.len() > 1 {
-       args[1].to_string()
-   } else {
-       "6049".to_string()
+
+   // with --msgpack, payloads are MessagePack instead of Json text
+   let binary = args[1..].iter().any(|a| a == "--msgpack" || a == "-m");
+   let port = match args[1..].iter().find(|a| !a.starts_with('-')) {
+       Some(p) => p.to_string(),
+       None => "6049".to_string(),
    };
-   let listener = match TcpListener::bind(&format!("127.0.0.1:{}", port)) {
+   let listener = match TcpListener::bind(format!("127.0.0.1:{}", port)) {
        Ok(l) => l,
        Err(e) => {
            handle_error(e);
@@ -24,7 +27,7 @@ fn main() {
 
    for stream in listener.incoming() {
        match stream {
-           Ok(s) => handle_client(s),
+           Ok(s) => handle_client(s, binary),
            Err(e) => handle_error(e),
        }
    }
@@ -45,11 +48,12 @@ impl Default for Command {
     }
 }
 
-fn handle_client(mut stream: TcpStream) {
+fn handle_client(mut stream: TcpStream, binary: bool) {
     println!("connected!");
 
     // this parses a command of the form:
-    // <command>\n\n<json>
+    // <command>\n\n<json><etx>
+    // where the payload is either Json text or MessagePack
     let parse_command = |s: &mut pacosso::Stream<TcpStream>| -> ParseResult<Command> {
         let mut v = Vec::new();
 
@@ -69,7 +73,11 @@ fn handle_client(mut stream: TcpStream) {
             Err(_) => return s.fail("utf8 error", Command::default()),
         };
 
-        let j = parse(s)?;
+        let j = if binary {
+            msgpack::decode(s)?
+        } else {
+            parse(s)?
+        };
 
         s.skip_whitespace()?;
         s.byte(3)?;
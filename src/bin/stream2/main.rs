@@ -4,11 +4,12 @@ use std::env;
 use std::str;
 use std::time;
 use std::thread;
-use pacosso::{Opts, ParseResult};
-use pacosso::error::{ParseError};
-use jsosso::parsing::{parse};
+use jsosso::push::PushParser;
 use jsosso::Json;
 
+// a client that sends nothing for this many read timeouts is dropped
+const MAX_IDLE_TIMEOUTS: usize = 10;
+
 fn main() {
    let args: Vec<String> = env::args().collect();
    let port = if args.len() > 1 {
@@ -17,7 +18,7 @@ fn main() {
        "6049".to_string()
    };
 
-   let listener = match TcpListener::bind(&format!("127.0.0.1:{}", port)) {
+   let listener = match TcpListener::bind(format!("127.0.0.1:{}", port)) {
        Ok(l) => l,
        Err(e) => {
            handle_error(e);
@@ -39,98 +40,165 @@ struct Command {
     payload: jsosso::Json,
 }
 
-impl Default for Command {
-    fn default() -> Command {
-        Command {
-            name: "ADD".to_string(),
-            payload: Json::Null,
-        }
-    }
-}
-
 fn handle_client(mut stream: TcpStream) {
     println!("connected!");
 
     stream.set_read_timeout(Some(time::Duration::new(3, 0))).unwrap();
 
-    let mut bs = vec![];
+    let mut session = Session::default();
+    let mut buf = [0; 1024];
+    let mut timeouts = 0;
     loop {
-        bs = {
-            let input = bs.chain(&mut stream);
-            match handle_input(input) {
-                Ok(v) => v,
-                Err(ParseError::IOError(e)) if e.kind() == ErrorKind::WouldBlock => {
-                     eprintln!("would block on read: {:?}", e);
-                     return;
-                },
+        let n = match stream.read(&mut buf) {
+            Ok(0) => {
+                if !session.is_idle() {
+                    eprintln!("error: connection closed within a message");
+                }
+                return;
+            },
+            Ok(n) => n,
+            // the session keeps partial messages, we just try again,
+            // but not forever
+            Err(e) if e.kind() == ErrorKind::WouldBlock ||
+                      e.kind() == ErrorKind::TimedOut => {
+                timeouts += 1;
+                if timeouts >= MAX_IDLE_TIMEOUTS {
+                    eprintln!("error: client idle for {} timeouts, hanging up", timeouts);
+                    let _ = send_ack(&mut stream, &nack("idle timeout"));
+                    return;
+                }
+                eprintln!("would block on read: {:?}", e);
+                continue;
+            },
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!("error on read: {:?}", e);
+                return;
+            },
+        };
+
+        timeouts = 0;
+
+        let mut bs = &buf[.. n];
+        while !bs.is_empty() {
+            let (k, cmd) = match session.feed(bs) {
+                Ok(r) => r,
                 Err(e) => {
-                    eprintln!("error on parse: {:?}", e);
+                    eprintln!("error on parse: {}", e);
+                    // the client shall know why we hang up
+                    let _ = send_ack(&mut stream, &nack(&e));
                     return;
                 },
+            };
+            bs = &bs[k ..];
+
+            if let Some(cmd) = cmd {
+                handle_command(&cmd);
+                if let Err(e) = send_ack(&mut stream, &ack(&cmd)) {
+                    eprintln!("error on write: {:?}", e);
+                    return;
+                }
             }
-        }; // drop input
+        }
+    }
+}
 
-        match stream.write(&"OK".as_bytes().to_vec()) {
-            Ok(2) => {
-                stream.flush().expect("flush failed");
-                continue;
-            },
-            Ok(n) => {
-                eprintln!("error: cannot write 2 bytes: {}", n);
-                break;
-            },
-            Err(e) if e.kind() == ErrorKind::Interrupted => break,
+// acks are Json objects terminated by ETX like the commands
+fn ack(cmd: &Command) -> Json {
+    let size = match &cmd.payload {
+        Json::Array(v) => Some(v.len()),
+        Json::Object(m) => Some(m.len()),
+        _ => None,
+    };
+    Json::object()
+        .insert("ok", true)
+        .insert("command", cmd.name.as_str())
+        .insert_opt("size", size)
+        .insert_if(cmd.name.is_empty(), "warning", "command without name")
+        .build()
+}
+
+fn nack(error: &str) -> Json {
+    Json::object()
+        .insert("ok", false)
+        .insert("error", error)
+        .build()
+}
+
+fn send_ack(stream: &mut TcpStream, ack: &Json) -> io::Result<()> {
+    let mut v = ack.to_string().into_bytes();
+    v.push(3);
+    loop {
+        match stream.write_all(&v) {
+            Ok(()) => return stream.flush(),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
             Err(e) if e.kind() == ErrorKind::WouldBlock  => {
                 eprintln!("would block on write: {:?}", e);
                 thread::sleep(time::Duration::new(0, 1000));
             },
-            Err(e) => {
-                eprintln!("error on write: {:?}", e);
-                break;
-            },
+            Err(e) => return Err(e),
         }
     }
 }
 
-fn handle_input<R: Read>(mut stream: R) -> ParseResult<Vec<u8>> {
-    // this parses a command of the form:
-    // <command>\n\n<json><etx>
-    let parse_command = |s: &mut pacosso::Stream<R>| -> ParseResult<Command> {
-        let mut v = Vec::new();
+// the state of a connection; this parses commands of the form:
+// <command>\n\n<json><etx>
+// from chunks of input as they arrive
+#[derive(Default)]
+struct Session {
+    head: Vec<u8>,
+    name: Option<String>,
+    payload: Option<Json>,
+    parser: PushParser,
+}
 
-        s.skip_whitespace()?;
+impl Session {
+    fn is_idle(&self) -> bool {
+        self.name.is_none() && self.head.iter().all(|b| b.is_ascii_whitespace())
+    }
 
-        loop {
-            let b = s.any_byte()?;
-            if b == b'\n' {
-                break;
+    // consumes bytes until a command is complete or all bytes are consumed
+    fn feed(&mut self, bs: &[u8]) -> Result<(usize, Option<Command>), String> {
+        let mut n = 0;
+        while n < bs.len() {
+            if self.name.is_none() {
+                self.head.push(bs[n]);
+                n += 1;
+                if self.head.ends_with(b"\n\n") {
+                    let h = &self.head[.. self.head.len() - 2];
+                    let name = match str::from_utf8(h) {
+                        Ok(c) => c.trim_start().to_string(),
+                        Err(_) => return Err("utf8 error".to_string()),
+                    };
+                    self.name = Some(name);
+                    self.head.clear();
+                }
+            } else if self.payload.is_none() {
+                let (k, r) = self.parser.feed(&bs[n ..]);
+                n += k;
+                match r {
+                    Some(Ok(j)) => self.payload = Some(j),
+                    Some(Err(e)) => return Err(e.to_string()),
+                    None => (),
+                }
+            } else {
+                let b = bs[n];
+                n += 1;
+                match b {
+                    3 => {
+                        let cmd = Command {
+                            name: self.name.take().unwrap_or_default(),
+                            payload: self.payload.take().unwrap_or(Json::Null),
+                        };
+                        return Ok((n, Some(cmd)));
+                    },
+                    b if b.is_ascii_whitespace() => (),
+                    b => return Err(format!("ETX expected, found {}", b)),
+                }
             }
-            v.push(b);
         }
-        s.byte(b'\n')?;
-
-        let cmd = match str::from_utf8(&v) {
-            Ok(c) => c,
-            Err(_) => return s.fail("utf8 error", Command::default()),
-        };
-
-        let j = parse(s)?;
-
-        s.skip_whitespace()?;
-        s.byte(3)?;
-
-        Ok(Command {
-            name: cmd.to_string(),
-            payload: j,
-        })
-    };
-
-    let mut p = pacosso::Stream::new(Opts::default()
-                                         .set_infinite_stream(),
-                                         &mut stream);
-    let cmd = parse_command(&mut p)?;
-    handle_command(&cmd);
-    p.drain()
+        Ok((n, None))
+    }
 }
 
 fn handle_command(cmd: &Command) {
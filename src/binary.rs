@@ -0,0 +1,21 @@
+use std::io::Read;
+use pacosso::{Stream, ParseResult};
+
+// Reads a big-endian unsigned integer of 'n' bytes.
+pub(crate) fn uint<R: Read>(s: &mut Stream<R>, n: usize) -> ParseResult<u64> {
+    let mut a = 0;
+    for _ in 0 .. n {
+        a = a << 8 | s.any_byte()? as u64;
+    }
+    Ok(a)
+}
+
+// Appends 'n' bytes to 'v' byte by byte; unlike Stream::get_bytes,
+// this does not depend on the size of the stream buffers.
+pub(crate) fn get_bytes<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>, n: u64) -> ParseResult<()> {
+    v.reserve(n.min(4096) as usize); // do not trust the length
+    for _ in 0 .. n {
+        v.push(s.any_byte()?);
+    }
+    Ok(())
+}
@@ -0,0 +1,760 @@
+use std::io::{self, Read, Write, ErrorKind};
+use pacosso::{Stream, ParseResult};
+use super::*;
+use super::error;
+use super::encoding::{base64_encode, base64_decode};
+use super::parsing::{parse_buffer_with, end_of_data};
+use super::binary::get_bytes;
+
+// element types
+const DOUBLE: u8 = 0x01;
+const STRING: u8 = 0x02;
+const DOCUMENT: u8 = 0x03;
+const ARRAY: u8 = 0x04;
+const BINARY: u8 = 0x05;
+const UNDEFINED: u8 = 0x06;
+const OBJECTID: u8 = 0x07;
+const BOOLEAN: u8 = 0x08;
+const DATETIME: u8 = 0x09;
+const NULL: u8 = 0x0a;
+const REGEX: u8 = 0x0b;
+const DBPOINTER: u8 = 0x0c;
+const CODE: u8 = 0x0d;
+const SYMBOL: u8 = 0x0e;
+const CODE_W_SCOPE: u8 = 0x0f;
+const INT32: u8 = 0x10;
+const TIMESTAMP: u8 = 0x11;
+const INT64: u8 = 0x12;
+const DECIMAL128: u8 = 0x13;
+const MINKEY: u8 = 0xff;
+const MAXKEY: u8 = 0x7f;
+
+// binary subtype with an additional length prefix
+const BINARY_OLD: u8 = 0x02;
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Options for the BSON decoder.
+///
+/// BSON types that Json does not have are represented
+/// in MongoDB Extended JSON v2, e.g. `{"$oid": "..."}` for an ObjectId.
+/// In relaxed mode (the default), integers and finite doubles
+/// become Json numbers and dates between the years 1970 and 9999
+/// are written as ISO-8601 strings.
+/// In canonical mode, all numbers and dates keep their BSON type
+/// in Extended JSON, e.g. `{"$numberLong": "42"}`;
+/// this preserves 64 bit integers exactly.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::bson::{from_slice, from_slice_with_opts, BsonOpts};
+///
+///    // {"n": <int64 42>}
+///    let input = b"\x10\x00\x00\x00\x12n\x00\x2a\x00\x00\x00\x00\x00\x00\x00\x00";
+///    assert_eq!(from_slice(input).unwrap(), json!({"n": 42}));
+///
+///    let o = BsonOpts::default().set_canonical(true);
+///    assert_eq!(from_slice_with_opts(input, &o).unwrap(), json!({"n": {"$numberLong": "42"}}));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BsonOpts {
+    /// Use canonical instead of relaxed Extended JSON.
+    /// The default is `false`.
+    pub canonical: bool,
+}
+
+impl BsonOpts {
+    /// Sets canonical Extended JSON.
+    pub fn set_canonical(self, c: bool) -> BsonOpts {
+        BsonOpts {
+            canonical: c,
+        }
+    }
+}
+
+/// Encodes Json object 'j' as BSON document into writer 'w'
+/// and returns the number of bytes written.
+///
+/// Numbers without fraction become int32 or int64, if they fit,
+/// all other numbers become doubles.
+/// Objects in Extended JSON v2 (canonical or relaxed) become
+/// the BSON types they represent:
+/// `$oid`, `$date`, `$binary`, `$numberInt`, `$numberLong`,
+/// `$numberDouble`, `$timestamp`, `$regularExpression`,
+/// `$symbol`, `$code`, `$minKey`, `$maxKey` and `$undefined`.
+///
+/// Fails with `ErrorKind::InvalidInput` if 'j' is not an object,
+/// if a key contains a null byte or if an Extended JSON object is malformed.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::bson::encode;
+///
+///    let mut v: Vec<u8> = Vec::new();
+///    encode(&json!({"id": {"$oid": "0123456789abcdef01234567"}}), &mut v).unwrap();
+///    assert_eq!(v, b"\x15\x00\x00\x00\x07id\x00\x01\x23\x45\x67\x89\xab\xcd\xef\x01\x23\x45\x67\x00");
+///    assert!(encode(&json!([1, 2]), &mut v).is_err());
+/// ```
+pub fn encode<W: Write>(j: &Json, w: &mut W) -> io::Result<usize> {
+    let v = to_vec(j)?;
+    w.write_all(&v)?;
+    w.flush()?;
+    Ok(v.len())
+}
+
+/// Encodes Json object 'j' as BSON document into a new byte vector.
+pub fn to_vec(j: &Json) -> io::Result<Vec<u8>> {
+    let mut v = Vec::new();
+    match j {
+        Json::Object(m) => write_document(&mut v, m.iter())?,
+        _ => return Err(invalid("a BSON document must be a Json object")),
+    }
+    Ok(v)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+fn write_document<'j, I>(v: &mut Vec<u8>, es: I) -> io::Result<()>
+    where I: Iterator<Item = (&'j String, &'j Json)>
+{
+    let start = v.len();
+    v.extend_from_slice(&[0; 4]);
+    for (k, e) in es {
+        write_element(v, k, e)?;
+    }
+    v.push(0);
+    patch_length(v, start)
+}
+
+fn write_array(v: &mut Vec<u8>, a: &[Json]) -> io::Result<()> {
+    let start = v.len();
+    v.extend_from_slice(&[0; 4]);
+    for (i, e) in a.iter().enumerate() {
+        write_element(v, &i.to_string(), e)?;
+    }
+    v.push(0);
+    patch_length(v, start)
+}
+
+// writes the length of the document starting at 'start'
+fn patch_length(v: &mut [u8], start: usize) -> io::Result<()> {
+    let n = match i32::try_from(v.len() - start) {
+        Ok(n) => n,
+        Err(_) => return Err(invalid("document too large")),
+    };
+    v[start .. start + 4].copy_from_slice(&n.to_le_bytes());
+    Ok(())
+}
+
+fn write_element(v: &mut Vec<u8>, k: &str, j: &Json) -> io::Result<()> {
+    let t = v.len();
+    v.push(0); // type, known after the value was written
+    write_cstring(v, k)?;
+    v[t] = write_value(v, j)?;
+    Ok(())
+}
+
+fn write_cstring(v: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    if s.contains('\0') {
+        return Err(invalid("null byte in key or regular expression"));
+    }
+    v.extend_from_slice(s.as_bytes());
+    v.push(0);
+    Ok(())
+}
+
+fn write_string(v: &mut Vec<u8>, s: &str) {
+    v.extend_from_slice(&(s.len() as i32 + 1).to_le_bytes());
+    v.extend_from_slice(s.as_bytes());
+    v.push(0);
+}
+
+// writes the value of an element and returns its type
+fn write_value(v: &mut Vec<u8>, j: &Json) -> io::Result<u8> {
+    match j {
+        Json::Null => Ok(NULL),
+        Json::Boolean(b) => {
+            v.push(*b as u8);
+            Ok(BOOLEAN)
+        },
+        Json::Number(n) => Ok(write_number(v, *n)),
+        Json::String(s) => {
+            write_string(v, s);
+            Ok(STRING)
+        },
+        Json::Array(a) => {
+            write_array(v, a)?;
+            Ok(ARRAY)
+        },
+        Json::Object(m) => match write_extended(v, m)? {
+            Some(t) => Ok(t),
+            None => {
+                write_document(v, m.iter())?;
+                Ok(DOCUMENT)
+            },
+        },
+    }
+}
+
+fn write_number(v: &mut Vec<u8>, n: f64) -> u8 {
+    const LIMIT: f64 = 9223372036854775808.0; // 2^63
+
+    // -0 is a double, NaN and infinity have no integral part
+    if n.fract() != 0.0 || (n == 0.0 && n.is_sign_negative()) || !(-LIMIT .. LIMIT).contains(&n) {
+        v.extend_from_slice(&n.to_le_bytes());
+        DOUBLE
+    } else if (i32::MIN as f64 ..= i32::MAX as f64).contains(&n) {
+        v.extend_from_slice(&(n as i32).to_le_bytes());
+        INT32
+    } else {
+        v.extend_from_slice(&(n as i64).to_le_bytes());
+        INT64
+    }
+}
+
+// Writes an object in Extended JSON as the BSON type it represents
+// and returns the type; returns None for other objects.
+fn write_extended(v: &mut Vec<u8>, m: &HashMap<String, Json>) -> io::Result<Option<u8>> {
+    let (k, j) = match m.iter().next() {
+        Some(e) if m.len() == 1 && e.0.starts_with('$') => e,
+        _ => return Ok(None),
+    };
+    let t = match (k.as_str(), j) {
+        ("$oid", Json::String(h)) => match hex_decode(h) {
+            Some(b) if b.len() == 12 => {
+                v.extend_from_slice(&b);
+                OBJECTID
+            },
+            _ => return Err(invalid("invalid $oid")),
+        },
+        ("$date", d) => {
+            let ms = match d {
+                Json::String(s) => parse_date(s),
+                Json::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+                _ => number_string(d, "$numberLong"),
+            };
+            match ms {
+                Some(ms) => v.extend_from_slice(&ms.to_le_bytes()),
+                None => return Err(invalid("invalid $date")),
+            }
+            DATETIME
+        },
+        ("$binary", b) => {
+            let data = field(b, "base64").and_then(|d| match d {
+                Json::String(s) => base64_decode(s),
+                _ => None,
+            });
+            let sub = field(b, "subType").and_then(|d| match d {
+                Json::String(s) if s.len() <= 2 => u8::from_str_radix(s, 16).ok(),
+                _ => None,
+            });
+            match (data, sub) {
+                (Some(data), Some(sub)) => write_binary(v, &data, sub),
+                _ => return Err(invalid("invalid $binary")),
+            }
+            BINARY
+        },
+        ("$numberInt", n) => match number_string(n, k).and_then(|n| i32::try_from(n).ok()) {
+            Some(n) => {
+                v.extend_from_slice(&n.to_le_bytes());
+                INT32
+            },
+            None => return Err(invalid("invalid $numberInt")),
+        },
+        ("$numberLong", n) => match number_string(n, k) {
+            Some(n) => {
+                v.extend_from_slice(&n.to_le_bytes());
+                INT64
+            },
+            None => return Err(invalid("invalid $numberLong")),
+        },
+        ("$numberDouble", Json::String(s)) => {
+            let n = match s.as_str() {
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                "NaN" => f64::NAN,
+                s => match s.parse::<f64>() {
+                    Ok(n) => n,
+                    Err(_) => return Err(invalid("invalid $numberDouble")),
+                },
+            };
+            v.extend_from_slice(&n.to_le_bytes());
+            DOUBLE
+        },
+        ("$timestamp", ts) => {
+            let t = field(ts, "t").and_then(as_u32);
+            let i = field(ts, "i").and_then(as_u32);
+            match (t, i) {
+                (Some(t), Some(i)) => {
+                    v.extend_from_slice(&i.to_le_bytes());
+                    v.extend_from_slice(&t.to_le_bytes());
+                },
+                _ => return Err(invalid("invalid $timestamp")),
+            }
+            TIMESTAMP
+        },
+        ("$regularExpression", re) => match (field(re, "pattern"), field(re, "options")) {
+            (Some(Json::String(p)), Some(Json::String(o))) => {
+                // the options are stored in alphabetical order
+                let mut o: Vec<char> = o.chars().collect();
+                o.sort_unstable();
+                write_cstring(v, p)?;
+                write_cstring(v, &o.into_iter().collect::<String>())?;
+                REGEX
+            },
+            _ => return Err(invalid("invalid $regularExpression")),
+        },
+        ("$symbol", Json::String(s)) => {
+            write_string(v, s);
+            SYMBOL
+        },
+        ("$code", Json::String(s)) => {
+            write_string(v, s);
+            CODE
+        },
+        ("$minKey", Json::Number(n)) if *n == 1.0 => MINKEY,
+        ("$maxKey", Json::Number(n)) if *n == 1.0 => MAXKEY,
+        ("$undefined", Json::Boolean(true)) => UNDEFINED,
+        ("$oid" | "$numberDouble" | "$symbol" | "$code" |
+         "$minKey" | "$maxKey" | "$undefined", _) => {
+            return Err(invalid(&format!("invalid {}", k)));
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(t))
+}
+
+fn write_binary(v: &mut Vec<u8>, data: &[u8], sub: u8) {
+    if sub == BINARY_OLD {
+        v.extend_from_slice(&(data.len() as i32 + 4).to_le_bytes());
+        v.push(sub);
+        v.extend_from_slice(&(data.len() as i32).to_le_bytes());
+    } else {
+        v.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        v.push(sub);
+    }
+    v.extend_from_slice(data);
+}
+
+fn field<'j>(j: &'j Json, k: &str) -> Option<&'j Json> {
+    match j {
+        Json::Object(m) => m.get(k),
+        _ => None,
+    }
+}
+
+fn as_u32(j: &Json) -> Option<u32> {
+    match j {
+        Json::Number(n) if n.fract() == 0.0 && (0.0 ..= u32::MAX as f64).contains(n) => Some(*n as u32),
+        _ => None,
+    }
+}
+
+// reads {"<k>": "<integer>"}
+fn number_string(j: &Json, k: &str) -> Option<i64> {
+    match j {
+        Json::String(s) => s.parse().ok(),
+        _ => match field(j, k) {
+            Some(Json::String(s)) => s.parse().ok(),
+            _ => None,
+        },
+    }
+}
+
+fn hex_decode(h: &str) -> Option<Vec<u8>> {
+    if h.len() & 1 != 0 || !h.is_ascii() {
+        return None;
+    }
+    (0 .. h.len()).step_by(2).map(|i| u8::from_str_radix(&h[i .. i + 2], 16).ok()).collect()
+}
+
+fn hex_encode(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+// Converts a proleptic Gregorian date to days since 1970-01-01
+// (http://howardhinnant.github.io/date_algorithms.html).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// The inverse of days_from_civil.
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+// Parses an ISO-8601 date of the form YYYY-MM-DDTHH:MM:SS[.fff](Z|+HH:MM|-HH:MM)
+// into milliseconds since the epoch.
+fn parse_date(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    let num = |from: usize, to: usize| -> Option<i64> {
+        match b.get(from .. to) {
+            Some(ds) if ds.iter().all(|d| d.is_ascii_digit()) => s[from .. to].parse().ok(),
+            _ => None,
+        }
+    };
+    let sep = |i: usize, c: u8| b.get(i) == Some(&c);
+    if !(sep(4, b'-') && sep(7, b'-') && (sep(10, b'T') || sep(10, b't')) && sep(13, b':') && sep(16, b':')) {
+        return None;
+    }
+    let (y, mo, d) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (h, mi, sec) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+    if !(1 ..= 12).contains(&mo) || !(1 ..= 31).contains(&d) || h > 23 || mi > 59 || sec > 60 {
+        return None;
+    }
+
+    // fractional seconds: only milliseconds are kept
+    let mut i = 19;
+    let mut ms = 0;
+    if sep(i, b'.') {
+        i += 1;
+        let start = i;
+        while i < b.len() && b[i].is_ascii_digit() {
+            if i - start < 3 {
+                ms = ms * 10 + (b[i] - b'0') as i64;
+            }
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        for _ in (i - start) .. 3 {
+            ms *= 10;
+        }
+    }
+
+    let offset = match b.get(i) {
+        Some(b'Z') | Some(b'z') if i + 1 == b.len() => 0,
+        Some(c @ (b'+' | b'-')) => {
+            let (oh, om) = match b.len() - i {
+                6 if sep(i + 3, b':') => (num(i + 1, i + 3)?, num(i + 4, i + 6)?),
+                5 => (num(i + 1, i + 3)?, num(i + 3, i + 5)?),
+                _ => return None,
+            };
+            let o = (oh * 60 + om) * 60_000;
+            if *c == b'-' { -o } else { o }
+        },
+        _ => return None,
+    };
+
+    let days = days_from_civil(y, mo, d);
+    Some(days * MS_PER_DAY + ((h * 60 + mi) * 60 + sec) * 1000 + ms - offset)
+}
+
+fn format_date(ms: i64) -> String {
+    let days = ms.div_euclid(MS_PER_DAY);
+    let rest = ms.rem_euclid(MS_PER_DAY);
+    let (y, m, d) = civil_from_days(days);
+    let (h, mi, s, f) = (rest / 3_600_000, rest / 60_000 % 60, rest / 1000 % 60, rest % 1000);
+    if f == 0 {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, h, mi, s)
+    } else {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", y, m, d, h, mi, s, f)
+    }
+}
+
+// an object with a single member
+fn ext(k: &str, j: Json) -> Json {
+    let mut m = HashMap::new();
+    m.insert(k.to_string(), j);
+    Json::Object(Box::new(m))
+}
+
+/// Decodes one BSON document from stream 's' into a Json object.
+/// The stream is left after the document,
+/// so that `decode` can be called again for the next document,
+/// e.g. to read a dump of many documents.
+///
+/// DBPointer, JavaScript code with scope and Decimal128
+/// cannot be represented and yield errors starting with "unsupported".
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::json;
+///    use jsosso::bson::decode;
+///    use pacosso::{Stream, Opts};
+///
+///    // {"d": <datetime 1000>}, {}
+///    let mut input = Cursor::new(b"\x10\x00\x00\x00\x09d\x00\xe8\x03\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    assert_eq!(decode(&mut s).unwrap(), json!({"d": {"$date": "1970-01-01T00:00:01Z"}}));
+///    assert_eq!(decode(&mut s).unwrap(), json!({}));
+///    assert!(decode(&mut s).unwrap_err().is_eof());
+/// ```
+pub fn decode<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    decode_with_opts(s, &BsonOpts::default())
+}
+
+/// Like `decode`, but with explicit decoder options 'o'.
+pub fn decode_with_opts<R: Read>(s: &mut Stream<R>, o: &BsonOpts) -> ParseResult<Json> {
+    let mut d = Decoder {
+        s,
+        pos: 0,
+        o: *o,
+    };
+    let es = d.document()?;
+    Ok(Json::Object(Box::new(es.into_iter().collect())))
+}
+
+/// Decodes a complete BSON document from byte slice 'b'.
+/// Nothing may follow the document.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::bson;
+///
+///    let j = json!({"list": [1, 2.5, "three"], "bin": {"$binary": {"base64": "AQI=", "subType": "00"}}});
+///    assert_eq!(bson::from_slice(&bson::to_vec(&j).unwrap()).unwrap(), j);
+/// ```
+pub fn from_slice(b: &[u8]) -> error::Result<Json> {
+    from_slice_with_opts(b, &BsonOpts::default())
+}
+
+/// Like `from_slice`, but with explicit decoder options 'o'.
+pub fn from_slice_with_opts(b: &[u8], o: &BsonOpts) -> error::Result<Json> {
+    Ok(parse_buffer_with(b, |s| decode_complete(s, o))?)
+}
+
+/// Decodes a complete BSON document from reader 'r'.
+/// Nothing may follow the document.
+pub fn from_reader<R: Read>(r: R) -> error::Result<Json> {
+    from_reader_with_opts(r, &BsonOpts::default())
+}
+
+/// Like `from_reader`, but with explicit decoder options 'o'.
+pub fn from_reader_with_opts<R: Read>(mut r: R, o: &BsonOpts) -> error::Result<Json> {
+    // the input is read completely, so that the stream buffers
+    // can be sized to the input (see parsing::EofGuard)
+    let mut v = Vec::new();
+    r.read_to_end(&mut v)?;
+    from_slice_with_opts(&v, o)
+}
+
+fn decode_complete<R: Read>(s: &mut Stream<R>, o: &BsonOpts) -> ParseResult<Json> {
+    let j = decode_with_opts(s, o)?;
+    end_of_data(s)?;
+    Ok(j)
+}
+
+// counts the bytes read to check the length fields
+struct Decoder<'x, 'a, R: Read> {
+    s: &'x mut Stream<'a, R>,
+    pos: usize,
+    o: BsonOpts,
+}
+
+impl<R: Read> Decoder<'_, '_, R> {
+    fn byte(&mut self) -> ParseResult<u8> {
+        let b = self.s.any_byte()?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> ParseResult<[u8; N]> {
+        let mut b = [0; N];
+        for x in b.iter_mut() {
+            *x = self.byte()?;
+        }
+        Ok(b)
+    }
+
+    fn get_bytes(&mut self, n: usize) -> ParseResult<Vec<u8>> {
+        let mut v = Vec::new();
+        get_bytes(self.s, &mut v, n as u64)?;
+        self.pos += n;
+        Ok(v)
+    }
+
+    fn length(&mut self) -> ParseResult<usize> {
+        let n = i32::from_le_bytes(self.bytes()?);
+        match usize::try_from(n) {
+            Ok(n) => Ok(n),
+            Err(_) => self.s.fail(&format!("invalid length {}", n), 0),
+        }
+    }
+
+    fn cstring(&mut self) -> ParseResult<String> {
+        let mut v = Vec::new();
+        loop {
+            match self.byte()? {
+                0 => break,
+                b => v.push(b),
+            }
+        }
+        self.utf8(v)
+    }
+
+    fn string(&mut self) -> ParseResult<String> {
+        let n = self.length()?;
+        if n == 0 {
+            return self.s.fail("invalid string length 0", String::new());
+        }
+        let v = self.get_bytes(n - 1)?;
+        if self.byte()? != 0 {
+            return self.s.fail("string not terminated by null byte", String::new());
+        }
+        self.utf8(v)
+    }
+
+    fn utf8(&mut self, v: Vec<u8>) -> ParseResult<String> {
+        match String::from_utf8(v) {
+            Ok(s) => Ok(s),
+            Err(_) => self.s.fail("utf8 error in string", String::new()),
+        }
+    }
+
+    // reads the elements of a document or an array
+    fn document(&mut self) -> ParseResult<Vec<(String, Json)>> {
+        let start = self.pos;
+        let n = self.length()?;
+        let mut es = Vec::new();
+        loop {
+            let t = self.byte()?;
+            if t == 0 {
+                break;
+            }
+            let k = self.cstring()?;
+            let e = self.element(t)?;
+            es.push((k, e));
+        }
+        if self.pos - start != n {
+            return self.s.fail(&format!("document length mismatch: {} != {}", n, self.pos - start), es);
+        }
+        Ok(es)
+    }
+
+    fn element(&mut self, t: u8) -> ParseResult<Json> {
+        match t {
+            DOUBLE => {
+                let n = f64::from_le_bytes(self.bytes()?);
+                Ok(self.double(n))
+            },
+            STRING => Ok(Json::String(self.string()?)),
+            DOCUMENT => {
+                let es = self.document()?;
+                Ok(Json::Object(Box::new(es.into_iter().collect())))
+            },
+            ARRAY => {
+                let es = self.document()?;
+                Ok(Json::Array(es.into_iter().map(|(_, e)| e).collect()))
+            },
+            BINARY => self.binary(),
+            UNDEFINED => Ok(ext("$undefined", Json::Boolean(true))),
+            OBJECTID => {
+                let b: [u8; 12] = self.bytes()?;
+                Ok(ext("$oid", Json::String(hex_encode(&b))))
+            },
+            BOOLEAN => match self.byte()? {
+                0 => Ok(Json::Boolean(false)),
+                1 => Ok(Json::Boolean(true)),
+                b => self.s.fail(&format!("invalid boolean {}", b), Json::Null),
+            },
+            DATETIME => {
+                let ms = i64::from_le_bytes(self.bytes()?);
+                let y = civil_from_days(ms.div_euclid(MS_PER_DAY)).0;
+                if !self.o.canonical && (1970 ..= 9999).contains(&y) {
+                    Ok(ext("$date", Json::String(format_date(ms))))
+                } else {
+                    Ok(ext("$date", ext("$numberLong", Json::String(ms.to_string()))))
+                }
+            },
+            NULL => Ok(Json::Null),
+            REGEX => {
+                let mut m = HashMap::new();
+                m.insert("pattern".to_string(), Json::String(self.cstring()?));
+                m.insert("options".to_string(), Json::String(self.cstring()?));
+                Ok(ext("$regularExpression", Json::Object(Box::new(m))))
+            },
+            CODE => Ok(ext("$code", Json::String(self.string()?))),
+            SYMBOL => Ok(ext("$symbol", Json::String(self.string()?))),
+            INT32 => {
+                let n = i32::from_le_bytes(self.bytes()?);
+                if self.o.canonical {
+                    Ok(ext("$numberInt", Json::String(n.to_string())))
+                } else {
+                    Ok(Json::from(n))
+                }
+            },
+            TIMESTAMP => {
+                let i = u32::from_le_bytes(self.bytes()?);
+                let t = u32::from_le_bytes(self.bytes()?);
+                let mut m = HashMap::new();
+                m.insert("t".to_string(), Json::from(t));
+                m.insert("i".to_string(), Json::from(i));
+                Ok(ext("$timestamp", Json::Object(Box::new(m))))
+            },
+            INT64 => {
+                let n = i64::from_le_bytes(self.bytes()?);
+                if self.o.canonical {
+                    Ok(ext("$numberLong", Json::String(n.to_string())))
+                } else {
+                    Ok(Json::from(n))
+                }
+            },
+            MINKEY => Ok(ext("$minKey", Json::from(1))),
+            MAXKEY => Ok(ext("$maxKey", Json::from(1))),
+            DBPOINTER | CODE_W_SCOPE | DECIMAL128 => {
+                self.s.fail(&format!("unsupported element type {:#04x}", t), Json::Null)
+            },
+            _ => self.s.fail(&format!("invalid element type {:#04x}", t), Json::Null),
+        }
+    }
+
+    fn double(&self, n: f64) -> Json {
+        let s = if n.is_nan() {
+            "NaN".to_string()
+        } else if n.is_infinite() {
+            if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+        } else if !self.o.canonical {
+            return Json::Number(n);
+        } else if n == 0.0 && n.is_sign_negative() {
+            "-0.0".to_string()
+        } else {
+            // the shortest round-trip form, always with fraction or exponent
+            let s = Json::Number(n).to_string();
+            if s.contains(['.', 'e']) { s } else { s + ".0" }
+        };
+        ext("$numberDouble", Json::String(s))
+    }
+
+    fn binary(&mut self) -> ParseResult<Json> {
+        let mut n = self.length()?;
+        let sub = self.byte()?;
+        if sub == BINARY_OLD {
+            let inner = self.length()?;
+            if inner + 4 != n {
+                return self.s.fail("invalid length of old binary subtype", Json::Null);
+            }
+            n = inner;
+        }
+        let v = self.get_bytes(n)?;
+        let mut m = HashMap::new();
+        m.insert("base64".to_string(), Json::String(base64_encode(&v)));
+        m.insert("subType".to_string(), Json::String(format!("{:02x}", sub)));
+        Ok(ext("$binary", Json::Object(Box::new(m))))
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use super::*;
+
+/// Builds a Json object member by member;
+/// it is created with `Json::object()`.
+/// Values are anything that converts into Json,
+/// including other builders.
+/// Inserting a key again replaces the value.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::{json, Json};
+///
+/// let name = "Add";
+/// let size: Option<usize> = None;
+/// let errors = vec!["too long", "too deep"];
+///
+/// let j = Json::object()
+///         .insert("ok", errors.is_empty())
+///         .insert("command", name)
+///         .insert_opt("size", size)
+///         .insert_if(!errors.is_empty(), "count", errors.len())
+///         .push_array("errors", |a| a.extend(errors.iter().copied()))
+///         .push_object("meta", |o| o.insert("version", 2))
+///         .build();
+///
+/// assert_eq!(j, json!({
+///     "ok": false,
+///     "command": "Add",
+///     "count": 2,
+///     "errors": ["too long", "too deep"],
+///     "meta": {"version": 2}
+/// }));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ObjectBuilder {
+    members: HashMap<String, Json>,
+}
+
+impl ObjectBuilder {
+    /// Inserts the member 'key' with the value 'value'.
+    pub fn insert<K: Into<String>, V: Into<Json>>(mut self, key: K, value: V) -> ObjectBuilder {
+        self.members.insert(key.into(), value.into());
+        self
+    }
+
+    /// Inserts the member 'key' only if 'cond' is true.
+    pub fn insert_if<K: Into<String>, V: Into<Json>>(self, cond: bool, key: K, value: V) -> ObjectBuilder {
+        if cond {
+            self.insert(key, value)
+        } else {
+            self
+        }
+    }
+
+    /// Inserts the member 'key' only if there is a value.
+    pub fn insert_opt<K: Into<String>, V: Into<Json>>(self, key: K, value: Option<V>) -> ObjectBuilder {
+        match value {
+            Some(v) => self.insert(key, v),
+            None => self,
+        }
+    }
+
+    /// Inserts the member 'key' with the array built by 'f'.
+    pub fn push_array<K, F>(self, key: K, f: F) -> ObjectBuilder
+        where K: Into<String>,
+              F: FnOnce(ArrayBuilder) -> ArrayBuilder,
+    {
+        self.insert(key, f(ArrayBuilder::default()))
+    }
+
+    /// Inserts the member 'key' with the object built by 'f'.
+    pub fn push_object<K, F>(self, key: K, f: F) -> ObjectBuilder
+        where K: Into<String>,
+              F: FnOnce(ObjectBuilder) -> ObjectBuilder,
+    {
+        self.insert(key, f(ObjectBuilder::default()))
+    }
+
+    /// Returns the Json object.
+    pub fn build(self) -> Json {
+        Json::Object(Box::new(self.members))
+    }
+}
+
+/// Builds a Json array element by element;
+/// it is created with `Json::array()`.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::{json, Json};
+///
+/// let j = Json::array()
+///         .push(1)
+///         .push_if(false, 2)
+///         .push_opt(Some("three"))
+///         .push_opt(None::<f64>)
+///         .extend(4 ..= 5)
+///         .push_array(|a| a.push(true))
+///         .push_object(|o| o.insert("six", 6))
+///         .build();
+///
+/// assert_eq!(j, json!([1, "three", 4, 5, [true], {"six": 6}]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ArrayBuilder {
+    elements: Vec<Json>,
+}
+
+impl ArrayBuilder {
+    /// Appends 'value'.
+    pub fn push<V: Into<Json>>(mut self, value: V) -> ArrayBuilder {
+        self.elements.push(value.into());
+        self
+    }
+
+    /// Appends 'value' only if 'cond' is true.
+    pub fn push_if<V: Into<Json>>(self, cond: bool, value: V) -> ArrayBuilder {
+        if cond {
+            self.push(value)
+        } else {
+            self
+        }
+    }
+
+    /// Appends the value only if there is one.
+    pub fn push_opt<V: Into<Json>>(self, value: Option<V>) -> ArrayBuilder {
+        match value {
+            Some(v) => self.push(v),
+            None => self,
+        }
+    }
+
+    /// Appends all values of 'values'.
+    pub fn extend<I, V>(mut self, values: I) -> ArrayBuilder
+        where I: IntoIterator<Item = V>,
+              V: Into<Json>,
+    {
+        self.elements.extend(values.into_iter().map(|v| v.into()));
+        self
+    }
+
+    /// Appends the array built by 'f'.
+    pub fn push_array<F: FnOnce(ArrayBuilder) -> ArrayBuilder>(self, f: F) -> ArrayBuilder {
+        self.push(f(ArrayBuilder::default()))
+    }
+
+    /// Appends the object built by 'f'.
+    pub fn push_object<F: FnOnce(ObjectBuilder) -> ObjectBuilder>(self, f: F) -> ArrayBuilder {
+        self.push(f(ObjectBuilder::default()))
+    }
+
+    /// Returns the Json array.
+    pub fn build(self) -> Json {
+        Json::Array(self.elements)
+    }
+}
+
+impl From<ObjectBuilder> for Json {
+    fn from(b: ObjectBuilder) -> Json {
+        b.build()
+    }
+}
+
+impl From<ArrayBuilder> for Json {
+    fn from(b: ArrayBuilder) -> Json {
+        b.build()
+    }
+}
+
+impl Json {
+    /// Starts building a Json object; see `ObjectBuilder`.
+    pub fn object() -> ObjectBuilder {
+        ObjectBuilder::default()
+    }
+
+    /// Starts building a Json array; see `ArrayBuilder`.
+    pub fn array() -> ArrayBuilder {
+        ArrayBuilder::default()
+    }
+}
@@ -0,0 +1,428 @@
+use std::io::{self, Read, Write};
+use pacosso::{Stream, ParseResult};
+use super::*;
+use super::error;
+use super::parsing::{parse_buffer_with, end_of_data};
+use super::binary::{uint, get_bytes};
+
+// major types (in the upper three bits of the initial byte)
+const UINT: u8 = 0x00;
+const NINT: u8 = 0x20;
+const BYTES: u8 = 0x40;
+const TEXT: u8 = 0x60;
+const ARRAY: u8 = 0x80;
+const MAP: u8 = 0xa0;
+const TAG: u8 = 0xc0;
+
+// simple values and floats
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const F16: u8 = 0xf9;
+const F32: u8 = 0xfa;
+const F64: u8 = 0xfb;
+const BREAK: u8 = 0xff;
+
+// additional information for indefinite lengths
+const INDEFINITE: u8 = 31;
+
+// the self-described CBOR tag, which may precede any value
+const SELF_DESCRIBE: u64 = 55799;
+
+/// Options for the CBOR encoder.
+///
+/// Integers are always written with the smallest width
+/// and floats with the smallest width that preserves their value.
+/// With `deterministic`, the encoding additionally follows
+/// the core deterministic encoding requirements of RFC 8949, section 4.2.1,
+/// so that equal Json values are encoded to equal byte sequences.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::cbor::{to_vec_with_opts, CborOpts};
+///
+///    let jdoc = json!({"b": 1, "aa": 2, "a": 3});
+///    let v = to_vec_with_opts(&jdoc, &CborOpts::default().set_deterministic(true));
+///    assert_eq!(v, b"\xa3\x61a\x03\x61b\x01\x62aa\x02");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CborOpts {
+    /// Sort map keys by their encoding.
+    /// The default is `false`, which writes keys in the order of the map.
+    pub deterministic: bool,
+}
+
+impl CborOpts {
+    /// Sets deterministic encoding.
+    pub fn set_deterministic(self, d: bool) -> CborOpts {
+        CborOpts {
+            deterministic: d,
+        }
+    }
+}
+
+/// Encodes Json value 'j' as CBOR into writer 'w'
+/// and returns the number of bytes written.
+///
+/// Numbers without fraction are written as integers
+/// if they fit into 64 bits, all others as floats.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::cbor::encode;
+///
+///    let mut v: Vec<u8> = Vec::new();
+///    encode(&json!([1, 1000, (-1), 1.5, "a", null]), &mut v).unwrap();
+///    assert_eq!(v, b"\x86\x01\x19\x03\xe8\x20\xf9\x3e\x00\x61a\xf6");
+/// ```
+pub fn encode<W: Write>(j: &Json, w: &mut W) -> io::Result<usize> {
+    encode_with_opts(j, w, &CborOpts::default())
+}
+
+/// Like `encode`, but with explicit encoder options 'o'.
+pub fn encode_with_opts<W: Write>(j: &Json, w: &mut W, o: &CborOpts) -> io::Result<usize> {
+    let v = to_vec_with_opts(j, o);
+    w.write_all(&v)?;
+    w.flush()?;
+    Ok(v.len())
+}
+
+/// Encodes Json value 'j' as CBOR into a new byte vector.
+pub fn to_vec(j: &Json) -> Vec<u8> {
+    to_vec_with_opts(j, &CborOpts::default())
+}
+
+/// Like `to_vec`, but with explicit encoder options 'o'.
+pub fn to_vec_with_opts(j: &Json, o: &CborOpts) -> Vec<u8> {
+    let mut v = Vec::new();
+    write_value(&mut v, j, o);
+    v
+}
+
+fn write_value(v: &mut Vec<u8>, j: &Json, o: &CborOpts) {
+    match j {
+        Json::Null => v.push(NULL),
+        Json::Boolean(true) => v.push(TRUE),
+        Json::Boolean(false) => v.push(FALSE),
+        Json::Number(n) => write_number(v, *n),
+        Json::String(s) => write_text(v, s),
+        Json::Array(a) => {
+            write_head(v, ARRAY, a.len() as u64);
+            for e in a.iter() {
+                write_value(v, e, o);
+            }
+        },
+        Json::Object(m) => {
+            write_head(v, MAP, m.len() as u64);
+            if o.deterministic {
+                // the encoded keys sort by length first, then bytewise
+                let mut ks: Vec<&String> = m.keys().collect();
+                ks.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+                for k in ks {
+                    write_text(v, k);
+                    write_value(v, &m[k], o);
+                }
+            } else {
+                for (k, e) in m.iter() {
+                    write_text(v, k);
+                    write_value(v, e, o);
+                }
+            }
+        },
+    }
+}
+
+fn write_head(v: &mut Vec<u8>, major: u8, n: u64) {
+    if n < 24 {
+        v.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        v.push(major | 24);
+        v.push(n as u8);
+    } else if n <= u16::MAX as u64 {
+        v.push(major | 25);
+        v.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        v.push(major | 26);
+        v.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        v.push(major | 27);
+        v.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_text(v: &mut Vec<u8>, s: &str) {
+    write_head(v, TEXT, s.len() as u64);
+    v.extend_from_slice(s.as_bytes());
+}
+
+fn write_number(v: &mut Vec<u8>, n: f64) {
+    const LIMIT: f64 = 18446744073709551616.0; // 2^64
+
+    // -0 is a float, NaN and infinity have no integral part
+    if n.fract() == 0.0 && !(n == 0.0 && n.is_sign_negative()) && (-LIMIT .. LIMIT).contains(&n) {
+        if n >= 0.0 {
+            write_head(v, UINT, n as u64);
+        } else {
+            write_head(v, NINT, (-1 - n as i128) as u64);
+        }
+    } else if let Some(h) = to_f16(n) {
+        v.push(F16);
+        v.extend_from_slice(&h.to_be_bytes());
+    } else if (n as f32) as f64 == n {
+        v.push(F32);
+        v.extend_from_slice(&(n as f32).to_be_bytes());
+    } else {
+        v.push(F64);
+        v.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+// Returns the half precision representation of 'n',
+// if it represents 'n' exactly.
+fn to_f16(n: f64) -> Option<u16> {
+    if n.is_nan() {
+        return Some(0x7e00);
+    }
+    let f = n as f32;
+    if f as f64 != n {
+        return None;
+    }
+
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    if f == 0.0 {
+        return Some(sign);
+    }
+    if f.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+
+    let e = ((bits >> 23) & 0xff) as i32 - 127;
+    let m = bits & 0x7fffff;
+    match e {
+        -14 ..= 15 if m & 0x1fff == 0 => Some(sign | ((e + 15) as u16) << 10 | (m >> 13) as u16),
+        -24 ..= -15 => {
+            // subnormal: the implicit bit becomes part of the mantissa
+            let m = m | 0x800000;
+            let shift = 13 + (-14 - e);
+            if m & ((1 << shift) - 1) == 0 {
+                Some(sign | (m >> shift) as u16)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn from_f16(h: u16) -> f64 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let e = ((h >> 10) & 0x1f) as i32;
+    let m = (h & 0x3ff) as f64;
+    match e {
+        0 => sign * m * 2f64.powi(-24),
+        31 if m == 0.0 => sign * f64::INFINITY,
+        31 => f64::NAN,
+        _ => sign * (1.0 + m / 1024.0) * 2f64.powi(e - 15),
+    }
+}
+
+/// Decodes one CBOR data item from stream 's' into a Json value.
+/// The stream is left after the data item,
+/// so that `decode` can be called again for the next item.
+///
+/// Arrays, maps and text strings of indefinite length are accepted.
+/// Byte strings, tags (except the self-described CBOR tag),
+/// map keys that are not text strings and simple values
+/// other than false, true and null cannot be represented as Json;
+/// they yield errors starting with "unsupported".
+/// A map with the same key twice is an error, as in Json text.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::json;
+///    use jsosso::cbor::decode;
+///    use pacosso::{Stream, Opts};
+///
+///    // an indefinite-length map followed by an integer
+///    let mut input = Cursor::new(b"\xbf\x61a\x01\x61b\x9f\x02\x03\xff\xff\x18\x64".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    assert_eq!(decode(&mut s).unwrap(), json!({"a": 1, "b": [2, 3]}));
+///    assert_eq!(decode(&mut s).unwrap(), json!(100));
+///    assert!(decode(&mut s).unwrap_err().is_eof());
+/// ```
+pub fn decode<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let b = s.any_byte()?;
+    item(s, b)
+}
+
+/// Decodes a complete CBOR data item from byte slice 'b'.
+/// Nothing may follow the data item.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::cbor;
+///
+///    let j = json!({"list": [1, 2.5, "three"]});
+///    assert_eq!(cbor::from_slice(&cbor::to_vec(&j)).unwrap(), j);
+///    assert!(cbor::from_slice(b"\x40").unwrap_err().to_string().contains("unsupported byte string"));
+/// ```
+pub fn from_slice(b: &[u8]) -> error::Result<Json> {
+    Ok(parse_buffer_with(b, decode_complete)?)
+}
+
+/// Decodes a complete CBOR data item from reader 'r'.
+/// Nothing may follow the data item.
+pub fn from_reader<R: Read>(mut r: R) -> error::Result<Json> {
+    // the input is read completely, so that the stream buffers
+    // can be sized to the input (see parsing::EofGuard)
+    let mut v = Vec::new();
+    r.read_to_end(&mut v)?;
+    from_slice(&v)
+}
+
+fn decode_complete<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let j = decode(s)?;
+    end_of_data(s)?;
+    Ok(j)
+}
+
+// decodes the data item starting with initial byte 'b'
+fn item<R: Read>(s: &mut Stream<R>, b: u8) -> ParseResult<Json> {
+    let info = b & 0x1f;
+    match b & 0xe0 {
+        UINT => Ok(Json::Number(argument(s, info)? as f64)),
+        NINT => Ok(Json::Number((-1 - argument(s, info)? as i128) as f64)),
+        BYTES => s.fail("unsupported byte string", Json::Null),
+        TEXT => Ok(Json::String(text(s, info)?)),
+        ARRAY => array(s, info),
+        MAP => map(s, info),
+        TAG => match argument(s, info)? {
+            SELF_DESCRIBE => decode(s),
+            t => s.fail(&format!("unsupported tag {}", t), Json::Null),
+        },
+        _ => simple(s, info),
+    }
+}
+
+// reads the argument encoded by the additional information 'info'
+fn argument<R: Read>(s: &mut Stream<R>, info: u8) -> ParseResult<u64> {
+    let n = match info {
+        0 ..= 23 => return Ok(info as u64),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        INDEFINITE => return s.fail("unexpected indefinite length", 0),
+        _ => return s.fail(&format!("invalid additional information {}", info), 0),
+    };
+    uint(s, n)
+}
+
+fn text<R: Read>(s: &mut Stream<R>, info: u8) -> ParseResult<String> {
+    let mut v = Vec::new();
+    if info == INDEFINITE {
+        loop {
+            let b = s.any_byte()?;
+            if b == BREAK {
+                break;
+            }
+            if b & 0xe0 != TEXT || b & 0x1f == INDEFINITE {
+                return s.fail("invalid chunk in indefinite-length text string", String::new());
+            }
+            let n = argument(s, b & 0x1f)?;
+            get_bytes(s, &mut v, n)?;
+        }
+    } else {
+        let n = argument(s, info)?;
+        get_bytes(s, &mut v, n)?;
+    }
+    match String::from_utf8(v) {
+        Ok(t) => Ok(t),
+        Err(_) => s.fail("utf8 error in text string", String::new()),
+    }
+}
+
+fn array<R: Read>(s: &mut Stream<R>, info: u8) -> ParseResult<Json> {
+    let mut a = Vec::new();
+    if info == INDEFINITE {
+        loop {
+            let b = s.any_byte()?;
+            if b == BREAK {
+                break;
+            }
+            a.push(item(s, b)?);
+        }
+    } else {
+        let n = argument(s, info)?;
+        a.reserve(n.min(1024) as usize); // do not trust the length
+        for _ in 0 .. n {
+            a.push(decode(s)?);
+        }
+    }
+    Ok(Json::Array(a))
+}
+
+fn map<R: Read>(s: &mut Stream<R>, info: u8) -> ParseResult<Json> {
+    let mut m = HashMap::new();
+    if info == INDEFINITE {
+        loop {
+            let b = s.any_byte()?;
+            if b == BREAK {
+                break;
+            }
+            entry(s, &mut m, b)?;
+        }
+    } else {
+        let n = argument(s, info)?;
+        for _ in 0 .. n {
+            let b = s.any_byte()?;
+            entry(s, &mut m, b)?;
+        }
+    }
+    Ok(Json::Object(Box::new(m)))
+}
+
+// Decodes a key and its value into 'm'; as in Json objects,
+// a key must not occur twice.
+fn entry<R: Read>(s: &mut Stream<R>, m: &mut HashMap<String, Json>, b: u8) -> ParseResult<()> {
+    let k = key(s, b)?;
+    if m.contains_key(&k) {
+        return s.fail(&format!("duplicated key '{}' in map", k), ());
+    }
+    m.insert(k, decode(s)?);
+    Ok(())
+}
+
+fn key<R: Read>(s: &mut Stream<R>, b: u8) -> ParseResult<String> {
+    if b & 0xe0 != TEXT {
+        return s.fail("unsupported map key, text string expected", String::new());
+    }
+    text(s, b & 0x1f)
+}
+
+fn simple<R: Read>(s: &mut Stream<R>, info: u8) -> ParseResult<Json> {
+    match info {
+        20 => Ok(Json::Boolean(false)),
+        21 => Ok(Json::Boolean(true)),
+        22 => Ok(Json::Null),
+        25 => Ok(Json::Number(from_f16(argument(s, info)? as u16))),
+        26 => Ok(Json::Number(f32::from_bits(argument(s, info)? as u32) as f64)),
+        27 => Ok(Json::Number(f64::from_bits(argument(s, info)?))),
+        INDEFINITE => s.fail("unexpected break", Json::Null),
+        24 => {
+            let n = s.any_byte()?;
+            s.fail(&format!("unsupported simple value {}", n), Json::Null)
+        },
+        _ => s.fail(&format!("unsupported simple value {}", info), Json::Null),
+    }
+}
@@ -21,47 +21,484 @@
 /// assert_eq!(
 ///     json!({
 ///         "Sensor": 4711,
-///         "Measurement": 31.123
+///         "Measurement": -31.123,
 ///     }),
 ///     Json::Object(Box::new(vec![
 ///         ("Sensor".to_string(), Json::Number(4711.0)),
-///         ("Measurement".to_string(), Json::Number(31.123))
+///         ("Measurement".to_string(), Json::Number(-31.123))
 ///     ].into_iter().collect()))
 /// );
 /// ```
 ///
-/// Note that signed numbers need parentheses around them, e.g.:
+/// Values and keys may be arbitrary Rust expressions.
+/// Values are converted with `Json::from`, keys with `to_string`.
+/// Elements and members may be followed by a trailing comma.
 /// ```
 /// use jsosso::*;
 ///
+/// let id = "Sensor";
+/// let values = vec![1.5, 2.5];
+///
 /// assert_eq!(
 ///     json!({
-///         "Sensor": 4711,
-///         "Measurement": (-31.123)
+///         id: 4711,
+///         format!("{}s", "value"): [values[0] * 2.0, -values[1], null],
+///         "count": values.len(),
 ///     }),
-///     Json::Object(Box::new(vec![
-///         ("Sensor".to_string(), Json::Number(4711.0)),
-///         ("Measurement".to_string(), Json::Number(-31.123))
-///     ].into_iter().collect()))
+///     json!({
+///         "Sensor": 4711,
+///         "values": [3, (-2.5), null],
+///         "count": 2
+///     })
+/// );
+/// ```
+///
+/// An existing Json array or object can be spliced into an array or object
+/// with `..`; splicing anything else panics.
+/// Members spliced into an object replace members with the same key before them.
+/// ```
+/// use jsosso::*;
+///
+/// let tail = json!([3, 4]);
+/// let defaults = json!({"unit": "C", "precision": 2});
+///
+/// assert_eq!(json!([1, 2, ..tail.clone()]), json!([1, 2, 3, 4]));
+/// assert_eq!(
+///     json!({"unit": "K", ..defaults, "precision": 3}),
+///     json!({"unit": "C", "precision": 3})
 /// );
 /// ```
+///
+/// Every element and member is one level of macro recursion,
+/// so very long literals may need a higher `recursion_limit`.
 #[macro_export]
 macro_rules! json {
     (null) => {
         $crate::Json::Null
     };
-    ([ $( $element:tt ),* ]) => {
-        $crate::Json::Array(vec![
-            $( json!($element) ),*
-        ])
+    ([ $( $tt:tt )* ]) => {{
+        #[allow(unused_mut)]
+        let mut array: Vec<$crate::Json> = Vec::new();
+        $crate::json_internal!(@array array $( $tt )*);
+        $crate::Json::Array(array)
+    }};
+    ({ $( $tt:tt )* }) => {{
+        #[allow(unused_mut)]
+        let mut object: ::std::collections::HashMap<String, $crate::Json> = ::std::collections::HashMap::new();
+        $crate::json_internal!(@object object () ($( $tt )*));
+        $crate::Json::Object(Box::new(object))
+    }};
+    ( $other:expr ) => {
+        $crate::Json::from($other)
+    };
+}
+
+// The token muncher behind json!.
+// Arrays push one element after the other onto the vector 'a';
+// objects collect the tokens of a key up to the colon
+// and then insert the value into the map 'o'.
+// The pushes are spelled out as function calls,
+// since clippy would suggest vec! for literals without splices.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_internal {
+    // arrays: the end
+    (@array $a:ident) => {};
+
+    // arrays: splicing
+    (@array $a:ident .. $splice:expr , $( $rest:tt )*) => {
+        $crate::json_internal!(@splice_array $a $splice);
+        $crate::json_internal!(@array $a $( $rest )*);
+    };
+    (@array $a:ident .. $splice:expr) => {
+        $crate::json_internal!(@splice_array $a $splice);
     };
-    ({ $( $key:tt : $value:tt ),* }) => {
-        $crate::Json::Object(Box::new(vec![
-            $( ($key.to_string(), json!($value)) ),*
-          ].into_iter().collect())
-        )
+
+    // arrays: literals and nested structures
+    (@array $a:ident null $( , $( $rest:tt )* )?) => {
+        ::std::vec::Vec::push(&mut $a, $crate::Json::Null);
+        $crate::json_internal!(@array $a $( $( $rest )* )?);
     };
-    ( $other:tt ) => {
-        $crate::Json::from($other)
+    (@array $a:ident [ $( $array:tt )* ] $( , $( $rest:tt )* )?) => {
+        ::std::vec::Vec::push(&mut $a, $crate::json!([ $( $array )* ]));
+        $crate::json_internal!(@array $a $( $( $rest )* )?);
+    };
+    (@array $a:ident { $( $object:tt )* } $( , $( $rest:tt )* )?) => {
+        ::std::vec::Vec::push(&mut $a, $crate::json!({ $( $object )* }));
+        $crate::json_internal!(@array $a $( $( $rest )* )?);
+    };
+
+    // arrays: expressions
+    (@array $a:ident $value:expr , $( $rest:tt )*) => {
+        ::std::vec::Vec::push(&mut $a, $crate::Json::from($value));
+        $crate::json_internal!(@array $a $( $rest )*);
+    };
+    (@array $a:ident $value:expr) => {
+        ::std::vec::Vec::push(&mut $a, $crate::Json::from($value));
+    };
+
+    // objects: the end
+    (@object $o:ident () ()) => {};
+
+    // objects: splicing
+    (@object $o:ident () (.. $splice:expr , $( $rest:tt )*)) => {
+        $crate::json_internal!(@splice_object $o $splice);
+        $crate::json_internal!(@object $o () ($( $rest )*));
+    };
+    (@object $o:ident () (.. $splice:expr)) => {
+        $crate::json_internal!(@splice_object $o $splice);
+    };
+
+    // objects: literals and nested structures after the key
+    (@object $o:ident ($( $key:tt )+) (: null $( , $( $rest:tt )* )?)) => {
+        $o.insert(($( $key )+).to_string(), $crate::Json::Null);
+        $crate::json_internal!(@object $o () ($( $( $rest )* )?));
+    };
+    (@object $o:ident ($( $key:tt )+) (: [ $( $array:tt )* ] $( , $( $rest:tt )* )?)) => {
+        $o.insert(($( $key )+).to_string(), $crate::json!([ $( $array )* ]));
+        $crate::json_internal!(@object $o () ($( $( $rest )* )?));
+    };
+    (@object $o:ident ($( $key:tt )+) (: { $( $object:tt )* } $( , $( $rest:tt )* )?)) => {
+        $o.insert(($( $key )+).to_string(), $crate::json!({ $( $object )* }));
+        $crate::json_internal!(@object $o () ($( $( $rest )* )?));
+    };
+
+    // objects: expressions after the key
+    (@object $o:ident ($( $key:tt )+) (: $value:expr , $( $rest:tt )*)) => {
+        $o.insert(($( $key )+).to_string(), $crate::Json::from($value));
+        $crate::json_internal!(@object $o () ($( $rest )*));
+    };
+    (@object $o:ident ($( $key:tt )+) (: $value:expr)) => {
+        $o.insert(($( $key )+).to_string(), $crate::Json::from($value));
+    };
+
+    // objects: the next token of the key
+    (@object $o:ident ($( $key:tt )*) ($next:tt $( $rest:tt )*)) => {
+        $crate::json_internal!(@object $o ($( $key )* $next) ($( $rest )*));
+    };
+
+    (@splice_array $a:ident $splice:expr) => {
+        match $crate::Json::from($splice) {
+            $crate::Json::Array(v) => $a.extend(v),
+            j => panic!("cannot splice {:?} into a Json array", j),
+        }
+    };
+    (@splice_object $o:ident $splice:expr) => {
+        match $crate::Json::from($splice) {
+            $crate::Json::Object(m) => $o.extend(*m),
+            j => panic!("cannot splice {:?} into a Json object", j),
+        }
+    };
+}
+
+/// The json_match macro matches a Json value against patterns
+/// written in the syntax of the json macro
+/// and evaluates to `Some` result of the first arm that matches
+/// or to `None` if no arm matches.
+///
+/// Patterns are
+/// - `_`, which matches anything;
+/// - `null` and literals like `true`, `-1.5` or `"ok"`,
+///   which match values equal to them;
+/// - a name, which binds the value as `&Json`;
+/// - a name with a type, e.g. `id: u32`, which converts the value
+///   with `TryFrom<&Json>` and matches if the conversion succeeds;
+///   conversions are implemented for `bool`, the number types,
+///   `String`, `&str`, `&[Json]` and `&HashMap<String, Json>`;
+/// - arrays of patterns, which match arrays of the same length,
+///   or, if the last pattern is `..` or `..rest`,
+///   arrays starting with elements that match the patterns before;
+///   `rest` binds the other elements as `&[Json]`;
+/// - objects with members `"key": pattern`, `name` or `name: type`,
+///   which match objects that have all these keys with matching values;
+///   `name` is short for `"name": name`.
+///   Other members of the object are ignored.
+///
+/// Examples:
+/// ```
+/// use jsosso::*;
+///
+/// fn describe(j: &Json) -> String {
+///     json_match!(j,
+///         {"type": "point", "at": [x: f64, y: f64]} => format!("point at {}/{}", x, y),
+///         {"type": "line", "points": [_, _, ..rest]} => format!("line with {} more points", rest.len()),
+///         {id: u32, name: &str} => format!("{} #{}", name, id),
+///         [] => "nothing".to_string(),
+///         _ => "unknown".to_string(),
+///     ).unwrap()
+/// }
+/// # let _ = describe;
+/// ```
+///
+/// ```
+/// use jsosso::*;
+///
+/// let j = json!({"sensor": 4711, "values": [1.5, 2.5], "unit": "C"});
+///
+/// assert_eq!(json_match!(j, {"values": [first: f64, ..]} => first), Some(1.5));
+/// assert_eq!(json_match!(j, {sensor: u8} => sensor), None);
+/// assert_eq!(
+///     json_match!(j,
+///         {"unit": "K", values} => values.clone(),
+///         {values: &[Json]} => Json::Array(values.to_vec()),
+///     ),
+///     Some(json!([1.5, 2.5]))
+/// );
+/// ```
+///
+/// Arms are separated by commas.
+/// The arms are evaluated within labeled blocks,
+/// so `break` in an arm needs a label.
+#[macro_export]
+macro_rules! json_match {
+    ($value:expr, $( $arms:tt )*) => {
+        'json_match: {
+            let value: &$crate::Json = &$value;
+            $crate::json_pattern_internal!(@arms 'json_match value () ($( $arms )*));
+        }
+    };
+}
+
+/// The json_let macro binds the names in a pattern of `json_match!`
+/// in the current scope, much like `let ... else`.
+/// If the value does not match, the else block is evaluated;
+/// it must not return normally, i.e. it returns, breaks, continues or panics.
+/// With `else |e| { ... }`, the block gets the `jsosso::error::MatchError`,
+/// which tells where and why the value does not match.
+///
+/// Example:
+/// ```
+/// use jsosso::*;
+/// use jsosso::error::MatchError;
+///
+/// fn first_tag(j: &Json) -> Result<(u32, String), MatchError> {
+///     json_let!({"id": id: u32, "tags": [tag: String, ..]} = j else |e| {
+///         return Err(e);
+///     });
+///     Ok((id, tag))
+/// }
+///
+/// assert_eq!(
+///     first_tag(&json!({"id": 7, "tags": ["a", "b"]})),
+///     Ok((7, "a".to_string()))
+/// );
+/// assert_eq!(
+///     first_tag(&json!({"id": 7, "tags": [1]})).unwrap_err().to_string(),
+///     "Json does not match at $.tags[0]: expected a string, found 1"
+/// );
+///
+/// let j = json!([1, 2, 3]);
+/// json_let!([one, ..rest] = j else { panic!("not an array") });
+/// assert_eq!(one, &json!(1));
+/// assert_eq!(rest.len(), 2);
+/// ```
+#[macro_export]
+macro_rules! json_let {
+    ($( $tt:tt )*) => {
+        $crate::json_pattern_internal!(@let_pattern () ($( $tt )*));
+    };
+}
+
+// The token muncher behind json_match! and json_let!.
+// The pattern is compiled into a sequence of let statements
+// that take the value apart and bind the names of the pattern
+// in the scope of the caller; temporaries are hidden by hygiene.
+// Each check that fails runs 'fail', which is
+// - (break 'label) for the arms of json_match!,
+// - (else block) or (else |e| block) for json_let!.
+// The path to the current value is kept as a list of segments,
+// [index] for elements and (key) for members,
+// and is only turned into a string for the MatchError.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_pattern_internal {
+    // json_match!: collect the tokens of the pattern up to =>
+    (@arms $l:lifetime $v:ident () ()) => {
+        break $l None;
+    };
+    (@arms $l:lifetime $v:ident ($( $pat:tt )+) (=> $body:expr $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@arm $l $v ($( $pat )+) ($body) ($( $( $rest )* )?));
+    };
+    (@arms $l:lifetime $v:ident ($( $pat:tt )*) ($next:tt $( $rest:tt )*)) => {
+        $crate::json_pattern_internal!(@arms $l $v ($( $pat )* $next) ($( $rest )*));
+    };
+
+    // json_match!: an arm that always matches ends the match
+    (@arm $l:lifetime $v:ident (_) ($body:expr) $rest:tt) => {
+        break $l Some($body);
+    };
+    (@arm $l:lifetime $v:ident (null) ($body:expr) $rest:tt) => {
+        $crate::json_pattern_internal!(@refutable $l $v (null) ($body) $rest);
+    };
+    (@arm $l:lifetime $v:ident ($lit:literal) ($body:expr) $rest:tt) => {
+        $crate::json_pattern_internal!(@refutable $l $v ($lit) ($body) $rest);
+    };
+    (@arm $l:lifetime $v:ident ($n:ident) ($body:expr) $rest:tt) => {
+        let $n: &$crate::Json = $v;
+        break $l Some($body);
+    };
+    (@arm $l:lifetime $v:ident ($( $pat:tt )+) ($body:expr) $rest:tt) => {
+        $crate::json_pattern_internal!(@refutable $l $v ($( $pat )+) ($body) $rest);
+    };
+    (@refutable $l:lifetime $v:ident $pat:tt ($body:expr) ($( $rest:tt )*)) => {
+        'arm: {
+            $crate::json_pattern_internal!(@let $v $pat () (break 'arm));
+            break $l Some($body);
+        }
+        $crate::json_pattern_internal!(@arms $l $v () ($( $rest )*));
+    };
+
+    // json_let!: collect the tokens of the pattern up to = and of the value up to else
+    (@let_pattern ($( $pat:tt )*) (= $( $rest:tt )*)) => {
+        $crate::json_pattern_internal!(@let_value ($( $pat )*) () ($( $rest )*));
+    };
+    (@let_pattern ($( $pat:tt )*) ($next:tt $( $rest:tt )*)) => {
+        $crate::json_pattern_internal!(@let_pattern ($( $pat )* $next) ($( $rest )*));
+    };
+    (@let_pattern $pat:tt ()) => {
+        compile_error!("expected json_let!(pattern = value else { ... })");
+    };
+    (@let_value $pat:tt ($( $value:tt )+) (else $( $fail:tt )+)) => {
+        let value: &$crate::Json = &($( $value )+);
+        $crate::json_pattern_internal!(@let value $pat () (else $( $fail )+));
+    };
+    (@let_value $pat:tt ($( $value:tt )*) ($next:tt $( $rest:tt )*)) => {
+        $crate::json_pattern_internal!(@let_value $pat ($( $value )* $next) ($( $rest )*));
+    };
+    (@let_value $pat:tt $value:tt ()) => {
+        compile_error!("expected json_let!(pattern = value else { ... })");
+    };
+
+    // patterns: match the value 'v'
+    (@let $v:ident (_) $path:tt $fail:tt) => {
+        let _ = $v;
+    };
+    (@let $v:ident (null) $path:tt $fail:tt) => {
+        if !matches!($v, $crate::Json::Null) {
+            $crate::json_pattern_internal!(@fail $fail $path
+                $crate::error::MatchError::unexpected("null", $v));
+        }
+    };
+    (@let $v:ident ($lit:literal) $path:tt $fail:tt) => {
+        if *$v != $crate::Json::from($lit) {
+            $crate::json_pattern_internal!(@fail $fail $path
+                $crate::error::MatchError::unexpected(stringify!($lit), $v));
+        }
+    };
+    (@let $v:ident ([ $( $elements:tt )* ]) $path:tt $fail:tt) => {
+        let array = match $v {
+            $crate::Json::Array(array) => array,
+            _ => $crate::json_pattern_internal!(@fail $fail $path
+                     $crate::error::MatchError::unexpected("an array", $v)),
+        };
+        $crate::json_pattern_internal!(@array array (0) $path $fail ($( $elements )*));
+    };
+    (@let $v:ident ({ $( $members:tt )* }) $path:tt $fail:tt) => {
+        let object = match $v {
+            $crate::Json::Object(object) => object,
+            _ => $crate::json_pattern_internal!(@fail $fail $path
+                     $crate::error::MatchError::unexpected("an object", $v)),
+        };
+        $crate::json_pattern_internal!(@object object $path $fail ($( $members )*));
+    };
+    (@let $v:ident ($n:ident : $t:ty) $path:tt $fail:tt) => {
+        let $n: $t = match <$t as ::std::convert::TryFrom<&$crate::Json>>::try_from($v) {
+            Ok(value) => value,
+            #[allow(unused_variables)]
+            Err(error) => $crate::json_pattern_internal!(@fail $fail $path error),
+        };
+    };
+    (@let $v:ident ($n:ident) $path:tt $fail:tt) => {
+        let $n: &$crate::Json = $v;
+    };
+
+    // arrays: the elements of 'a' from index 'i' on
+    (@array $a:ident ($i:expr) $path:tt $fail:tt ()) => {
+        if $a.len() != $i {
+            $crate::json_pattern_internal!(@fail $fail $path
+                $crate::error::MatchError::new(format!("expected {} elements, found {}", $i, $a.len())));
+        }
+    };
+    (@array $a:ident ($i:expr) $path:tt $fail:tt (.. $( , )?)) => {
+        let _ = $a;
+    };
+    (@array $a:ident ($i:expr) $path:tt $fail:tt (.. $rest:ident $( , )?)) => {
+        let $rest: &[$crate::Json] = &$a[$i ..];
+    };
+    (@array $a:ident ($i:expr) $path:tt $fail:tt ($n:ident : $t:ty $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@element $a ($i) $path $fail ($n : $t));
+        $crate::json_pattern_internal!(@array $a ($i + 1) $path $fail ($( $( $rest )* )?));
+    };
+    (@array $a:ident ($i:expr) $path:tt $fail:tt ($lit:literal $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@element $a ($i) $path $fail ($lit));
+        $crate::json_pattern_internal!(@array $a ($i + 1) $path $fail ($( $( $rest )* )?));
+    };
+    (@array $a:ident ($i:expr) $path:tt $fail:tt ($pat:tt $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@element $a ($i) $path $fail ($pat));
+        $crate::json_pattern_internal!(@array $a ($i + 1) $path $fail ($( $( $rest )* )?));
+    };
+    (@element $a:ident ($i:expr) ($( $path:tt )*) $fail:tt $pat:tt) => {
+        let element = match $a.get($i) {
+            Some(element) => element,
+            None => $crate::json_pattern_internal!(@fail $fail ($( $path )*)
+                        $crate::error::MatchError::new(format!(
+                            "expected at least {} elements, found {}", $i + 1, $a.len()))),
+        };
+        $crate::json_pattern_internal!(@let element $pat ($( $path )* [$i]) $fail);
+    };
+
+    // objects: the members of 'o'
+    (@object $o:ident $path:tt $fail:tt ()) => {
+        let _ = $o;
+    };
+    (@object $o:ident $path:tt $fail:tt ($key:literal : $n:ident : $t:ty $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@member $o ($key) $path $fail ($n : $t));
+        $crate::json_pattern_internal!(@object $o $path $fail ($( $( $rest )* )?));
+    };
+    (@object $o:ident $path:tt $fail:tt ($key:literal : $lit:literal $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@member $o ($key) $path $fail ($lit));
+        $crate::json_pattern_internal!(@object $o $path $fail ($( $( $rest )* )?));
+    };
+    (@object $o:ident $path:tt $fail:tt ($key:literal : $pat:tt $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@member $o ($key) $path $fail ($pat));
+        $crate::json_pattern_internal!(@object $o $path $fail ($( $( $rest )* )?));
+    };
+    (@object $o:ident $path:tt $fail:tt ($n:ident : $t:ty $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@member $o (stringify!($n)) $path $fail ($n : $t));
+        $crate::json_pattern_internal!(@object $o $path $fail ($( $( $rest )* )?));
+    };
+    (@object $o:ident $path:tt $fail:tt ($n:ident $( , $( $rest:tt )* )?)) => {
+        $crate::json_pattern_internal!(@member $o (stringify!($n)) $path $fail ($n));
+        $crate::json_pattern_internal!(@object $o $path $fail ($( $( $rest )* )?));
+    };
+    (@member $o:ident ($key:expr) ($( $path:tt )*) $fail:tt $pat:tt) => {
+        let member = match $o.get($key) {
+            Some(member) => member,
+            None => $crate::json_pattern_internal!(@fail $fail ($( $path )*)
+                        $crate::error::MatchError::new(format!(
+                            "missing member {}", $crate::Json::from($key)))),
+        };
+        $crate::json_pattern_internal!(@let member $pat ($( $path )* ($key)) $fail);
+    };
+
+    // failures
+    (@fail (break $l:lifetime) $path:tt $error:expr) => {
+        break $l
+    };
+    (@fail (else $b:block) $path:tt $error:expr) => {
+        $b
+    };
+    (@fail (else | $e:ident | $b:block) ($( $segment:tt )*) $error:expr) => {{
+        #[allow(unused_mut)]
+        let mut path = String::new();
+        $( $crate::json_pattern_internal!(@segment path $segment); )*
+        let $e = $crate::error::MatchError::within($error, &path);
+        $b
+    }};
+    (@segment $p:ident [$i:expr]) => {
+        $crate::error::MatchError::push_index(&mut $p, $i)
+    };
+    (@segment $p:ident ($key:expr)) => {
+        $crate::error::MatchError::push_key(&mut $p, $key)
     };
 }
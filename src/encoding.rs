@@ -0,0 +1,311 @@
+use std::io::{self, Read, ErrorKind};
+use super::parsing::{Utf8Policy, push_char};
+
+/// The Unicode encodings a Json text may come in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8 (the default).
+    Utf8,
+    /// UTF-16, little endian.
+    Utf16Le,
+    /// UTF-16, big endian.
+    Utf16Be,
+    /// UTF-32, little endian.
+    Utf32Le,
+    /// UTF-32, big endian.
+    Utf32Be,
+}
+
+/// Detects the encoding of a Json text from its first bytes
+/// and returns the encoding together with the length of the
+/// byte order mark (0 if there is none).
+///
+/// Without byte order mark, the encoding is derived from the pattern
+/// of null bytes in the first four bytes as described in RFC 4627:
+/// since the first two characters of a Json text are ASCII,
+///
+/// ```text
+///  00 00 00 xx  UTF-32BE
+///  00 xx 00 xx  UTF-16BE
+///  xx 00 00 00  UTF-32LE
+///  xx 00 xx 00  UTF-16LE
+///  xx xx xx xx  UTF-8
+/// ```
+///
+/// Example:
+///
+/// ```
+/// use jsosso::encoding::{detect, Encoding};
+///
+/// assert_eq!(detect(b"\xef\xbb\xbf[1]"), (Encoding::Utf8, 3));
+/// assert_eq!(detect(b"\xff\xfe[\x00"), (Encoding::Utf16Le, 2));
+/// assert_eq!(detect(b"\x00[\x001"), (Encoding::Utf16Be, 0));
+/// assert_eq!(detect(b"[1]"), (Encoding::Utf8, 0));
+/// ```
+pub fn detect(b: &[u8]) -> (Encoding, usize) {
+    match b {
+        [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+        [0x00, 0x00, 0xfe, 0xff, ..] => (Encoding::Utf32Be, 4),
+        [0xff, 0xfe, 0x00, 0x00, ..] => (Encoding::Utf32Le, 4),
+        [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+        [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+        [0x00, 0x00, 0x00, _, ..] => (Encoding::Utf32Be, 0),
+        [_, 0x00, 0x00, 0x00, ..] => (Encoding::Utf32Le, 0),
+        [0x00, _, ..] => (Encoding::Utf16Be, 0),
+        [_, 0x00, ..] => (Encoding::Utf16Le, 0),
+        _ => (Encoding::Utf8, 0),
+    }
+}
+
+/// A reader that transcodes its input to UTF-8.
+/// The encoding is detected from the first bytes of the input (see `detect`);
+/// a byte order mark is removed.
+/// Use it to parse Json texts that are not encoded in UTF-8:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::parsing::parse;
+/// use jsosso::encoding::Decoder;
+/// use jsosso::parsing::Utf8Policy;
+/// use pacosso::{Stream, Opts};
+///
+/// let utf16: Vec<u8> = "\u{feff}\"\u{1d11e} h\u{e9}llo\"".encode_utf16()
+///                      .flat_map(|u| u.to_le_bytes())
+///                      .collect();
+///
+/// let mut input = Decoder::new(&utf16[..], Utf8Policy::Strict);
+/// let mut s = Stream::new(Opts::default(), &mut input);
+///
+/// assert_eq!(parse(&mut s).unwrap(), Json::from("\u{1d11e} h\u{e9}llo"));
+/// ```
+///
+/// Code units that do not encode a valid Unicode character
+/// (e.g. unpaired surrogates) are handled according to the `Utf8Policy`:
+/// they are either reported as `InvalidData` I/O error
+/// or replaced by U+FFFD (the replacement character).
+/// With `Preserve`, unpaired surrogates are written as `\u` escapes,
+/// so that inside a string the parser's policy applies to them.
+/// UTF-8 input is passed through unchanged.
+pub struct Decoder<R: Read> {
+    input: R,
+    policy: Utf8Policy,
+    encoding: Option<Encoding>,
+    raw: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+const CHUNK: usize = 4096;
+
+impl<R: Read> Decoder<R> {
+    /// Creates a decoder on top of `input`.
+    pub fn new(input: R, policy: Utf8Policy) -> Decoder<R> {
+        Decoder {
+            input,
+            policy,
+            encoding: None,
+            raw: Vec::with_capacity(CHUNK),
+            out: Vec::with_capacity(CHUNK),
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Returns the detected encoding or None
+    /// if nothing has been read so far.
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    // reads more raw bytes; returns false on end of input
+    fn fill(&mut self) -> io::Result<bool> {
+        let l = self.raw.len();
+        self.raw.resize(l + CHUNK, 0);
+        loop {
+            match self.input.read(&mut self.raw[l..]) {
+                Ok(n) => {
+                    self.raw.truncate(l + n);
+                    return Ok(n > 0);
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.raw.truncate(l);
+                    return Err(e);
+                },
+            }
+        }
+    }
+
+    fn detect(&mut self) -> io::Result<()> {
+        while self.raw.len() < 4 && !self.eof {
+            if !self.fill()? {
+                self.eof = true;
+            }
+        }
+        let (e, n) = detect(&self.raw);
+        self.raw.drain(.. n);
+        self.encoding = Some(e);
+        Ok(())
+    }
+
+    fn invalid(&mut self, u: u32) -> io::Result<()> {
+        match self.policy {
+            Utf8Policy::Lossy => {
+                push_char(&mut self.out, char::REPLACEMENT_CHARACTER);
+                Ok(())
+            },
+            Utf8Policy::Preserve if (0xd800 .. 0xe000).contains(&u) => {
+                self.out.extend_from_slice(format!("\\u{:04x}", u).as_bytes());
+                Ok(())
+            },
+            Utf8Policy::Strict | Utf8Policy::Preserve => Err(io::Error::new(ErrorKind::InvalidData,
+                format!("invalid code unit {:#x} in {:?} input", u, self.encoding))),
+        }
+    }
+
+    // transcodes as many complete characters in raw as possible
+    fn transcode(&mut self) -> io::Result<()> {
+        let e = self.encoding.unwrap_or(Encoding::Utf8);
+        let mut i = 0;
+        match e {
+            Encoding::Utf8 => {
+                self.out.append(&mut self.raw);
+            },
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let unit = |b: &[u8]| if e == Encoding::Utf16Le {
+                    u16::from_le_bytes([b[0], b[1]])
+                } else {
+                    u16::from_be_bytes([b[0], b[1]])
+                };
+                while i + 2 <= self.raw.len() {
+                    let a = unit(&self.raw[i ..]);
+                    if (0xd800 .. 0xdc00).contains(&a) {
+                        if i + 4 > self.raw.len() && !self.eof {
+                            break; // wait for the low surrogate
+                        }
+                        if i + 4 <= self.raw.len() {
+                            let b = unit(&self.raw[i + 2 ..]);
+                            if (0xdc00 .. 0xe000).contains(&b) {
+                                let c = 0x10000 + (((a - 0xd800) as u32) << 10) + (b - 0xdc00) as u32;
+                                self.push_u32(c)?;
+                                i += 4;
+                                continue;
+                            }
+                        }
+                    }
+                    self.push_u32(a as u32)?;
+                    i += 2;
+                }
+                self.raw.drain(.. i);
+            },
+            Encoding::Utf32Le | Encoding::Utf32Be => {
+                while i + 4 <= self.raw.len() {
+                    let b = [self.raw[i], self.raw[i+1], self.raw[i+2], self.raw[i+3]];
+                    let c = if e == Encoding::Utf32Le {
+                        u32::from_le_bytes(b)
+                    } else {
+                        u32::from_be_bytes(b)
+                    };
+                    self.push_u32(c)?;
+                    i += 4;
+                }
+                self.raw.drain(.. i);
+            },
+        }
+
+        // incomplete code unit at the end of the input
+        if self.eof && !self.raw.is_empty() {
+            self.raw.clear();
+            return self.invalid(0);
+        }
+        Ok(())
+    }
+
+    fn push_u32(&mut self, c: u32) -> io::Result<()> {
+        match char::from_u32(c) {
+            Some(ch) => {
+                push_char(&mut self.out, ch);
+                Ok(())
+            },
+            None => self.invalid(c),
+        }
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.encoding.is_none() {
+            self.detect()?;
+        }
+
+        while self.pos >= self.out.len() {
+            self.out.clear();
+            self.pos = 0;
+            if self.raw.is_empty() || self.encoding != Some(Encoding::Utf8) {
+                if self.eof && self.raw.is_empty() {
+                    return Ok(0);
+                }
+                if !self.eof && !self.fill()? {
+                    self.eof = true;
+                }
+            }
+            self.transcode()?;
+        }
+
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[.. n].copy_from_slice(&self.out[self.pos .. self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Encodes binary data that Json cannot represent
+// in base64 (RFC 4648, with padding).
+pub(crate) fn base64_encode(b: &[u8]) -> String {
+    let mut s = String::with_capacity(b.len().div_ceil(3) * 4);
+    for c in b.chunks(3) {
+        let n = (c[0] as u32) << 16
+              | (*c.get(1).unwrap_or(&0) as u32) << 8
+              | *c.get(2).unwrap_or(&0) as u32;
+        for i in 0 .. 4 {
+            if i <= c.len() {
+                s.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+// Decodes base64 (RFC 4648); padding is optional.
+pub(crate) fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    if s.len() % 4 == 1 {
+        return None;
+    }
+    let mut v = Vec::with_capacity(s.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let d = match c {
+            b'A' ..= b'Z' => c - b'A',
+            b'a' ..= b'z' => c - b'a' + 26,
+            b'0' ..= b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        n = n << 6 | d as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            v.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(v)
+}
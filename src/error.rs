@@ -0,0 +1,146 @@
+use std::io;
+use std::fmt;
+use pacosso::ParseError;
+use super::Json;
+
+/// The error type of the convenience entry points
+/// like `Json::from_str` or `Json::from_file`.
+/// It wraps parsing errors and I/O errors.
+#[derive(Debug)]
+pub enum Error {
+    /// The input is not a valid Json document.
+    Parse(ParseError),
+    /// Reading the input failed.
+    Io(io::Error),
+    /// A value is larger than the configured maximum size (in bytes).
+    TooLarge(usize),
+}
+
+/// Result type of the convenience entry points.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns true if the input ended before the Json value was complete.
+    pub fn is_eof(&self) -> bool {
+        match self {
+            Error::Parse(e) => e.is_eof(),
+            Error::Io(e) => e.kind() == io::ErrorKind::UnexpectedEof,
+            Error::TooLarge(_) => false,
+        }
+    }
+
+    /// Returns true if reading the input failed.
+    pub fn is_io_error(&self) -> bool {
+        matches!(self, Error::Io(_))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(e) => e.fmt(f),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::TooLarge(n) => write!(f, "value exceeds the maximum size of {} bytes", n),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::TooLarge(_) => None,
+        }
+    }
+}
+
+/// I/O errors reported by the parser become `Error::Io`.
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        match e {
+            ParseError::IOError(e) => Error::Io(e),
+            e => Error::Parse(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// The error of `json_let!` and of typed captures
+/// when a Json value does not have the shape of a pattern.
+/// The path leads from the matched value to the mismatch,
+/// e.g. `$.tags[0]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchError {
+    path: String,
+    message: String,
+}
+
+impl MatchError {
+    /// Creates an error at the matched value itself.
+    /// Implementations of `TryFrom<&Json>` for typed captures use it.
+    pub fn new<S: Into<String>>(message: S) -> MatchError {
+        MatchError {
+            path: String::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns the path to the mismatch, starting with `$`.
+    pub fn path(&self) -> String {
+        format!("${}", self.path)
+    }
+
+    /// Returns the description of the mismatch without the path.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Creates an error for a value with the wrong type or content.
+    #[doc(hidden)]
+    pub fn unexpected(expected: &str, found: &Json) -> MatchError {
+        let found = match found {
+            Json::Array(v) => format!("an array of {} elements", v.len()),
+            Json::Object(m) => format!("an object with {} members", m.len()),
+            j => j.to_string(),
+        };
+        MatchError::new(format!("expected {}, found {}", expected, found))
+    }
+
+    /// Moves the error into the value at 'prefix'.
+    #[doc(hidden)]
+    pub fn within(mut self, prefix: &str) -> MatchError {
+        self.path.insert_str(0, prefix);
+        self
+    }
+
+    /// Appends the member 'key' to 'path'.
+    #[doc(hidden)]
+    pub fn push_key(path: &mut String, key: &str) {
+        if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            path.push('.');
+            path.push_str(key);
+        } else {
+            path.push_str(&format!("[{}]", Json::from(key)));
+        }
+    }
+
+    /// Appends the element 'index' to 'path'.
+    #[doc(hidden)]
+    pub fn push_index(path: &mut String, index: usize) {
+        path.push_str(&format!("[{}]", index));
+    }
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Json does not match at {}: {}", self.path(), self.message)
+    }
+}
+
+impl std::error::Error for MatchError {}
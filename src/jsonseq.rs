@@ -0,0 +1,245 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::fmt;
+use pacosso::{Stream, ParseResult, ParseError};
+use super::*;
+use super::parsing::{parse, parse_buffer_with, end_of_input};
+
+/// The record separator (RS) that starts every Json text in a sequence.
+pub const RS: u8 = 0x1e;
+
+/// An error in a Json text sequence together with
+/// the number of the record (starting at 1) in which it occurred.
+#[derive(Debug)]
+pub struct RecordError {
+    /// The record in which the error occurred.
+    pub record: u64,
+    /// True if the record was not terminated by a linebreak
+    /// and, hence, is probably truncated.
+    pub truncated: bool,
+    /// The error itself; I/O errors are reported as `ParseError::IOError`.
+    pub error: ParseError,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "record {}: {}", self.record, self.error)
+    }
+}
+
+impl std::error::Error for RecordError { }
+
+/// Reads Json values from a Json text sequence as defined in [RFC 7464].
+/// Each Json text is preceded by a record separator (0x1E)
+/// and followed by a linebreak.
+///
+/// The reader is an iterator yielding one result per record.
+/// Empty records are skipped. Following the RFC, a record
+/// that cannot be parsed is reported as an error and the reader
+/// recovers by continuing with the next record separator.
+/// If the record is not terminated by a linebreak,
+/// the error is marked as truncated.
+/// A top-level number, boolean or null that is not followed
+/// by whitespace may have been truncated and is reported as an error, too.
+/// Bytes before the first record separator are reported as an error
+/// with record number 0. Only I/O errors end the iteration.
+///
+/// [RFC 7464]: https://www.rfc-editor.org/rfc/rfc7464
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::jsonseq::Reader;
+///
+/// let input = b"\x1e{\"id\": 1}\n\x1e[1, 2\x1e123\x1e\"four\"\n";
+/// let vs: Vec<_> = Reader::new(&input[..]).collect();
+///
+/// assert_eq!(vs.len(), 4);
+/// assert!(vs[0].is_ok());
+/// assert!(match &vs[1] {
+///     Err(e) => e.record == 2 && e.truncated,
+///     Ok(v) => panic!("unexpected value: {:?}", v),
+/// });
+/// assert!(match &vs[2] {
+///     Err(e) => e.record == 3 && e.truncated,
+///     Ok(v) => panic!("unexpected value: {:?}", v),
+/// });
+/// assert!(match &vs[3] {
+///     Ok(Json::String(s)) => s == "four",
+///     _ => false,
+/// });
+/// ```
+pub struct Reader<R: Read> {
+    input: BufReader<R>,
+    record: u64,
+    separators: u64,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a Json text sequence reader on top of `input`.
+    pub fn new(input: R) -> Reader<R> {
+        Reader {
+            input: BufReader::new(input),
+            record: 0,
+            separators: 0,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the number of the record read last.
+    pub fn record(&self) -> u64 {
+        self.record
+    }
+
+    /// Consumes the reader and returns the underlying input.
+    /// Data buffered by the reader, but not yet parsed, is lost.
+    pub fn into_inner(self) -> R {
+        self.input.into_inner()
+    }
+
+    fn parse_record(&mut self) -> Result<Json, RecordError> {
+        parse_buffer_with(&self.buf, parse_record).map_err(|e| self.error(e))
+    }
+
+    fn error(&self, e: ParseError) -> RecordError {
+        RecordError {
+            record: self.record,
+            truncated: self.buf.last() != Some(&b'\n'),
+            error: e,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Json, RecordError>;
+
+    fn next(&mut self) -> Option<Result<Json, RecordError>> {
+        while !self.done {
+            self.buf.clear();
+            match self.input.read_until(RS, &mut self.buf) {
+                Ok(0) => {
+                    self.done = true;
+                },
+                Ok(_) => {
+                    // the bytes we have read belong to the record
+                    // started by the previous RS
+                    self.record = self.separators;
+                    if self.buf.last() == Some(&RS) {
+                        self.buf.pop();
+                        self.separators += 1;
+                    } else {
+                        self.done = true;
+                    }
+
+                    // ignore consecutive record separators
+                    if self.buf.is_empty() {
+                        continue;
+                    }
+
+                    // whatever precedes the first RS is not a record
+                    if self.record == 0 {
+                        if self.buf.iter().all(|b| b.is_ascii_whitespace()) {
+                            continue;
+                        }
+                        let e = parse_buffer_with(&self.buf, |s| {
+                            s.fail("record separator expected", Json::Null)
+                        });
+                        return Some(e.map_err(|e| self.error(e)));
+                    }
+
+                    return Some(self.parse_record());
+                },
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(RecordError {
+                        record: self.separators,
+                        truncated: true,
+                        error: ParseError::IOError(e),
+                    }));
+                },
+            }
+        }
+        None
+    }
+}
+
+// Parses a record that must contain exactly one Json text.
+// Numbers, booleans and null are not self-delimiting;
+// if they are not followed by whitespace, they may be truncated.
+fn parse_record<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let j = parse(s)?;
+
+    if let Json::Number(_) | Json::Boolean(_) | Json::Null = j {
+        match s.peek_byte() {
+            Ok(_) => (),
+            Err(e) if e.is_eof() => return s.fail(
+                "truncated record: value not followed by whitespace", Json::Null
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+
+    end_of_input(s)?;
+    Ok(j)
+}
+
+/// Writes Json values as a Json text sequence as defined in [RFC 7464].
+/// Each value is serialized compactly, preceded by a record separator
+/// and followed by a linebreak.
+///
+/// [RFC 7464]: https://www.rfc-editor.org/rfc/rfc7464
+///
+/// Example:
+///
+/// ```
+/// use jsosso::json;
+/// use jsosso::jsonseq::Writer;
+///
+/// let mut w = Writer::new(Vec::new());
+/// w.write(&json!([1, 2])).unwrap();
+/// w.write(&json!(true)).unwrap();
+///
+/// assert_eq!(w.into_inner(), b"\x1e[1,2]\n\x1etrue\n".to_vec());
+/// ```
+pub struct Writer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a Json text sequence writer on top of `output`.
+    pub fn new(output: W) -> Writer<W> {
+        Writer {
+            output,
+        }
+    }
+
+    /// Writes one Json value framed by record separator and linebreak
+    /// and returns the number of bytes written.
+    pub fn write(&mut self, j: &Json) -> io::Result<usize> {
+        self.output.write_all(&[RS])?;
+        let n = j.serialize_compact(&mut self.output)?;
+        self.output.write_all(b"\n")?;
+        self.output.flush()?;
+        Ok(n + 2)
+    }
+
+    /// Writes all values of `js` and returns the number of bytes written.
+    pub fn write_all<'a, I>(&mut self, js: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = &'a Json>,
+    {
+        let mut n = 0;
+        for j in js {
+            n += self.write(j)?;
+        }
+        Ok(n)
+    }
+
+    /// Consumes the writer and returns the underlying output.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
@@ -1,12 +1,22 @@
-//! Jsosso is a simple Json parser.
-//! It's main purpose is to serve as a demonstrator for [`pacosso`].
-//! As such, it highlights simplicity, not performance
-//! or other features you may expect from a full-fledged Json parser.
+//! Jsosso is a Json parser built on [`pacosso`].
+//! It started as a demonstrator for pacosso and still keeps its code simple,
+//! but it also cares for performance where it counts:
+//! numbers are converted without allocation
+//! (with an integer fast path and Eisel-Lemire for floats)
+//! and whitespace is skipped by peeking instead of by failing parsers.
 //!
 //! This crate implements a Json Enum and contains modules
+//! - to build Json objects and arrays with fluent builders
 //! - to serialise Json values
-//! - to parse Json values from streams
-//! - and to generate random Json values.
+//! - to parse Json values from streams, strings, byte slices and files
+//! - to transcode UTF-16 and UTF-32 input to UTF-8
+//! - to read and write Json Lines (NDJSON)
+//! - to read and write Json text sequences (RFC 7464)
+//! - to parse input that arrives in chunks with a push parser
+//! - to encode and decode Json values as CBOR, MessagePack, BSON and UBJSON
+//! - to parse and serialize asynchronously with tokio (feature `async`)
+//! - to generate random Json values, documents of a given size and malformed documents
+//! - and to test properties with proptest and quickcheck (features `proptest` and `quickcheck`).
 //!
 //! It also provides an executable with some examples and benchmarks.
 //!
@@ -16,7 +26,7 @@ use std::collections::HashMap;
 use pacosso::{Stream, ParseResult, ParseError};
 
 /// Representation of a Json value.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Json {
     /// Represents a Json 'null' value.
     Null,
@@ -67,6 +77,106 @@ impl_from_num_for_json!(
     u8 i8 u16 i16 u32 i32 u64 i64 usize isize f32 f64
 );
 
+// The conversions for the typed captures of json_match! and json_let!
+impl<'a> TryFrom<&'a Json> for bool {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<bool, error::MatchError> {
+        match j {
+            Json::Boolean(b) => Ok(*b),
+            j => Err(error::MatchError::unexpected("a boolean", j)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for &'a str {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<&'a str, error::MatchError> {
+        match j {
+            Json::String(s) => Ok(s),
+            j => Err(error::MatchError::unexpected("a string", j)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for String {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<String, error::MatchError> {
+        <&str>::try_from(j).map(|s| s.to_string())
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for &'a [Json] {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<&'a [Json], error::MatchError> {
+        match j {
+            Json::Array(v) => Ok(v),
+            j => Err(error::MatchError::unexpected("an array", j)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for &'a HashMap<String, Json> {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<&'a HashMap<String, Json>, error::MatchError> {
+        match j {
+            Json::Object(m) => Ok(m),
+            j => Err(error::MatchError::unexpected("an object", j)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for f64 {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<f64, error::MatchError> {
+        match j {
+            Json::Number(n) => Ok(*n),
+            j => Err(error::MatchError::unexpected("a number", j)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Json> for f32 {
+    type Error = error::MatchError;
+
+    fn try_from(j: &'a Json) -> Result<f32, error::MatchError> {
+        f64::try_from(j).map(|n| n as f32)
+    }
+}
+
+// TryFrom<&Json> for all integer types;
+// the number must be integral and in the range of the type
+macro_rules! impl_try_from_json_for_int {
+    ( $( $t:ident)* ) => {
+        $(
+            impl<'a> TryFrom<&'a Json> for $t {
+                type Error = error::MatchError;
+
+                fn try_from(j: &'a Json) -> Result<$t, error::MatchError> {
+                    match j {
+                        // MAX as f64 may round up, hence the + 1.0
+                        Json::Number(n) if n.fract() == 0.0 &&
+                                           *n >= $t::MIN as f64 &&
+                                           *n < $t::MAX as f64 + 1.0 => Ok(*n as $t),
+                        j => Err(error::MatchError::unexpected(
+                            concat!("an integer of type ", stringify!($t)), j,
+                        )),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_json_for_int!(
+    u8 i8 u16 i16 u32 i32 u64 i64 usize isize
+);
+
 
 /// Implements the Json serialiser.
 pub mod serializing;
@@ -74,11 +184,58 @@ pub mod serializing;
 /// Implements the Json parser.
 pub mod parsing;
 
+/// Implements the error types of the convenience entry points and of pattern matching.
+pub mod error;
+
+// Implements the conversion of decimal numbers to f64.
+mod number;
+
+// Implements reading integers and byte strings shared by the binary formats.
+mod binary;
+
+/// Implements encoding detection and transcoding of UTF-16 and UTF-32 input.
+pub mod encoding;
+
+/// Implements reader and writer for Json Lines (NDJSON).
+pub mod ndjson;
+
+/// Implements reader and writer for Json text sequences (RFC 7464).
+pub mod jsonseq;
+
+/// Implements a push parser for input that arrives in chunks.
+pub mod push;
+
+/// Implements CBOR (RFC 8949) encoding and decoding of Json values.
+pub mod cbor;
+
+/// Implements MessagePack encoding and decoding of Json values.
+pub mod msgpack;
+
+/// Implements BSON encoding and decoding of Json values
+/// with Extended JSON v2 for the types Json does not have.
+pub mod bson;
+
+/// Implements UBJSON encoding and decoding of Json values.
+pub mod ubjson;
+
+/// Implements parsing and serialization for tokio's asynchronous I/O
+/// (requires feature `async`).
+#[cfg(feature = "async")]
+pub mod async_io;
+
 /// Implements and embedded Json representation language. 
 #[macro_use] mod dsl;
 
+/// Implements fluent builders for Json objects and arrays.
+pub mod builder;
+
 /// Implements the random Json value generator. 
 pub mod arbitrary;
 
+/// Implements property-based testing with proptest (feature `proptest`)
+/// and quickcheck (feature `quickcheck`).
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+pub mod property;
+
 #[cfg(test)]
 mod test;
@@ -0,0 +1,387 @@
+use std::io::{self, Read, Write};
+use pacosso::{Stream, ParseResult};
+use super::*;
+use super::error;
+use super::encoding::base64_encode;
+use super::parsing::{parse_buffer_with, end_of_data};
+use super::binary::{uint, get_bytes};
+
+// formats that are not ranges of fixed formats
+const NIL: u8 = 0xc0;
+const FALSE: u8 = 0xc2;
+const TRUE: u8 = 0xc3;
+const BIN8: u8 = 0xc4;
+const BIN16: u8 = 0xc5;
+const BIN32: u8 = 0xc6;
+const EXT8: u8 = 0xc7;
+const EXT16: u8 = 0xc8;
+const EXT32: u8 = 0xc9;
+const FLOAT32: u8 = 0xca;
+const FLOAT64: u8 = 0xcb;
+const UINT8: u8 = 0xcc;
+const UINT16: u8 = 0xcd;
+const UINT32: u8 = 0xce;
+const UINT64: u8 = 0xcf;
+const INT8: u8 = 0xd0;
+const INT16: u8 = 0xd1;
+const INT32: u8 = 0xd2;
+const INT64: u8 = 0xd3;
+const FIXEXT1: u8 = 0xd4;
+const FIXEXT16: u8 = 0xd8;
+const STR8: u8 = 0xd9;
+const STR16: u8 = 0xda;
+const STR32: u8 = 0xdb;
+const ARRAY16: u8 = 0xdc;
+const ARRAY32: u8 = 0xdd;
+const MAP16: u8 = 0xde;
+const MAP32: u8 = 0xdf;
+
+// the fixed formats carry a small value in the lower bits
+const FIXMAP: u8 = 0x80;
+const FIXARRAY: u8 = 0x90;
+const FIXSTR: u8 = 0xa0;
+
+/// How the decoder treats extension types, which Json cannot represent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtPolicy {
+    /// Extension types are errors.
+    #[default]
+    Reject,
+    /// Extension types become objects of the form
+    /// `{"type": <type>, "data": "<base64>"}`.
+    Object,
+}
+
+/// Options for the MessagePack decoder.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::msgpack::{from_slice, from_slice_with_opts, MsgpackOpts, ExtPolicy};
+///
+///    // the timestamp extension (type -1) with 32 bit seconds
+///    let input = b"\xd6\xff\x00\x00\x00\x01";
+///    assert!(from_slice(input).is_err());
+///
+///    let o = MsgpackOpts::default().set_ext_policy(ExtPolicy::Object);
+///    assert_eq!(from_slice_with_opts(input, &o).unwrap(), json!({"type": (-1), "data": "AAAAAQ=="}));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsgpackOpts {
+    /// Policy for extension types. The default is `Reject`.
+    pub ext: ExtPolicy,
+}
+
+impl MsgpackOpts {
+    /// Sets the policy for extension types.
+    pub fn set_ext_policy(self, p: ExtPolicy) -> MsgpackOpts {
+        MsgpackOpts {
+            ext: p,
+        }
+    }
+}
+
+/// Encodes Json value 'j' as MessagePack into writer 'w'
+/// and returns the number of bytes written.
+///
+/// Each value is written in the most compact format:
+/// numbers without fraction as integers of the smallest width
+/// if they fit into 64 bits, other numbers as float 32
+/// if that preserves their value and as float 64 otherwise;
+/// strings, arrays and maps with the smallest length field.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::msgpack::encode;
+///
+///    let mut v: Vec<u8> = Vec::new();
+///    encode(&json!([1, 300, (-1), 1.5, "a", null]), &mut v).unwrap();
+///    assert_eq!(v, b"\x96\x01\xcd\x01\x2c\xff\xca\x3f\xc0\x00\x00\xa1a\xc0");
+/// ```
+pub fn encode<W: Write>(j: &Json, w: &mut W) -> io::Result<usize> {
+    let v = to_vec(j);
+    w.write_all(&v)?;
+    w.flush()?;
+    Ok(v.len())
+}
+
+/// Encodes Json value 'j' as MessagePack into a new byte vector.
+pub fn to_vec(j: &Json) -> Vec<u8> {
+    let mut v = Vec::new();
+    write_value(&mut v, j);
+    v
+}
+
+fn write_value(v: &mut Vec<u8>, j: &Json) {
+    match j {
+        Json::Null => v.push(NIL),
+        Json::Boolean(true) => v.push(TRUE),
+        Json::Boolean(false) => v.push(FALSE),
+        Json::Number(n) => write_number(v, *n),
+        Json::String(s) => write_str(v, s),
+        Json::Array(a) => {
+            write_len(v, a.len(), FIXARRAY, 15, None, ARRAY16, ARRAY32);
+            for e in a.iter() {
+                write_value(v, e);
+            }
+        },
+        Json::Object(m) => {
+            write_len(v, m.len(), FIXMAP, 15, None, MAP16, MAP32);
+            for (k, e) in m.iter() {
+                write_str(v, k);
+                write_value(v, e);
+            }
+        },
+    }
+}
+
+// writes the format and length field of a string, array or map
+fn write_len(v: &mut Vec<u8>, n: usize, fix: u8, fix_max: usize,
+             f8: Option<u8>, f16: u8, f32: u8) {
+    match f8 {
+        _ if n <= fix_max => v.push(fix | n as u8),
+        Some(f8) if n <= u8::MAX as usize => {
+            v.push(f8);
+            v.push(n as u8);
+        },
+        _ if n <= u16::MAX as usize => {
+            v.push(f16);
+            v.extend_from_slice(&(n as u16).to_be_bytes());
+        },
+        _ => {
+            v.push(f32);
+            v.extend_from_slice(&(n as u32).to_be_bytes());
+        },
+    }
+}
+
+fn write_str(v: &mut Vec<u8>, s: &str) {
+    write_len(v, s.len(), FIXSTR, 31, Some(STR8), STR16, STR32);
+    v.extend_from_slice(s.as_bytes());
+}
+
+fn write_number(v: &mut Vec<u8>, n: f64) {
+    const LIMIT: f64 = 18446744073709551616.0; // 2^64
+
+    // NaN and infinity have no integral part
+    if n.fract() == 0.0 && !(n == 0.0 && n.is_sign_negative()) && (-LIMIT / 2.0 .. LIMIT).contains(&n) {
+        if n >= 0.0 {
+            write_uint(v, n as u64);
+        } else {
+            write_int(v, n as i64);
+        }
+    } else if n.is_nan() || (n as f32) as f64 == n {
+        v.push(FLOAT32);
+        v.extend_from_slice(&(n as f32).to_be_bytes());
+    } else {
+        v.push(FLOAT64);
+        v.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_uint(v: &mut Vec<u8>, n: u64) {
+    if n < 0x80 {
+        v.push(n as u8); // positive fixint
+    } else if n <= u8::MAX as u64 {
+        v.push(UINT8);
+        v.push(n as u8);
+    } else if n <= u16::MAX as u64 {
+        v.push(UINT16);
+        v.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        v.push(UINT32);
+        v.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        v.push(UINT64);
+        v.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_int(v: &mut Vec<u8>, n: i64) {
+    if n >= -32 {
+        v.push(n as u8); // negative fixint
+    } else if n >= i8::MIN as i64 {
+        v.push(INT8);
+        v.push(n as u8);
+    } else if n >= i16::MIN as i64 {
+        v.push(INT16);
+        v.extend_from_slice(&(n as i16).to_be_bytes());
+    } else if n >= i32::MIN as i64 {
+        v.push(INT32);
+        v.extend_from_slice(&(n as i32).to_be_bytes());
+    } else {
+        v.push(INT64);
+        v.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+/// Decodes one MessagePack object from stream 's' into a Json value.
+/// The stream is left after the object,
+/// so that `decode` can be called again for the next object.
+///
+/// Binary data, map keys that are not strings
+/// and, with the default options, extension types
+/// cannot be represented as Json;
+/// they yield errors starting with "unsupported".
+/// A map with the same key twice is an error, as in Json text.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::json;
+///    use jsosso::msgpack::decode;
+///    use pacosso::{Stream, Opts};
+///
+///    let mut input = Cursor::new(b"\x82\xa1a\x01\xa1b\x92\x02\x03\xcc\xc8".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    assert_eq!(decode(&mut s).unwrap(), json!({"a": 1, "b": [2, 3]}));
+///    assert_eq!(decode(&mut s).unwrap(), json!(200));
+///    assert!(decode(&mut s).unwrap_err().is_eof());
+/// ```
+pub fn decode<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    decode_with_opts(s, &MsgpackOpts::default())
+}
+
+/// Like `decode`, but with explicit decoder options 'o'.
+pub fn decode_with_opts<R: Read>(s: &mut Stream<R>, o: &MsgpackOpts) -> ParseResult<Json> {
+    let b = s.any_byte()?;
+    match b {
+        0x00 ..= 0x7f => Ok(Json::Number(b as f64)),
+        0x80 ..= 0x8f => map(s, (b & 0x0f) as u32, o),
+        0x90 ..= 0x9f => array(s, (b & 0x0f) as u32, o),
+        0xa0 ..= 0xbf => Ok(Json::String(string(s, (b & 0x1f) as u32)?)),
+        0xe0 ..= 0xff => Ok(Json::Number(b as i8 as f64)),
+        NIL => Ok(Json::Null),
+        FALSE => Ok(Json::Boolean(false)),
+        TRUE => Ok(Json::Boolean(true)),
+        BIN8 | BIN16 | BIN32 => s.fail("unsupported binary data", Json::Null),
+        EXT8 => {
+            let n = uint(s, 1)? as u32;
+            ext(s, n, o)
+        },
+        EXT16 => {
+            let n = uint(s, 2)? as u32;
+            ext(s, n, o)
+        },
+        EXT32 => {
+            let n = uint(s, 4)? as u32;
+            ext(s, n, o)
+        },
+        FIXEXT1 ..= FIXEXT16 => ext(s, 1 << (b - FIXEXT1), o),
+        FLOAT32 => Ok(Json::Number(f32::from_bits(uint(s, 4)? as u32) as f64)),
+        FLOAT64 => Ok(Json::Number(f64::from_bits(uint(s, 8)?))),
+        UINT8 => Ok(Json::Number(uint(s, 1)? as f64)),
+        UINT16 => Ok(Json::Number(uint(s, 2)? as f64)),
+        UINT32 => Ok(Json::Number(uint(s, 4)? as f64)),
+        UINT64 => Ok(Json::Number(uint(s, 8)? as f64)),
+        INT8 => Ok(Json::Number(uint(s, 1)? as i8 as f64)),
+        INT16 => Ok(Json::Number(uint(s, 2)? as i16 as f64)),
+        INT32 => Ok(Json::Number(uint(s, 4)? as i32 as f64)),
+        INT64 => Ok(Json::Number(uint(s, 8)? as i64 as f64)),
+        STR8 | STR16 | STR32 => {
+            let n = uint(s, 1 << (b - STR8))? as u32;
+            Ok(Json::String(string(s, n)?))
+        },
+        ARRAY16 | ARRAY32 => {
+            let n = uint(s, 2 << (b - ARRAY16))? as u32;
+            array(s, n, o)
+        },
+        MAP16 | MAP32 => {
+            let n = uint(s, 2 << (b - MAP16))? as u32;
+            map(s, n, o)
+        },
+        _ => s.fail(&format!("invalid format {}", b), Json::Null), // 0xc1
+    }
+}
+
+/// Decodes a complete MessagePack object from byte slice 'b'.
+/// Nothing may follow the object.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::msgpack;
+///
+///    let j = json!({"list": [1, 2.5, "three"]});
+///    assert_eq!(msgpack::from_slice(&msgpack::to_vec(&j)).unwrap(), j);
+/// ```
+pub fn from_slice(b: &[u8]) -> error::Result<Json> {
+    from_slice_with_opts(b, &MsgpackOpts::default())
+}
+
+/// Like `from_slice`, but with explicit decoder options 'o'.
+pub fn from_slice_with_opts(b: &[u8], o: &MsgpackOpts) -> error::Result<Json> {
+    Ok(parse_buffer_with(b, |s| decode_complete(s, o))?)
+}
+
+/// Decodes a complete MessagePack object from reader 'r'.
+/// Nothing may follow the object.
+pub fn from_reader<R: Read>(r: R) -> error::Result<Json> {
+    from_reader_with_opts(r, &MsgpackOpts::default())
+}
+
+/// Like `from_reader`, but with explicit decoder options 'o'.
+pub fn from_reader_with_opts<R: Read>(mut r: R, o: &MsgpackOpts) -> error::Result<Json> {
+    // the input is read completely, so that the stream buffers
+    // can be sized to the input (see parsing::EofGuard)
+    let mut v = Vec::new();
+    r.read_to_end(&mut v)?;
+    from_slice_with_opts(&v, o)
+}
+
+fn decode_complete<R: Read>(s: &mut Stream<R>, o: &MsgpackOpts) -> ParseResult<Json> {
+    let j = decode_with_opts(s, o)?;
+    end_of_data(s)?;
+    Ok(j)
+}
+
+fn string<R: Read>(s: &mut Stream<R>, n: u32) -> ParseResult<String> {
+    let mut v = Vec::new();
+    get_bytes(s, &mut v, n as u64)?;
+    match String::from_utf8(v) {
+        Ok(t) => Ok(t),
+        Err(_) => s.fail("utf8 error in string", String::new()),
+    }
+}
+
+fn array<R: Read>(s: &mut Stream<R>, n: u32, o: &MsgpackOpts) -> ParseResult<Json> {
+    let mut a = Vec::with_capacity(n.min(1024) as usize);
+    for _ in 0 .. n {
+        a.push(decode_with_opts(s, o)?);
+    }
+    Ok(Json::Array(a))
+}
+
+fn map<R: Read>(s: &mut Stream<R>, n: u32, o: &MsgpackOpts) -> ParseResult<Json> {
+    let mut m = HashMap::new();
+    for _ in 0 .. n {
+        let k = match decode_with_opts(s, o)? {
+            Json::String(k) => k,
+            _ => return s.fail("unsupported map key, string expected", Json::Null),
+        };
+        if m.contains_key(&k) {
+            return s.fail(&format!("duplicated key '{}' in map", k), Json::Null);
+        }
+        m.insert(k, decode_with_opts(s, o)?);
+    }
+    Ok(Json::Object(Box::new(m)))
+}
+
+fn ext<R: Read>(s: &mut Stream<R>, n: u32, o: &MsgpackOpts) -> ParseResult<Json> {
+    let t = s.any_byte()? as i8;
+    if o.ext == ExtPolicy::Reject {
+        return s.fail(&format!("unsupported extension type {}", t), Json::Null);
+    }
+    let mut data = Vec::new();
+    get_bytes(s, &mut data, n as u64)?;
+    let mut m = HashMap::new();
+    m.insert("type".to_string(), Json::from(t));
+    m.insert("data".to_string(), Json::String(base64_encode(&data)));
+    Ok(Json::Object(Box::new(m)))
+}
@@ -0,0 +1,186 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::fmt;
+use pacosso::ParseError;
+use super::*;
+use super::parsing::parse_buffer;
+use super::serializing::SerializeOpts;
+
+/// An error in a Json Lines stream together with
+/// the number of the line (starting at 1) in which it occurred.
+#[derive(Debug)]
+pub struct LineError {
+    /// The line in which the error occurred.
+    pub line: u64,
+    /// The error itself; I/O errors are reported as `ParseError::IOError`.
+    pub error: ParseError,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for LineError { }
+
+/// Reads Json values from a Json Lines (NDJSON) stream,
+/// one value per line. Blank lines are skipped.
+///
+/// The reader is an iterator yielding one result per non-blank line.
+/// A line that does not contain exactly one Json value
+/// yields an error, but does not stop the iteration;
+/// the next call continues with the following line.
+/// Only I/O errors end the iteration.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::Json;
+/// use jsosso::ndjson::Reader;
+///
+/// let input = "{\"id\": 1}\n\n[1, 2\n\"three\"\n";
+/// let vs: Vec<_> = Reader::new(input.as_bytes()).collect();
+///
+/// assert_eq!(vs.len(), 3);
+/// assert!(vs[0].is_ok());
+/// assert!(match &vs[1] {
+///     Err(e) => e.line == 3,
+///     Ok(v) => panic!("unexpected value: {:?}", v),
+/// });
+/// assert!(match &vs[2] {
+///     Ok(Json::String(s)) => s == "three",
+///     _ => false,
+/// });
+/// ```
+pub struct Reader<R: Read> {
+    input: BufReader<R>,
+    line: u64,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a Json Lines reader on top of `input`.
+    pub fn new(input: R) -> Reader<R> {
+        Reader {
+            input: BufReader::new(input),
+            line: 0,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the number of the line read last.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Consumes the reader and returns the underlying input.
+    /// Data buffered by the reader, but not yet parsed, is lost.
+    pub fn into_inner(self) -> R {
+        self.input.into_inner()
+    }
+
+    fn parse_line(&mut self) -> Result<Json, LineError> {
+        parse_buffer(&self.buf).map_err(|e| LineError {
+            line: self.line,
+            error: e,
+        })
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Json, LineError>;
+
+    fn next(&mut self) -> Option<Result<Json, LineError>> {
+        while !self.done {
+            self.buf.clear();
+            match self.input.read_until(b'\n', &mut self.buf) {
+                Ok(0) => {
+                    self.done = true;
+                },
+                Ok(_) => {
+                    self.line += 1;
+                    if self.buf.iter().all(|b| b.is_ascii_whitespace()) {
+                        continue;
+                    }
+                    return Some(self.parse_line());
+                },
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(LineError {
+                        line: self.line + 1,
+                        error: ParseError::IOError(e),
+                    }));
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Writes Json values as Json Lines (NDJSON),
+/// each value serialized compactly and terminated by a linebreak.
+///
+/// Example:
+///
+/// ```
+/// use std::str;
+/// use jsosso::json;
+/// use jsosso::ndjson::Writer;
+///
+/// let mut w = Writer::new(Vec::new());
+/// w.write(&json!([1, 2])).unwrap();
+/// w.write(&json!("hello\nworld")).unwrap();
+///
+/// let v = w.into_inner().unwrap();
+/// assert_eq!(str::from_utf8(&v).unwrap(), "[1,2]\n\"hello\\nworld\"\n");
+/// ```
+///
+/// Lines are not flushed one by one; call `flush` to pass
+/// the lines written so far on, e.g. to a socket.
+pub struct Writer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a Json Lines writer on top of `output`.
+    pub fn new(output: W) -> Writer<W> {
+        Writer {
+            output,
+        }
+    }
+
+    /// Writes one Json value followed by a linebreak
+    /// and returns the number of bytes written.
+    pub fn write(&mut self, j: &Json) -> io::Result<usize> {
+        // serialize_compact would flush the output
+        let mut v = j.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+        v.push(b'\n');
+        self.output.write_all(&v)?;
+        Ok(v.len())
+    }
+
+    /// Writes all values of `js` and returns the number of bytes written.
+    pub fn write_all<'a, I>(&mut self, js: I) -> io::Result<usize>
+    where
+        I: IntoIterator<Item = &'a Json>,
+    {
+        let mut n = 0;
+        for j in js {
+            n += self.write(j)?;
+        }
+        Ok(n)
+    }
+
+    /// Flushes the underlying output.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    /// Flushes the writer, consumes it and returns the underlying output.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
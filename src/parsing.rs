@@ -1,11 +1,18 @@
-use std::io::Read;
+use std::io::{self, Read, Cursor};
 use std::collections::HashMap;
-use std::str;
+use std::fs::File;
+use std::path::Path;
+use std::str::{self, FromStr};
 use super::*;
+use super::number;
+use super::error;
+use super::encoding;
+use pacosso::Opts;
 
 /// Parses the first complete Json value in stream 's'
 /// and returns it as Enum 'Json' on success and a parse error otherwise.
-/// For details on how to create and manage streams, please refer to [`pacosso`].
+/// For details on how to create and manage streams, please refer to [`pacosso`];
+/// the reader of the stream should be wrapped in an [`EofGuard`].
 ///
 /// [`pacosso`]: https://github.com/toschoo/pacosso
 ///
@@ -32,11 +39,412 @@ use super::*;
 ///    });
 /// ```
 pub fn parse<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
-    s.skip_whitespace()?;
-    jvalue(s)
+    parse_with_opts(s, &ParseOpts::default())
 }
 
-fn jvalue<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+/// Defines how the parser handles text that is not valid Unicode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Invalid text is an error.
+    Strict,
+    /// Invalid text is replaced by U+FFFD (the replacement character).
+    Lossy,
+    /// Unpaired surrogates, from `\u` escapes or encoded as in WTF-8,
+    /// are kept as the escape text `\udxxx` (in lower case);
+    /// any other invalid text is an error.
+    /// `Json::String` holds a Rust `String`, which is always valid UTF-8,
+    /// hence the escape text. Serialize with
+    /// `SerializeOpts::set_surrogate_escapes` to write it back unescaped.
+    /// Note that a string that contains the text `\ud800` itself
+    /// cannot be told apart from one with a preserved surrogate.
+    Preserve,
+}
+
+/// Options that control the parser.
+/// Invalid text is rejected, replaced or preserved (see `Utf8Policy`).
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::{Json};
+///    use jsosso::parsing::*;
+///    use pacosso::{Stream, Opts};
+///
+///    let mut input = Cursor::new(b"\"caf\xe9\"".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///    let o = ParseOpts::default().set_invalid_utf8(Utf8Policy::Lossy);
+///
+///    assert_eq!(parse_with_opts(&mut s, &o).unwrap(), Json::from("caf\u{fffd}"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOpts {
+    /// Policy for byte sequences in strings that are not valid UTF-8.
+    /// The default is `Strict`.
+    pub invalid_utf8: Utf8Policy,
+    /// Policy for `\u` escapes encoding unpaired UTF-16 surrogates.
+    /// The default is `Lossy`.
+    pub lone_surrogates: Utf8Policy,
+}
+
+impl Default for ParseOpts {
+    fn default() -> ParseOpts {
+        ParseOpts {
+            invalid_utf8: Utf8Policy::Strict,
+            lone_surrogates: Utf8Policy::Lossy,
+        }
+    }
+}
+
+impl ParseOpts {
+    /// Sets the policy for invalid UTF-8 in strings.
+    pub fn set_invalid_utf8(self, p: Utf8Policy) -> ParseOpts {
+        ParseOpts {
+            invalid_utf8: p,
+            ..self
+        }
+    }
+
+    /// Sets the policy for unpaired surrogates in `\u` escapes.
+    pub fn set_lone_surrogates(self, p: Utf8Policy) -> ParseOpts {
+        ParseOpts {
+            lone_surrogates: p,
+            ..self
+        }
+    }
+
+    /// Sets the same policy for invalid UTF-8 and for unpaired surrogates.
+    pub fn set_utf8_policy(self, p: Utf8Policy) -> ParseOpts {
+        ParseOpts {
+            invalid_utf8: p,
+            lone_surrogates: p,
+        }
+    }
+}
+
+/// Like `parse`, but with explicit parser options 'o'.
+/// A UTF-8 byte order mark at the very beginning of the stream is skipped.
+pub fn parse_with_opts<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
+    if s.position().stream == 0 {
+        skip_bom(s)?;
+    }
+    skip_whitespace(s)?;
+    jvalue(s, o)
+}
+
+fn skip_bom<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    match s.bytes(&[0xef, 0xbb, 0xbf]) {
+        Ok(()) => Ok(()),
+        Err(e) if e.is_expected_token() || e.is_eof() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a complete Json document from stream 's'.
+/// Unlike `parse`, this function fails if the Json value
+/// is followed by anything else than whitespace.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::{Json};
+///    use jsosso::parsing::*;
+///    use pacosso::{Stream, Opts};
+///
+///    let mut input = Cursor::new(b"[1]]".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    assert!(match parse_complete(&mut s) {
+///        Ok(v) => panic!("unexpected value: {:?}", v),
+///        Err(e) => e.is_error_type("trailing characters"),
+///    });
+/// ```
+pub fn parse_complete<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    parse_complete_with_opts(s, &ParseOpts::default())
+}
+
+/// Like `parse_complete`, but with explicit parser options 'o'.
+pub fn parse_complete_with_opts<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
+    let j = parse_with_opts(s, o)?;
+    end_of_input(s)?;
+    Ok(j)
+}
+
+// Succeeds if nothing but whitespace is left in the stream.
+pub(crate) fn end_of_input<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    skip_whitespace(s)?;
+    match s.peek_byte() {
+        Ok(c) => s.fail(&format!("trailing characters after Json value: {:?}", c as char), ()),
+        Err(e) if e.is_eof() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Succeeds if nothing is left in the stream;
+// used by the binary formats, where whitespace is data.
+pub(crate) fn end_of_data<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    match s.peek_byte() {
+        Ok(c) => s.fail(&format!("trailing bytes after value: 0x{:02x}", c), ()),
+        Err(e) if e.is_eof() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns an iterator over the Json values in stream 's'.
+/// The values may be separated by whitespace.
+/// The iterator ends at the end of the stream
+/// or after the first error, which it yields as its last item.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::{Json};
+///    use jsosso::parsing::*;
+///    use pacosso::{Stream, Opts};
+///
+///    let mut input = Cursor::new(b"{} [true] 1 \"one\" ".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    let vs: Vec<Json> = match parse_many(&mut s).collect() {
+///        Ok(vs) => vs,
+///        Err(e) => panic!("unexpected error: {:?}", e),
+///    };
+///    assert_eq!(vs.len(), 4);
+///    assert_eq!(vs[2], Json::Number(1.0));
+/// ```
+pub fn parse_many<'s, 'a, R: Read>(s: &'s mut Stream<'a, R>) -> Values<'s, 'a, R> {
+    parse_many_with_opts(s, ParseOpts::default())
+}
+
+/// Like `parse_many`, but with explicit parser options 'o'.
+pub fn parse_many_with_opts<'s, 'a, R: Read>(s: &'s mut Stream<'a, R>, o: ParseOpts) -> Values<'s, 'a, R> {
+    Values {
+        s,
+        o,
+        done: false,
+    }
+}
+
+/// Iterator over a sequence of Json values as returned by `parse_many`.
+pub struct Values<'s, 'a, R: Read> {
+    s: &'s mut Stream<'a, R>,
+    o: ParseOpts,
+    done: bool,
+}
+
+impl<'s, 'a, R: Read> Iterator for Values<'s, 'a, R> {
+    type Item = ParseResult<Json>;
+
+    fn next(&mut self) -> Option<ParseResult<Json>> {
+        if self.done {
+            return None;
+        }
+
+        let r = if self.s.position().stream == 0 {
+            skip_bom(self.s)
+        } else {
+            Ok(())
+        };
+        let r = r.and_then(|_| skip_whitespace(self.s))
+                 .and_then(|_| self.s.peek_byte());
+        match r {
+            Ok(_) => (),
+            Err(e) if e.is_eof() => {
+                self.done = true;
+                return None;
+            },
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            },
+        }
+
+        let r = jvalue(self.s, &self.o);
+        if r.is_err() {
+            self.done = true;
+        }
+        Some(r)
+    }
+}
+
+impl Json {
+    /// Parses a complete Json document from byte slice 'b'.
+    /// Only whitespace may follow the Json value.
+    /// UTF-16 and UTF-32 input is detected (see `encoding::detect`)
+    /// and transcoded; code units that do not encode a character are an error.
+    ///
+    /// Example:
+    ///
+    /// ```
+    ///    use jsosso::{Json, json};
+    ///
+    ///    assert_eq!(Json::from_slice(b"[1, 2, 3]").unwrap(), json!([1, 2, 3]));
+    ///    assert_eq!(Json::from_slice(b"\xff\xfe[\x001\x00]\x00").unwrap(), json!([1]));
+    ///    assert!(Json::from_slice(b"[1, 2, 3] 4").is_err());
+    /// ```
+    pub fn from_slice(b: &[u8]) -> error::Result<Json> {
+        if encoding::detect(b).0 == encoding::Encoding::Utf8 {
+            return Ok(parse_buffer(b)?);
+        }
+        let mut v = Vec::with_capacity(b.len());
+        encoding::Decoder::new(b, Utf8Policy::Strict).read_to_end(&mut v)?;
+        Ok(parse_buffer(&v)?)
+    }
+
+    /// Parses a complete Json document from reader 'r'.
+    /// Only whitespace may follow the Json value.
+    /// The reader is buffered by the parser;
+    /// it does not need to be wrapped in a `BufReader`.
+    /// Like `from_slice`, this transcodes UTF-16 and UTF-32 input.
+    ///
+    /// Example:
+    ///
+    /// ```
+    ///    use jsosso::{Json, json};
+    ///
+    ///    let input = "{\"id\": 1}".as_bytes();
+    ///    assert_eq!(Json::from_reader(input).unwrap(), json!({"id": 1}));
+    /// ```
+    pub fn from_reader<R: Read>(r: R) -> error::Result<Json> {
+        let mut input = EofGuard::new(encoding::Decoder::new(r, Utf8Policy::Strict));
+        let mut s = Stream::new(Opts::default(), &mut input);
+        Ok(parse_complete(&mut s)?)
+    }
+
+    /// Parses a complete Json document from the file at 'path'.
+    /// Only whitespace may follow the Json value.
+    /// Like `from_slice`, this transcodes UTF-16 and UTF-32 input.
+    ///
+    /// Example:
+    ///
+    /// ```
+    ///    use jsosso::Json;
+    ///
+    ///    assert!(match Json::from_file("rsc/test/pass1.json") {
+    ///        Ok(Json::Array(a)) => !a.is_empty(),
+    ///        Ok(v) => panic!("unexpected value: {:?}", v),
+    ///        Err(e) => panic!("unexpected error: {}", e),
+    ///    });
+    ///    assert!(match Json::from_file("rsc/test/does-not-exist.json") {
+    ///        Err(e) => e.is_io_error(),
+    ///        Ok(v) => panic!("unexpected value: {:?}", v),
+    ///    });
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> error::Result<Json> {
+        Json::from_reader(File::open(path)?)
+    }
+}
+
+/// Parses a complete Json document from a string.
+/// Only whitespace may follow the Json value.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::{Json, json};
+///
+///    let j: Json = "{\"list\": [1, 2, 3]}".parse().unwrap();
+///    assert_eq!(j, json!({"list": [1, 2, 3]}));
+/// ```
+impl FromStr for Json {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Json> {
+        Ok(parse_buffer(s.as_bytes())?)
+    }
+}
+
+// Parses a complete Json document held in memory
+// with a buffer size adapted to the size of the document.
+pub(crate) fn parse_buffer(buf: &[u8]) -> ParseResult<Json> {
+    parse_buffer_with(buf, parse_complete)
+}
+
+// Applies parser 'p' to a stream on top of 'buf'.
+// The buffer size is chosen such that the input does not end
+// one byte before a buffer boundary (see `EofGuard`).
+pub(crate) fn parse_buffer_with<'b, F>(buf: &'b [u8], p: F) -> ParseResult<Json>
+    where F: FnOnce(&mut Stream<Cursor<&'b [u8]>>) -> ParseResult<Json>
+{
+    let mut size = buf.len().clamp(8, 8192);
+    while buf.len() % size == size - 1 {
+        size += 1;
+    }
+    let mut input = Cursor::new(buf);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(size)
+               .set_buf_num(3),
+               &mut input);
+    p(&mut s)
+}
+
+/// A reader for Json text that guards against a bug in pacosso 0.2:
+/// when the input ends one byte before the end of a stream buffer,
+/// the stream yields a stale byte instead of reporting the end of the input.
+/// In that case, this reader appends a single space to the input,
+/// so that the last buffer is full.
+/// Trailing whitespace does not change the meaning of Json text;
+/// binary formats, where it would, should be decoded from a slice instead.
+/// Only text that ends within `null`, `true` or `false` is affected:
+/// it is reported as an unexpected byte instead of the end of the input.
+///
+/// The convenience entry points (`Json::from_reader`, `Json::from_slice`, ...)
+/// take care of this themselves; streams created by the caller
+/// should wrap their reader.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::{Json, json};
+///    use jsosso::parsing::*;
+///    use pacosso::{Stream, Opts};
+///
+///    // 15 bytes with buffers of 8 bytes
+///    let mut input = EofGuard::new(Cursor::new(b"[1, 2, 3, 4] [5".to_vec()));
+///    let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+///    let mut vs = parse_many(&mut s);
+///
+///    assert_eq!(vs.next().unwrap().unwrap(), json!([1, 2, 3, 4]));
+///    assert!(vs.next().unwrap().unwrap_err().is_eof());
+/// ```
+pub struct EofGuard<R: Read> {
+    inner: R,
+    padded: bool,
+}
+
+impl<R: Read> EofGuard<R> {
+    /// Wraps reader 'inner'.
+    pub fn new(inner: R) -> EofGuard<R> {
+        EofGuard {
+            inner,
+            padded: false,
+        }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for EofGuard<R> {
+    // pacosso asks for exactly one byte only
+    // when a single byte is missing to fill its buffer
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && buf.len() == 1 && !self.padded {
+            self.padded = true;
+            buf[0] = b' ';
+            return Ok(1);
+        }
+        Ok(n)
+    }
+}
+
+fn jvalue<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
     /* This looks nice, but it is less efficient and would force us
        to use a buffer that basically is big enough to contain the whole jvalue.
 
@@ -46,9 +454,9 @@ fn jvalue<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
 
     let ch = s.peek_byte()?;
     match ch {
-      b'"' => jstring(s),
-      b'{' => jobject(s),
-      b'[' => jarray(s),
+      b'"' => jstring(s, o),
+      b'{' => jobject(s, o),
+      b'[' => jarray(s, o),
       b'n' => jnil(s),
       b't' => jboolean(s),
       b'f' => jboolean(s),
@@ -62,7 +470,7 @@ fn fail<R: Read>(s: &mut Stream<R>, msg: String) -> ParseResult<Json> {
 }
 
 fn jnil<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
-    s.string("null")?;
+    literal(s, b"null")?;
     Ok(Json::Null)
 }
 
@@ -71,124 +479,248 @@ fn jboolean<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
     let c = s.any_byte()?;
 
     if c == b'f' {
-       s.string("alse")?;
+       literal(s, b"alse")?;
        return Ok(Json::Boolean(false));
     } else if c == b't' {
-       s.string("rue")?;
+       literal(s, b"rue")?;
        return Ok(Json::Boolean(true));
     }
     fail(s, "boolean value expected".to_string())
 }
 
-// jnumber is extremely inefficient. Definitely needs review.
+// Consumes the bytes of a literal one by one.
+// pacosso's 'string' (0.2.x) does not report eof when the literal
+// would reach the end of a buffer at the end of the input;
+// it compares against the unused bytes of the buffer instead,
+// e.g. "[\n  tr" with 8-byte buffers fails with "have: r\0\0".
+fn literal<R: Read>(s: &mut Stream<R>, l: &[u8]) -> ParseResult<()> {
+    for b in l {
+        s.byte(*b)?;
+    }
+    Ok(())
+}
+
+// Numbers are converted while they are scanned:
+// the first 19 significant digits are accumulated in a u64,
+// further digits only count for the exponent.
+// The text of the number is kept in a small buffer on the stack
+// for the rare cases where the fast conversion cannot decide.
 fn jnumber<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
-    let mut zero = false;
-    let mut v = Vec::new();
+    let mut n = Number::default();
 
     // sign: eof is an error
     let c = s.peek_byte()?;
     if c == b'-' {
         s.byte(b'-')?;
-        v.push(b'-');
+        n.negative = true;
+        n.text.push(b'-');
     }
 
     // leading 0: eof is an error here
     let c = s.peek_byte()?;
     if c == b'0' {
-        zero = true;
         s.byte(b'0')?;
-        v.push(b'0');
-    }
-
-    // digits without leading 0: eof is an error here
-    if !zero {
-        let ds = s.digits()?;
-        v.extend_from_slice(&ds);
+        n.text.push(b'0');
+    } else {
+        // digits without leading 0: eof is an error here
+        let d = s.digit()?;
+        n.digit(d, false);
+        while let Some(d) = next_digit(s)? {
+            n.digit(d, false);
+        }
     }
 
     // decimal point: eof is not an error
-    let c = match s.peek_byte() {
-        Ok(c) => c,
-        Err(e) if e.is_eof() => b' ',
-        Err(e) => return Err(e),
-    };
-    if c == b'.' {
-        jfrac(s, &mut v)?;
+    if peek_or_eof(s)? == Some(b'.') {
+        jfrac(s, &mut n)?;
     }
 
     // exponent: eof is not an error
-    let c = match s.peek_byte() {
-        Ok(c) => c,
-        Err(e) if e.is_eof() => b' ',
-        Err(e) => return Err(e),
-    };
-
-    if c == b'e' {
-        s.byte(b'e')?;
-        jexp(s, &mut v)?;
-    } else if c == b'E' {
-        s.byte(b'E')?;
-        jexp(s, &mut v)?;
+    match peek_or_eof(s)? {
+        Some(b'e') | Some(b'E') => jexp(s, &mut n)?,
+        _ => (),
     }
 
-    // well, that's really lazy
-    let x = match str::from_utf8(&v) {
-        Ok(x) => x,
-        Err(e) => return fail(s, format!("internal error: {:?}", e)),
+    let f = match n.to_f64() {
+        Some(f) => f,
+        None => n.slow_to_f64(s)?,
     };
 
-    match x.parse::<f64>() {
-        Ok(f) => return Ok(Json::Number(f)),
-        Err(e) => return fail(s, format!("internal error: {:?}", e)),
+    if n.negative {
+        Ok(Json::Number(-f))
+    } else {
+        Ok(Json::Number(f))
     }
 }
 
-fn jfrac<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>) -> ParseResult<()> {
+fn jfrac<R: Read>(s: &mut Stream<R>, n: &mut Number) -> ParseResult<()> {
      s.byte(b'.')?;
-     v.push(b'.');
-     let ds = s.digits()?; 
-     v.extend_from_slice(&ds);
+     n.text.push(b'.');
+     let d = s.digit()?;
+     n.digit(d, true);
+     while let Some(d) = next_digit(s)? {
+         n.digit(d, true);
+     }
      Ok(())
 }
 
-fn jexp<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>) -> ParseResult<()> {
-     v.push(b'e');
+fn jexp<R: Read>(s: &mut Stream<R>, n: &mut Number) -> ParseResult<()> {
+     s.any_byte()?;
+     n.text.push(b'e');
      let c = s.peek_byte()?;
+     let negative = c == b'-';
      if c == b'-' {
          s.byte(b'-')?;
-         v.push(b'-');
+         n.text.push(b'-');
      } else if c == b'+' {
          s.byte(b'+')?;
      }
-     let ds = s.digits()?; 
-     v.extend_from_slice(&ds);
+     let mut x: i64 = 0;
+     let mut d = s.digit()?;
+     loop {
+         n.text.push(d);
+         // beyond that, the result is 0 or infinity anyway
+         if x < 0x10000 {
+             x = x * 10 + (d - b'0') as i64;
+         }
+         d = match next_digit(s)? {
+             Some(d) => d,
+             None => break,
+         };
+     }
+     if negative {
+         n.exponent -= x;
+     } else {
+         n.exponent += x;
+     }
      Ok(())
 }
 
-fn jarray<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+// Consumes and returns the next byte if it is a digit.
+fn next_digit<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<u8>> {
+    match peek_or_eof(s)? {
+        Some(c) if c.is_ascii_digit() => {
+            s.any_byte()?;
+            Ok(Some(c))
+        },
+        _ => Ok(None),
+    }
+}
+
+// Peeks the next byte; eof is not an error.
+fn peek_or_eof<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<u8>> {
+    match s.peek_byte() {
+        Ok(c) => Ok(Some(c)),
+        Err(e) if e.is_eof() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// A number in the making.
+#[derive(Default)]
+struct Number {
+    negative: bool,
+    significand: u64,
+    digits: usize,
+    exponent: i64,
+    truncated: bool,
+    text: NumberText,
+}
+
+impl Number {
+    fn digit(&mut self, d: u8, fraction: bool) {
+        self.text.push(d);
+        if self.digits == 0 && d == b'0' {
+            // leading zeros in the fraction
+            self.exponent -= 1;
+        } else if self.digits < number::MAX_DIGITS {
+            self.significand = self.significand * 10 + (d - b'0') as u64;
+            self.digits += 1;
+            if fraction {
+                self.exponent -= 1;
+            }
+        } else {
+            self.truncated |= d != b'0';
+            if !fraction {
+                self.exponent += 1;
+            }
+        }
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        if self.truncated {
+            number::truncated_to_f64(self.significand, self.exponent)
+        } else {
+            number::to_f64(self.significand, self.exponent)
+        }
+    }
+
+    fn slow_to_f64<R: Read>(&self, s: &mut Stream<R>) -> ParseResult<f64> {
+        let t = match str::from_utf8(self.text.as_bytes()) {
+            Ok(t) => t,
+            Err(e) => return s.fail(&format!("internal error: {:?}", e), 0.0),
+        };
+        match t.trim_start_matches('-').parse::<f64>() {
+            Ok(f) => Ok(f),
+            Err(e) => s.fail(&format!("internal error: {:?}", e), 0.0),
+        }
+    }
+}
+
+// The text of a number; only very long numbers go to the heap.
+#[derive(Default)]
+struct NumberText {
+    buf: [u8; 32],
+    len: usize,
+    more: Vec<u8>,
+}
+
+impl NumberText {
+    fn push(&mut self, b: u8) {
+        if self.len < self.buf.len() {
+            self.buf[self.len] = b;
+            self.len += 1;
+        } else {
+            if self.more.is_empty() {
+                self.more.extend_from_slice(&self.buf);
+            }
+            self.more.push(b);
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        if self.more.is_empty() {
+            &self.buf[.. self.len]
+        } else {
+            &self.more
+        }
+    }
+}
+
+fn jarray<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
 
     s.byte(b'[')?;
-    s.skip_whitespace()?;
+    skip_whitespace(s)?;
 
     let mut v = Vec::new();
 
     // empty array
-    match s.byte(b']') {
-        Ok(()) =>  return Ok(Json::Array(v)),
-        Err(e) =>  if !e.is_expected_token() {
-                       return Err(e);
-        },
+    if s.peek_byte()? == b']' {
+        s.any_byte()?;
+        return Ok(Json::Array(v));
     }
 
     loop {
-        s.skip_whitespace()?;
-        let e = jvalue(s)?;
+        skip_whitespace(s)?;
+        let e = jvalue(s, o)?;
         v.push(e);   
-        s.skip_whitespace()?;
-        match s.byte(b',') {
-          Ok(()) => continue,
-          _ => break,
+        skip_whitespace(s)?;
+        // peeking is cheaper than a failing 'byte'
+        if peek_or_eof(s)? == Some(b',') {
+            s.any_byte()?;
+            continue;
         }
+        break;
     }
 
     s.byte(b']')?;
@@ -196,21 +728,21 @@ fn jarray<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
     Ok(Json::Array(v))
 }
 
-fn jobject<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+fn jobject<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
 
     s.byte(b'{')?;
 
-    let m = keyvalues(s)?;
+    let m = keyvalues(s, o)?;
 
     s.byte(b'}')?;
 
     Ok(Json::Object(Box::new(m)))
 }
 
-fn keyvalues<R: Read>(s: &mut Stream<R>) -> ParseResult<HashMap<String, Json>> {
+fn keyvalues<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<HashMap<String, Json>> {
     let mut m: HashMap<String, Json> = HashMap::new();
 
-    s.skip_whitespace()?;
+    skip_whitespace(s)?;
 
     // may be empty
     let c = s.peek_byte()?;
@@ -219,7 +751,7 @@ fn keyvalues<R: Read>(s: &mut Stream<R>) -> ParseResult<HashMap<String, Json>> {
     }
     
     loop {
-        let (k, v) = keyvalue(s)?;
+        let (k, v) = keyvalue(s, o)?;
         let _ = match m.insert(k.clone(), v) {
             Some(_) => return Err(ParseError::Failed(format!(
                           "duplicated key '{}' in object", k.clone()),
@@ -227,39 +759,45 @@ fn keyvalues<R: Read>(s: &mut Stream<R>) -> ParseResult<HashMap<String, Json>> {
                        ),
             _ => true,
         };
-        s.skip_whitespace()?;
-        match s.byte(b',') {
-            Ok(()) => continue,
-            Err(e) if e.is_expected_token() => return Ok(m),
-            Err(e) => return Err(e),
+        skip_whitespace(s)?;
+        if peek_or_eof(s)? == Some(b',') {
+            s.any_byte()?;
+            continue;
         }
+        return Ok(m);
     }
 }
 
-fn keyvalue<R: Read>(s: &mut Stream<R>) -> ParseResult<(String, Json)> {
-    s.skip_whitespace()?;
-    let k = plain_string(s)?;
-    s.skip_whitespace()?;
+fn keyvalue<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<(String, Json)> {
+    skip_whitespace(s)?;
+    let k = plain_string(s, o)?;
+    skip_whitespace(s)?;
     s.byte(b':')?;
-    s.skip_whitespace()?;
-    let v = jvalue(s)?;
+    skip_whitespace(s)?;
+    let v = jvalue(s, o)?;
 
     Ok((k, v))
 }
 
-fn jstring<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
-    let x = plain_string(s)?;
+fn jstring<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<Json> {
+    let x = plain_string(s, o)?;
     Ok(Json::String(x))
 }
 
-fn plain_string<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+// Strings are scanned byte by byte; UTF-8 is validated once for the whole string.
+// Scanning in bulk (e.g. eight bytes at a time for quotes and backslashes)
+// needs access to the buffered bytes as a slice, which the released
+// pacosso (0.2.x) does not offer: peeking a chunk and then consuming it
+// advances the cursor twice per byte and allocates the chunk.
+// Revisit once pacosso lends out its buffers.
+fn plain_string<R: Read>(s: &mut Stream<R>, o: &ParseOpts) -> ParseResult<String> {
 
-    s.byte('"' as u8)?;
+    s.byte(b'"')?;
     let mut v: Vec<u8> = Vec::new();
     loop {
        let c = s.any_byte()?;
        if c == b'\\' {
-           escape(s, &mut v)?;
+           escape(s, &mut v, o)?;
            continue;
        }
        if c == b'"' {
@@ -268,31 +806,62 @@ fn plain_string<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
        v.push(c);
     }
 
-    match str::from_utf8(&v) {
-      Ok(x) => return Ok(x.to_string()),
-      Err(_) => return Err(ParseError::Failed("unicode error".to_string(), s.position())),
+    match String::from_utf8(v) {
+      Ok(x) => Ok(x),
+      Err(e) if o.invalid_utf8 == Utf8Policy::Lossy => {
+          Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+      },
+      Err(e) if o.invalid_utf8 == Utf8Policy::Preserve => {
+          escape_wtf8(e.as_bytes()).map_err(|i| ParseError::Failed(format!(
+                    "utf8 error: invalid byte sequence in string at offset {}", i),
+                    s.position())
+                )
+      },
+      Err(e) => Err(ParseError::Failed(format!(
+                    "utf8 error: invalid byte sequence in string at offset {}",
+                    e.utf8_error().valid_up_to()),
+                    s.position())
+                ),
+    }
+}
+
+// Skips Json whitespace, counting line breaks.
+// Unlike pacosso's skip_whitespace, this does not fail internally
+// when there is no whitespace, which is the common case.
+fn skip_whitespace<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    loop {
+        match peek_or_eof(s)? {
+            Some(b' ') | Some(b'\t') | Some(b'\r') => {
+                s.any_byte()?;
+            },
+            Some(b'\n') => {
+                s.any_byte()?;
+                s.count_lines();
+            },
+            _ => return Ok(()),
+        }
     }
 }
 
 fn convert_ascii<R: Read>(s: &mut Stream<R>, n: u8) -> ParseResult<u16> {
     match n {
-        b'0' => return Ok(0),
-        b'1' => return Ok(1),
-        b'2' => return Ok(2),
-        b'3' => return Ok(3),
-        b'4' => return Ok(4),
-        b'5' => return Ok(5),
-        b'6' => return Ok(6),
-        b'7' => return Ok(7),
-        b'8' => return Ok(8),
-        b'9' => return Ok(9),
-        b'a' => return Ok(10),
-        b'b' => return Ok(11),
-        b'c' => return Ok(12),
-        b'd' => return Ok(13),
-        b'e' => return Ok(14),
-        b'f' => return Ok(15),
-        _ => return Err(ParseError::Failed(format!("hexadecimal expected, have: {}", n), s.position())),
+        b'0' => Ok(0),
+        b'1' => Ok(1),
+        b'2' => Ok(2),
+        b'3' => Ok(3),
+        b'4' => Ok(4),
+        b'5' => Ok(5),
+        b'6' => Ok(6),
+        b'7' => Ok(7),
+        b'8' => Ok(8),
+        b'9' => Ok(9),
+        b'a' => Ok(10),
+        b'b' => Ok(11),
+        b'c' => Ok(12),
+        b'd' => Ok(13),
+        b'e' => Ok(14),
+        b'f' => Ok(15),
+        _ => Err(ParseError::Failed(format!("hexadecimal expected, have: {}", n), s.position())),
     }
 }
 
@@ -304,11 +873,9 @@ fn utf16bytes<R: Read>(s: &mut Stream<R>) -> ParseResult<u16> {
     for b in bs {
         let i = convert_ascii(s, b.to_ascii_lowercase())?;
         u += i * h.pow(x);
-        if x > 0 {
-            x -= 1;
-        }
+        x = x.saturating_sub(1);
     }
-    Ok(u as u16)
+    Ok(u)
 }
 
 fn push_replacement(v: &mut Vec<u8>) {
@@ -319,53 +886,101 @@ fn push_replacement(v: &mut Vec<u8>) {
      }
 }
 
-fn codepoint<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>) -> ParseResult<()> {
-    let mut w = Vec::with_capacity(2);
-
-    for i in 0 .. 2 {
-        let a = utf16bytes(s)?;
-        w.push(a);
-        let cs: Vec<Result<char, u16>> = char::decode_utf16(w.clone())
-                .map(|r| r.map_err(|e| e.unpaired_surrogate()))
-                .collect();
-        match cs[..] {
-            [Ok(c)] => {
-               let mut buf = [0; 4];
-               let xs = c.encode_utf8(&mut buf);
-               for x in xs.bytes() {
-                  v.push(x);
-               }
-               return Ok(());
+fn is_high_surrogate(u: u16) -> bool {
+    (0xd800 .. 0xdc00).contains(&u)
+}
+
+fn is_low_surrogate(u: u16) -> bool {
+    (0xdc00 .. 0xe000).contains(&u)
+}
+
+pub(crate) fn push_char(v: &mut Vec<u8>, c: char) {
+     let mut buf = [0; 4];
+     v.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+// Replaces surrogates encoded as in WTF-8 (0xed 0xa0..0xbf 0x80..0xbf)
+// by their escape text; fails with the offset of any other invalid sequence.
+fn escape_wtf8(v: &[u8]) -> Result<String, usize> {
+    let mut x = String::with_capacity(v.len());
+    let mut i = 0;
+    loop {
+        match std::str::from_utf8(&v[i ..]) {
+            Ok(t) => {
+                x.push_str(t);
+                return Ok(x);
             },
-            [Err(_)] if i == 0 => {
-                match s.bytes(&vec![b'\\', b'u']) {
-                    Ok(()) => {
-                        continue;
-                    },
-                    Err(e) => {
-                         if e.is_expected_token() || e.is_eof() {
-                             push_replacement(v);
-                             return Ok(());
-                         } else {
-                             return Err(e);
-                         }
+            Err(e) => {
+                let j = i + e.valid_up_to();
+                // cannot fail: the bytes are valid up to j
+                x.push_str(std::str::from_utf8(&v[i .. j]).unwrap_or_default());
+                match v.get(j .. j + 3) {
+                    Some(&[0xed, b, c]) if (0xa0 .. 0xc0).contains(&b) && (0x80 .. 0xc0).contains(&c) => {
+                        let u = 0xd000 | (b as u16 & 0x3f) << 6 | (c as u16 & 0x3f);
+                        x.push_str(&format!("\\u{:04x}", u));
+                        i = j + 3;
                     },
+                    _ => return Err(j),
                 }
             },
-            [Err(_)] => {
-                push_replacement(v);
-                return Ok(());
-            },
-            _ => return Err(ParseError::Failed("unexpected result".to_string(),
-                                               s.position())
-                 ),
         }
     }
+}
 
-    Ok(())
+fn lone_surrogate<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>, o: &ParseOpts, u: u16) -> ParseResult<()> {
+    match o.lone_surrogates {
+        Utf8Policy::Lossy => {
+            push_replacement(v);
+            Ok(())
+        },
+        Utf8Policy::Preserve => {
+            v.extend_from_slice(format!("\\u{:04x}", u).as_bytes());
+            Ok(())
+        },
+        Utf8Policy::Strict => Err(ParseError::Failed(format!(
+                                  "unicode error: unpaired surrogate \\u{:04x}", u),
+                                  s.position())
+                              ),
+    }
+}
+
+fn codepoint<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>, o: &ParseOpts) -> ParseResult<()> {
+    let mut a = utf16bytes(s)?;
+
+    loop {
+        if is_low_surrogate(a) {
+            return lone_surrogate(s, v, o, a);
+        }
+
+        if !is_high_surrogate(a) {
+            // cannot fail: a is not a surrogate
+            push_char(v, char::from_u32(a as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+            return Ok(());
+        }
+
+        // a high surrogate must be followed by a low surrogate
+        match s.bytes(b"\\u") {
+            Ok(()) => (),
+            Err(e) if e.is_expected_token() || e.is_eof() => {
+                return lone_surrogate(s, v, o, a);
+            },
+            Err(e) => return Err(e),
+        }
+
+        let b = utf16bytes(s)?;
+        if is_low_surrogate(b) {
+            let c = 0x10000 + (((a - 0xd800) as u32) << 10) + (b - 0xdc00) as u32;
+            push_char(v, char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER));
+            return Ok(());
+        }
+
+        // a is unpaired, b is a codepoint in its own right
+        lone_surrogate(s, v, o, a)?;
+        a = b;
+    }
 }
 
-fn escape<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>) -> ParseResult<()> {
+fn escape<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>, o: &ParseOpts) -> ParseResult<()> {
        let c = s.any_byte()?;
        match c {
           b'\\' => v.push(b'\\'),
@@ -376,7 +991,7 @@ fn escape<R: Read>(s: &mut Stream<R>, v: &mut Vec<u8>) -> ParseResult<()> {
           b'n'  => v.push(b'\n'),
           b'r'  => v.push(b'\r'),
           b't'  => v.push(b'\t'),
-          b'u'  => codepoint(s, v)?,
+          b'u'  => codepoint(s, v, o)?,
           _     => return Err(ParseError::Failed(format!(
                        "unknown escape sequence {}", c),
                         s.position()
@@ -0,0 +1,164 @@
+use super::*;
+use super::arbitrary::{GeneratorConfig, make_n_seeded_with_config, shrink};
+#[cfg(feature = "proptest")]
+use super::arbitrary::Shrink;
+
+/// The maximum number of elements of generated values
+/// unless configured otherwise.
+pub const DEFAULT_ELEMENTS: usize = 50;
+
+/// A proptest strategy for Json values (requires feature `proptest`).
+///
+/// The values are made by the generator of the `arbitrary` module
+/// from a seed drawn from the test runner.
+/// Failing values are shrunk with `arbitrary::shrink`:
+/// elements are dropped, strings shortened and numbers simplified.
+/// `any::<Json>()` is `JsonStrategy::default()`,
+/// which uses `GeneratorConfig::stress()`.
+///
+/// Example:
+///
+/// ```
+/// use proptest::prelude::*;
+/// use jsosso::Json;
+///
+/// proptest!(|(j in any::<Json>())| {
+///     let v = j.serialize_to_vec(&Default::default());
+///     prop_assert_eq!(Json::from_slice(&v).unwrap(), j);
+/// });
+/// ```
+#[cfg(feature = "proptest")]
+#[derive(Debug, Clone, Copy)]
+pub struct JsonStrategy {
+    config: GeneratorConfig,
+    elements: usize,
+}
+
+#[cfg(feature = "proptest")]
+impl Default for JsonStrategy {
+    fn default() -> JsonStrategy {
+        JsonStrategy::new(GeneratorConfig::stress(), DEFAULT_ELEMENTS)
+    }
+}
+
+#[cfg(feature = "proptest")]
+impl JsonStrategy {
+    /// Creates a strategy for values of at most 'n' elements
+    /// made according to generator configuration 'c'.
+    pub fn new(c: GeneratorConfig, n: usize) -> JsonStrategy {
+        JsonStrategy {
+            config: c,
+            elements: n,
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+impl proptest::strategy::Strategy for JsonStrategy {
+    type Tree = JsonValueTree;
+    type Value = Json;
+
+    fn new_tree(&self, runner: &mut proptest::test_runner::TestRunner) -> proptest::strategy::NewTree<Self> {
+        use proptest::prelude::Rng;
+
+        let seed = runner.rng().next_u64();
+        Ok(JsonValueTree::new(make_n_seeded_with_config(self.elements, seed, &self.config)))
+    }
+}
+
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for Json {
+    type Parameters = ();
+    type Strategy = JsonStrategy;
+
+    fn arbitrary_with(_: ()) -> JsonStrategy {
+        JsonStrategy::default()
+    }
+}
+
+/// The value tree of `JsonStrategy` (requires feature `proptest`).
+/// It shrinks greedily: the first simpler variant
+/// that still fails replaces the current value.
+#[cfg(feature = "proptest")]
+#[derive(Debug, Clone)]
+pub struct JsonValueTree {
+    current: Json,
+    variants: Shrink,
+    // the value before the last simplification
+    // and its remaining variants
+    previous: Option<(Json, Shrink)>,
+}
+
+#[cfg(feature = "proptest")]
+impl JsonValueTree {
+    fn new(j: Json) -> JsonValueTree {
+        JsonValueTree {
+            variants: shrink(&j),
+            current: j,
+            previous: None,
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+impl proptest::strategy::ValueTree for JsonValueTree {
+    type Value = Json;
+
+    fn current(&self) -> Json {
+        self.current.clone()
+    }
+
+    fn simplify(&mut self) -> bool {
+        let j = match self.variants.next() {
+            Some(j) => j,
+            None => return false,
+        };
+        let variants = std::mem::replace(&mut self.variants, shrink(&j));
+        let current = std::mem::replace(&mut self.current, j);
+        self.previous = Some((current, variants));
+        true
+    }
+
+    fn complicate(&mut self) -> bool {
+        match self.previous.take() {
+            Some((j, variants)) => {
+                self.current = j;
+                self.variants = variants;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// Generates Json values for quickcheck (requires feature `quickcheck`).
+///
+/// The values are made by the generator of the `arbitrary` module
+/// with `GeneratorConfig::stress()` from a seed drawn from quickcheck's `Gen`;
+/// the size of the `Gen` is the maximum number of elements.
+/// Failing values are shrunk with `arbitrary::shrink`.
+///
+/// Example:
+///
+/// ```
+/// use quickcheck::{QuickCheck, TestResult};
+/// use jsosso::Json;
+///
+/// fn round_trip(j: Json) -> TestResult {
+///     let v = j.serialize_to_vec(&Default::default());
+///     TestResult::from_bool(Json::from_slice(&v).ok() == Some(j))
+/// }
+///
+/// QuickCheck::new().tests(20).quickcheck(round_trip as fn(Json) -> TestResult);
+/// ```
+#[cfg(feature = "quickcheck")]
+impl quickcheck::Arbitrary for Json {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Json {
+        let seed = u64::arbitrary(g);
+        make_n_seeded_with_config(g.size(), seed, &GeneratorConfig::stress())
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Json>> {
+        Box::new(shrink(self))
+    }
+}
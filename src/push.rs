@@ -0,0 +1,258 @@
+use super::*;
+use super::error;
+use super::parsing::{ParseOpts, parse_buffer_with, parse_complete_with_opts};
+
+/// A push parser for input that arrives in chunks,
+/// e.g. from a non-blocking socket.
+///
+/// Bytes are passed to `feed` whenever they arrive.
+/// The parser keeps the bytes of an incomplete value
+/// across calls and returns the value as soon as it is complete.
+/// The value itself is parsed with the ordinary parser
+/// once its end was found.
+///
+/// `feed` stops after the first complete value
+/// and reports the number of bytes it consumed;
+/// the caller passes the remaining bytes in the next call.
+/// Numbers, booleans and null at the top level
+/// end with the first byte that cannot be part of them;
+/// at the end of the input, `finish` completes them.
+///
+/// Each value is buffered completely before it is parsed,
+/// i.e. memory grows with the size of the value
+/// and every byte is looked at twice: once to find the end of the value
+/// and once to parse it. To bound the memory, set a maximum size
+/// with `set_max_size`; a larger value is reported as `Error::TooLarge`
+/// and the rest of it is skipped without buffering.
+///
+/// Example:
+///
+/// ```
+/// use jsosso::json;
+/// use jsosso::push::PushParser;
+///
+/// let mut p = PushParser::new();
+///
+/// // an incomplete value is kept
+/// assert!(matches!(p.feed(b"{\"id\": [1, "), (11, None)));
+///
+/// // the rest completes it
+/// let (n, r) = p.feed(b"2]} 42");
+/// assert_eq!(n, 3);
+/// assert_eq!(r.unwrap().unwrap(), json!({"id": [1, 2]}));
+///
+/// // the scalar may continue in the next chunk
+/// assert!(matches!(p.feed(b" 42"), (3, None)));
+/// assert_eq!(p.finish().unwrap(), Some(json!(42)));
+/// ```
+#[derive(Default)]
+pub struct PushParser {
+    opts: ParseOpts,
+    delim: Delimiter,
+    buf: Vec<u8>,
+    max_size: Option<usize>,
+    skipping: bool,
+}
+
+impl PushParser {
+    /// Creates a push parser with default parser options.
+    pub fn new() -> PushParser {
+        PushParser::default()
+    }
+
+    /// Creates a push parser with parser options 'o'.
+    pub fn with_opts(o: ParseOpts) -> PushParser {
+        PushParser {
+            opts: o,
+            ..PushParser::default()
+        }
+    }
+
+    /// Sets the maximum size of a value in bytes.
+    /// By default, the size is not limited.
+    pub fn set_max_size(self, n: usize) -> PushParser {
+        PushParser {
+            max_size: Some(n),
+            ..self
+        }
+    }
+
+    /// Consumes bytes from 'bs' until a Json value is complete
+    /// or all bytes are consumed. Returns the number of bytes consumed
+    /// and, if a value was completed, the result of parsing it.
+    /// A value that exceeds the maximum size is reported
+    /// as soon as the limit is reached.
+    /// After an error, the parser starts over with the next value.
+    pub fn feed(&mut self, bs: &[u8]) -> (usize, Option<error::Result<Json>>) {
+        let mut n = 0;
+        while n < bs.len() {
+            let b = bs[n];
+            let (take, stop) = match self.delim.step(b) {
+                Step::Skip => (false, false),
+                Step::Take => (true, false),
+                Step::Stop => (false, true),
+                Step::TakeStop => (true, true),
+            };
+            if take || !stop {
+                n += 1;
+            }
+            if take && !self.skipping {
+                self.buf.push(b);
+                if let Some(m) = self.max_size.filter(|m| self.buf.len() > *m) {
+                    self.buf.clear();
+                    self.skipping = true;
+                    return (n, Some(Err(error::Error::TooLarge(m))));
+                }
+            }
+            if stop {
+                if self.skipping {
+                    // the rest of a value that was too large
+                    self.reset();
+                    continue;
+                }
+                break;
+            }
+        }
+
+        if self.delim.is_done() {
+            (n, Some(self.complete()))
+        } else {
+            (n, None)
+        }
+    }
+
+    /// Signals the end of the input. Returns the pending value, if any.
+    /// A pending value that is incomplete yields an EOF error.
+    pub fn finish(&mut self) -> error::Result<Option<Json>> {
+        if self.skipping {
+            // the error was reported by feed
+            self.reset();
+            return Ok(None);
+        }
+        if self.is_idle() {
+            return Ok(None);
+        }
+        self.complete().map(Some)
+    }
+
+    /// Returns true if no value is pending,
+    /// i.e. all bytes consumed so far belong to completed values.
+    pub fn is_idle(&self) -> bool {
+        self.buf.is_empty() && !self.skipping
+    }
+
+    fn complete(&mut self) -> error::Result<Json> {
+        let o = &self.opts;
+        let r = parse_buffer_with(&self.buf, |s| parse_complete_with_opts(s, o));
+        self.reset();
+        Ok(r?)
+    }
+
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.delim = Delimiter::default();
+        self.skipping = false;
+    }
+}
+
+// Finds the end of a Json value without parsing it.
+#[derive(Default)]
+pub(crate) struct Delimiter {
+    state: State,
+    depth: usize,
+}
+
+#[derive(Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Idle,
+    Scalar,
+    Container,
+    String,
+    Escape,
+    Done,
+}
+
+pub(crate) enum Step {
+    Skip,     // leading whitespace
+    Take,     // part of the value
+    Stop,     // the value ended before this byte
+    TakeStop, // this byte ends the value
+}
+
+impl Delimiter {
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    pub(crate) fn step(&mut self, b: u8) -> Step {
+        match self.state {
+            State::Done => Step::Stop,
+            State::Idle => match b {
+                b' ' | b'\t' | b'\n' | b'\r' => Step::Skip,
+                // a byte order mark; it is skipped by the parser
+                0xef | 0xbb | 0xbf => Step::Take,
+                b'"' => {
+                    self.state = State::String;
+                    Step::Take
+                },
+                b'[' | b'{' => {
+                    self.depth = 1;
+                    self.state = State::Container;
+                    Step::Take
+                },
+                _ => {
+                    self.state = State::Scalar;
+                    Step::Take
+                },
+            },
+            // within containers, we only need to watch strings and brackets
+            State::Container => match b {
+                b'"' => {
+                    self.state = State::String;
+                    Step::Take
+                },
+                b'[' | b'{' => {
+                    self.depth += 1;
+                    Step::Take
+                },
+                b']' | b'}' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        self.state = State::Done;
+                        return Step::TakeStop;
+                    }
+                    Step::Take
+                },
+                _ => Step::Take,
+            },
+            // numbers, true, false and null
+            State::Scalar => match b {
+                b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'+' | b'-' | b'.' => Step::Take,
+                _ => {
+                    self.state = State::Done;
+                    Step::Stop
+                },
+            },
+            State::String => match b {
+                b'\\' => {
+                    self.state = State::Escape;
+                    Step::Take
+                },
+                b'"' if self.depth > 0 => {
+                    self.state = State::Container;
+                    Step::Take
+                },
+                b'"' => {
+                    self.state = State::Done;
+                    Step::TakeStop
+                },
+                _ => Step::Take,
+            },
+            State::Escape => {
+                self.state = State::String;
+                Step::Take
+            },
+        }
+    }
+}
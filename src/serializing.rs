@@ -1,13 +1,142 @@
 use std::io::{self, Write};
 use std::collections::HashMap;
+use std::fmt;
 use std::str;
 use super::*;
+use super::number;
 
+// Output is collected in 'buf' and handed to the writer
+// in chunks of 'flush_at' bytes instead of token by token.
 #[derive(Debug)]
 struct Human {
     size: usize,
     line: u64,
-    ind: String,
+    ind: usize,
+    o: SerializeOpts,
+    buf: Vec<u8>,
+    flush_at: usize,
+}
+
+impl Human {
+    fn new(o: &SerializeOpts, flush_at: usize) -> Human {
+        Human {
+            size: 0,
+            line: 0,
+            ind: 0,
+            o: *o,
+            buf: Vec::with_capacity(flush_at.min(BUF_SIZE)),
+            flush_at,
+        }
+    }
+}
+
+const BUF_SIZE: usize = 8192;
+const SPACES: &[u8] = b"                ";
+const ZEROS: &[u8] = b"0000000000000000";
+const HEX: &[u8] = b"0123456789abcdef";
+
+// checks whether 'bs' starts with the escape text of a surrogate
+fn is_surrogate_escape(bs: &[u8]) -> bool {
+    bs.len() >= 6 && bs[1] == b'u' &&
+    std::str::from_utf8(&bs[2 .. 6]).ok()
+       .and_then(|x| u16::from_str_radix(x, 16).ok())
+       .is_some_and(|u| (0xd800 .. 0xe000).contains(&u))
+}
+
+/// Options that control the serializer.
+///
+/// Numbers are written like ECMAScript's `Number.prototype.toString`
+/// (and, hence, like `JSON.stringify` in browsers) with the shortest
+/// representation that reads back to the same value.
+/// Integral values are written without fraction, e.g. `1` instead of `1.0`;
+/// NaN and infinity, which Json cannot represent, are written as `null`.
+/// Very small and very large numbers are written in exponential notation;
+/// the thresholds are configurable.
+///
+/// Example:
+///
+/// ```
+///    use std::str;
+///    use jsosso::{Json, json};
+///    use jsosso::serializing::SerializeOpts;
+///
+///    let jdoc = json!([1.0, 0.1, 1e21, 0.0000001, 123456.5]);
+///    let mut v: Vec<u8> = Vec::new();
+///    jdoc.serialize_with_opts(&mut v, &SerializeOpts::default().set_compact(true)).unwrap();
+///    assert_eq!(str::from_utf8(&v).unwrap(), "[1,0.1,1e+21,1e-7,123456.5]");
+///
+///    let o = SerializeOpts::default()
+///            .set_compact(true)
+///            .set_exponent_thresholds(-3, 5);
+///    v.clear();
+///    jdoc.serialize_with_opts(&mut v, &o).unwrap();
+///    assert_eq!(str::from_utf8(&v).unwrap(), "[1,0.1,1e+21,1e-7,1.234565e+5]");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializeOpts {
+    /// Write without any whitespace or linebreaks.
+    /// The default is `false`.
+    pub compact: bool,
+    /// Numbers whose absolute value is less than 10^`exp_lower`
+    /// are written in exponential notation. The default is -6.
+    pub exp_lower: i32,
+    /// Numbers whose absolute value is at least 10^`exp_upper`
+    /// are written in exponential notation. The default is 21.
+    pub exp_upper: i32,
+    /// Write the escape text of unpaired surrogates kept by
+    /// `Utf8Policy::Preserve` (e.g. `\ud800`) as escape,
+    /// i.e. without escaping its backslash. The default is `false`.
+    pub surrogate_escapes: bool,
+    /// Write object members sorted by key instead of in the order
+    /// of the map, which differs between runs. The default is `false`.
+    pub sorted_keys: bool,
+}
+
+impl Default for SerializeOpts {
+    fn default() -> SerializeOpts {
+        SerializeOpts {
+            compact: false,
+            exp_lower: -6,
+            exp_upper: 21,
+            surrogate_escapes: false,
+            sorted_keys: false,
+        }
+    }
+}
+
+impl SerializeOpts {
+    /// Sets compact output.
+    pub fn set_compact(self, c: bool) -> SerializeOpts {
+        SerializeOpts {
+            compact: c,
+            ..self
+        }
+    }
+
+    /// Sets whether preserved surrogates are written as escape.
+    pub fn set_surrogate_escapes(self, e: bool) -> SerializeOpts {
+        SerializeOpts {
+            surrogate_escapes: e,
+            ..self
+        }
+    }
+
+    /// Sets whether object members are sorted by key.
+    pub fn set_sorted_keys(self, s: bool) -> SerializeOpts {
+        SerializeOpts {
+            sorted_keys: s,
+            ..self
+        }
+    }
+
+    /// Sets the thresholds for exponential notation of numbers.
+    pub fn set_exponent_thresholds(self, lower: i32, upper: i32) -> SerializeOpts {
+        SerializeOpts {
+            exp_lower: lower,
+            exp_upper: upper,
+            ..self
+        }
+    }
 }
 
 impl Json {
@@ -28,44 +157,116 @@ impl Json {
     /// });
     /// ```
     pub fn serialize<W: Write> (&self, w: &mut W) -> io::Result<usize> {
-        let mut h = Human {
-            size: 0,
-            line: 0,
-            ind: "".to_string(),
-        };
+        self.serialize_with_opts(w, &SerializeOpts::default())
+    }
+
+    /// Serializes the Json value into `Writer` 'w'
+    /// without any whitespace or linebreaks.
+    ///
+    /// Example:
+    /// ```
+    /// use std::str;
+    /// use jsosso::{Json, json};
+    ///
+    /// let jdoc = json!({"list": [1, 2, 3]});
+    /// let mut v: Vec<u8> = Vec::new();
+    /// jdoc.serialize_compact(&mut v).unwrap();
+    /// assert_eq!(str::from_utf8(&v).unwrap(), "{\"list\":[1,2,3]}");
+    /// ```
+    pub fn serialize_compact<W: Write> (&self, w: &mut W) -> io::Result<usize> {
+        self.serialize_with_opts(w, &SerializeOpts::default().set_compact(true))
+    }
+
+    /// Serializes the Json value into `Writer` 'w'
+    /// with explicit serializer options 'o'.
+    pub fn serialize_with_opts<W: Write> (&self, w: &mut W, o: &SerializeOpts) -> io::Result<usize> {
+        let mut h = Human::new(o, BUF_SIZE);
 
         self.write_jvalue(w, &mut h)?;
+        w.write_all(&h.buf)?;
         w.flush()?;
 
         Ok(h.size)
     }
 
+    /// Serializes the Json value into a new byte vector.
+    /// This is faster than `serialize` with a `Vec` as writer.
+    ///
+    /// Example:
+    /// ```
+    /// use jsosso::json;
+    /// use jsosso::serializing::SerializeOpts;
+    ///
+    /// let jdoc = json!({"list": [1, 2, 3]});
+    /// let v = jdoc.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+    /// assert_eq!(v, b"{\"list\":[1,2,3]}");
+    /// ```
+    pub fn serialize_to_vec(&self, o: &SerializeOpts) -> Vec<u8> {
+        let mut h = Human::new(o, usize::MAX);
+
+        // the sink is never written to, since the buffer is never flushed
+        if let Err(e) = self.write_jvalue(&mut io::sink(), &mut h) {
+            panic!("internal error: {:?}", e); // cannot happen
+        }
+
+        h.buf
+    }
+
+    /// Serializes the Json value into a string
+    /// with whitespace and linebreaks for human readers.
+    /// Use `to_string()` for the compact form.
+    ///
+    /// Example:
+    /// ```
+    /// use jsosso::json;
+    ///
+    /// let jdoc = json!([1, 2]);
+    /// assert_eq!(jdoc.to_string_pretty(), "[\n  1, 2\n]\n");
+    /// assert_eq!(jdoc.to_string(), "[1,2]");
+    /// ```
+    pub fn to_string_pretty(&self) -> String {
+        let v = self.serialize_to_vec(&SerializeOpts::default());
+        match String::from_utf8(v) {
+            Ok(s) => s,
+            Err(e) => panic!("internal error: {:?}", e), // cannot happen
+        }
+    }
+
     fn write_jvalue<W: Write> (&self, w: &mut W, h: &mut Human) -> io::Result<()> {
         match self {
-            Json::Null => return self.write_jnull(w, h),
-            Json::Boolean(t) => return self.write_jboolean(*t, w, h),
-            Json::Number(n) => return self.write_jnumber(*n, w, h),
-            Json::String(s) => return self.write_jstring(s, w, h),
-            Json::Array(a) => return self.write_jarray(a, w, h),
-            Json::Object(o) => return self.write_jobject(o, w, h),
+            Json::Null => self.write_jnull(w, h),
+            Json::Boolean(t) => self.write_jboolean(*t, w, h),
+            Json::Number(n) => self.write_jnumber(*n, w, h),
+            Json::String(s) => self.write_jstring(s, w, h),
+            Json::Array(a) => self.write_jarray(a, w, h),
+            Json::Object(o) => self.write_jobject(o, w, h),
         }
     }
 
     fn write_indent<W: Write>(&self, w: &mut W, h: &mut Human) -> io::Result<()> {
-        if h.ind.len() > 0 {
-            let v = h.ind.bytes().collect::<Vec<u8>>();
-            self.write(w, h, &v)?;
-        }
-        Ok(()) 
+        let n = h.ind;
+        self.write_repeated(w, h, SPACES, n)
     }
 
     fn write<W: Write>(&self, w: &mut W, h: &mut Human, b: &[u8]) -> io::Result<()> {
-        let mut s = 0;
-        while s < b.len() {
-            s += w.write(&b[s..])?;
+        h.buf.extend_from_slice(b);
+        h.size += b.len();
+        h.line += b.len() as u64;
+        if h.buf.len() >= h.flush_at {
+            w.write_all(&h.buf)?;
+            h.buf.clear();
+        }
+        Ok(())
+    }
+
+    // writes 'n' bytes of the repeated byte in 'chunk'
+    fn write_repeated<W: Write>(&self, w: &mut W, h: &mut Human, chunk: &[u8], n: usize) -> io::Result<()> {
+        let mut n = n;
+        while n > 0 {
+            let m = n.min(chunk.len());
+            self.write(w, h, &chunk[.. m])?;
+            n -= m;
         }
-        h.size += s;
-        h.line += s as u64;
         Ok(())
     }
 
@@ -75,57 +276,109 @@ impl Json {
 
     fn write_jboolean<W: Write> (&self, t: bool, w: &mut W, h: &mut Human) -> io::Result<()> {
         if t {
-            return self.write(w, h, b"true");
+            self.write(w, h, b"true")
         } else {
-            return self.write(w, h, b"false");
+            self.write(w, h, b"false")
         }
     }
 
     fn write_jnumber<W: Write> (&self, n: f64, w: &mut W, h: &mut Human) -> io::Result<()> {
-        let t = format!("{}", n).bytes().collect::<Vec<u8>>();
-        self.write(w, h, &t)
+        if !n.is_finite() {
+            return self.write(w, h, b"null");
+        }
+        if n == 0.0 {
+            return self.write(w, h, b"0"); // -0 as well
+        }
+        if n < 0.0 {
+            self.write(w, h, b"-")?;
+        }
+
+        // the value is 0.d1d2...dk * 10^e
+        let d = number::shortest(n);
+        let ds = &d.digits[.. d.len];
+        let k = d.len as i32;
+        let e = d.exponent;
+
+        if h.o.exp_lower < e && e <= h.o.exp_upper {
+            if k <= e {
+                self.write(w, h, ds)?;
+                return self.write_repeated(w, h, ZEROS, (e - k) as usize);
+            }
+            if e > 0 {
+                self.write(w, h, &ds[.. e as usize])?;
+                self.write(w, h, b".")?;
+                return self.write(w, h, &ds[e as usize ..]);
+            }
+            self.write(w, h, b"0.")?;
+            self.write_repeated(w, h, ZEROS, -e as usize)?;
+            return self.write(w, h, ds);
+        }
+
+        self.write(w, h, &ds[.. 1])?;
+        if k > 1 {
+            self.write(w, h, b".")?;
+            self.write(w, h, &ds[1 ..])?;
+        }
+        let x = e - 1;
+        self.write(w, h, if x < 0 { b"e-" } else { b"e+" })?;
+
+        let mut b = [0u8; 10];
+        let mut i = b.len();
+        let mut x = x.unsigned_abs();
+        loop {
+            i -= 1;
+            b[i] = b'0' + (x % 10) as u8;
+            x /= 10;
+            if x == 0 {
+                break;
+            }
+        }
+        self.write(w, h, &b[i ..])
     }
 
+
     fn write_jstring<W: Write> (&self, s: &str, w: &mut W, h: &mut Human) -> io::Result<()> {
         self.write(w, h, b"\"")?;
-        let mut v = Vec::new();
-        for b in s.bytes().collect::<Vec<u8>>() {
-            match b {
-                b'"' => {
-                    v.push(b'\\'); v.push(b);
-                },
-                b'\\' => {
-                    v.push(b'\\'); v.push(b);
-                },
-                b'/' => {
-                    v.push(b'\\'); v.push(b);
+        let bs = s.as_bytes();
+        let mut start = 0; // start of the current run of unescaped bytes
+        let mut u = *b"\\u0000";
+        for (i, b) in bs.iter().enumerate() {
+            let e: &[u8] = match b {
+                b'"' => b"\\\"",
+                b'\\' if h.o.surrogate_escapes && is_surrogate_escape(&bs[i ..]) => continue,
+                b'\\' => b"\\\\",
+                b'/' => b"\\/",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                8 => b"\\b", // backspace
+                12 => b"\\f", // formfeed
+                0 ..= 0x1f => { // other control characters
+                    u[4] = HEX[(b >> 4) as usize];
+                    u[5] = HEX[(b & 0xf) as usize];
+                    &u
                 },
-                b'\n' => {
-                    v.push(b'\\'); v.push(b'n');
-                },
-                b'\r' => {
-                    v.push(b'\\'); v.push(b'r');
-                },
-                b'\t' => {
-                    v.push(b'\\'); v.push(b't');
-                },
-                8 => { // backspace
-                    v.push(b'\\'); v.push(b'b');
-                },
-                12 => { // formfeed
-                    v.push(b'\\'); v.push(b'f');
-                },
-                _ => v.push(b),
+                _ => continue,
+            };
+            if start < i {
+                self.write(w, h, &bs[start .. i])?;
             }
+            self.write(w, h, e)?;
+            start = i + 1;
+        }
+        if start < bs.len() {
+            self.write(w, h, &bs[start ..])?;
         }
-        self.write(w, h, &v)?;
         self.write(w, h, b"\"")
     }
 
     fn write_jarray<W: Write> (&self, a: &Vec<Json>, w: &mut W, h: &mut Human) -> io::Result<()> {
+        if h.o.compact {
+            return self.write_compact_jarray(a, w, h);
+        }
         let mut first = true;
         self.write(w, h, b"[\n")?;
-        h.ind += "  ";
+        h.ind += 2;
         h.line = 0;
         for o in a {
            if first {
@@ -142,18 +395,33 @@ impl Json {
            }
            o.write_jvalue(w, h)?;
         }
-        h.ind.pop();
-        h.ind.pop();
+        h.ind -= 2;
         self.write(w, h, b"\n")?;
         self.write_indent(w,h)?;
         self.write(w, h, b"]\n")
     }
 
     fn write_jobject<W: Write> (&self, o: &HashMap<String, Json>, w: &mut W, h: &mut Human) -> io::Result<()> {
+        if h.o.sorted_keys {
+            let mut ms: Vec<(&String, &Json)> = o.iter().collect();
+            ms.sort_unstable_by_key(|(k, _)| *k);
+            return self.write_members(ms, w, h);
+        }
+        self.write_members(o, w, h)
+    }
+
+    fn write_members<'a, I, W>(&self, ms: I, w: &mut W, h: &mut Human) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a String, &'a Json)>,
+        W: Write,
+    {
+        if h.o.compact {
+            return self.write_compact_members(ms, w, h);
+        }
         let mut first = true;
         self.write(w, h, b"{\n")?;
-        h.ind += "  ";
-        for (k, v) in o {
+        h.ind += 2;
+        for (k, v) in ms {
            if first {
                first = false;
            } else {
@@ -164,10 +432,63 @@ impl Json {
            self.write(w, h, b": ")?;
            v.write_jvalue(w, h)?;
         }
-        h.ind.pop();
-        h.ind.pop();
+        h.ind -= 2;
         self.write(w, h, b"\n")?;
         self.write_indent(w,h)?;
         self.write(w, h, b"}\n")
     }
+
+    fn write_compact_jarray<W: Write> (&self, a: &Vec<Json>, w: &mut W, h: &mut Human) -> io::Result<()> {
+        let mut first = true;
+        self.write(w, h, b"[")?;
+        for o in a {
+           if first {
+               first = false;
+           } else {
+               self.write(w, h, b",")?;
+           }
+           o.write_jvalue(w, h)?;
+        }
+        self.write(w, h, b"]")
+    }
+
+    fn write_compact_members<'a, I, W>(&self, ms: I, w: &mut W, h: &mut Human) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a String, &'a Json)>,
+        W: Write,
+    {
+        let mut first = true;
+        self.write(w, h, b"{")?;
+        for (k, v) in ms {
+           if first {
+               first = false;
+           } else {
+               self.write(w, h, b",")?;
+           }
+           self.write_jstring(k, w, h)?;
+           self.write(w, h, b":")?;
+           v.write_jvalue(w, h)?;
+        }
+        self.write(w, h, b"}")
+    }
+}
+
+/// Formats the Json value compactly, i.e. without any whitespace or linebreaks;
+/// `to_string()` is the fast way to get the compact serialization as `String`.
+///
+/// Example:
+/// ```
+/// use jsosso::json;
+///
+/// let jdoc = json!({"list": [1, 2.5, "three"]});
+/// assert_eq!(jdoc.to_string(), "{\"list\":[1,2.5,\"three\"]}");
+/// ```
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = self.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+        match str::from_utf8(&v) {
+            Ok(s) => f.write_str(s),
+            Err(e) => panic!("internal error: {:?}", e), // cannot happen
+        }
+    }
 }
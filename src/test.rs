@@ -1,10 +1,23 @@
+#![allow(clippy::approx_constant)]
+
 use super::*;
 use super::parsing::*;
 use super::arbitrary::*;
+use super::encoding::*;
+use super::ndjson::{Reader, Writer, LineError};
+use super::jsonseq::{Reader as SeqReader, Writer as SeqWriter, RecordError, RS};
+use super::serializing::SerializeOpts;
+use super::error;
+use super::push::PushParser;
+use super::cbor::{self, CborOpts};
+use super::msgpack::{self, MsgpackOpts, ExtPolicy};
+use super::bson::{self, BsonOpts};
+use super::ubjson;
+use std::str::FromStr;
 use std::io::Cursor;
 use std::fs::File;
 use std::str;
-use pacosso::Opts;
+use pacosso::{Opts, ParseResult};
 
 fn approx_eq(a: f64, b: f64) -> bool {
     println!("{} >= {} && {} <= {}", a, b - 0.1, a, b + 0.1);
@@ -41,12 +54,7 @@ fn test_escape() {
         Ok(Json::String(s)) => {
             println!("{}", s);
             println!("linebreak: '\n', another: '\r\n', tab: '\t'");
-            let r = if s == "linebreak: '\n', another: '\r\n', tab: '\t'" {
-                true
-            } else {
-                false 
-            };
-            r
+            s == "linebreak: '\n', another: '\r\n', tab: '\t'"
         },
         Ok(_) => panic!("unexpected value"),
         Err(e) => panic!("error: {:?}", e),
@@ -65,12 +73,7 @@ fn test_unicode() {
     assert!(match parse(&mut s) {
         Ok(Json::String(s)) => {
             println!("{}", s);
-            let r = if s == "𝄞 mus�ic" {
-                true
-            } else {
-                false 
-            };
-            r
+            s == "𝄞 mus�ic"
         },
         Ok(_) => panic!("unexpected value"),
         Err(e) => panic!("error: {:?}", e),
@@ -578,6 +581,44 @@ fn test_fail_bad_boolean() {
     });
 }
 
+#[test]
+fn test_fail_truncated_literals() {
+    for l in ["null", "true", "false"] {
+        for k in 1 .. l.len() {
+            for t in [l[.. k].to_string(), format!("[{}", &l[.. k]), format!("{{\"a\": {}", &l[.. k])] {
+                assert!(match Json::from_str(&t) {
+                    Ok(v) => panic!("unexpected value: {:?}", v),
+                    Err(e) if e.is_eof() => true,
+                    Err(e) => panic!("unexpected error on '{}': {:?}", t, e),
+                });
+            }
+        }
+    }
+
+    // the truncated literal ends at any offset of the buffer
+    // but one byte before its end (see EofGuard)
+    for l in ["null", "true", "false"] {
+        for k in 1 .. l.len() {
+            for n in 0 .. 8 {
+                let t = format!("[{}{}", " ".repeat(n), &l[.. k]);
+                if t.len() % 8 == 7 {
+                    continue;
+                }
+                let mut input = Cursor::new(t.clone().into_bytes());
+                let mut s = Stream::new(Opts::default()
+                           .set_buf_size(8)
+                           .set_buf_num(3),
+                           &mut input);
+                assert!(match parse(&mut s) {
+                    Ok(v) => panic!("unexpected value: {:?}", v),
+                    Err(e) if e.is_eof() => true,
+                    Err(e) => panic!("unexpected error on '{}': {:?}", t, e),
+                });
+            }
+        }
+    }
+}
+
 #[test]
 fn test_fail_single_quote() {
     let v: Vec<u8> = r#"
@@ -649,6 +690,40 @@ fn test_pass_empty_string() {
     });
 }
 
+// strings are scanned in bulk; quotes, escapes and multi-byte characters
+// may be anywhere relative to the eight-byte words and to the buffers
+#[test]
+fn test_pass_strings_at_any_offset() {
+    for size in [8, 13] {
+        for n in 0 .. 40 {
+            for (special, expected) in [("\\\"", "\""), ("\\\\", "\\"), ("\\u00e9", "\u{e9}"), ("\u{1d11e}", "\u{1d11e}")] {
+                let t = "x".repeat(n);
+                let v = format!("[\"{}{}{}\", \"{}\"]", t, special, t, t).into_bytes();
+                let mut input = EofGuard::new(Cursor::new(v));
+                let mut s = Stream::new(Opts::default()
+                           .set_buf_size(size)
+                           .set_buf_num(3),
+                           &mut input);
+                assert!(match parse_complete(&mut s) {
+                    Ok(j) => j == json!([format!("{}{}{}", t, expected, t), t]),
+                    Err(e) => panic!("unexpected error: {:?}", e),
+                }, "{} bytes around {}", n, special);
+            }
+
+            let v = format!("\"{}", "x".repeat(n)).into_bytes();
+            let mut input = Cursor::new(v);
+            let mut s = Stream::new(Opts::default()
+                       .set_buf_size(size)
+                       .set_buf_num(3),
+                       &mut input);
+            assert!(match parse_complete(&mut s) {
+                Ok(v) => panic!("unexpected value: {:?}", v),
+                Err(e) => e.is_eof(),
+            });
+        }
+    }
+}
+
 #[test]
 fn test_pass_empty_object() {
     let v: Vec<u8> = r#"
@@ -734,6 +809,522 @@ fn test_pass_from_file_pattern3() {
     });
 }
 
+#[test]
+fn test_seeded_arbitrary() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    for seed in [0, 1, 42, u64::MAX] {
+        let j = make_seeded(seed);
+        assert_eq!(make_seeded(seed), j, "seed: {}", seed);
+        assert_eq!(make_arbitrary_with(&mut StdRng::seed_from_u64(seed)), j, "seed: {}", seed);
+
+        let k = make_n_seeded(20, seed);
+        assert_eq!(make_n_seeded(20, seed), k, "seed: {}", seed);
+        assert_eq!(make_n_arbitrary_with(&mut StdRng::seed_from_u64(seed), 20), k, "seed: {}", seed);
+    }
+
+    // one generator yields a reproducible sequence of values
+    let mut r1 = StdRng::seed_from_u64(7);
+    let mut r2 = StdRng::seed_from_u64(7);
+    let v1: Vec<Json> = (0 .. 10).map(|_| make_n_arbitrary_with(&mut r1, 20)).collect();
+    let v2: Vec<Json> = (0 .. 10).map(|_| make_n_arbitrary_with(&mut r2, 20)).collect();
+    assert_eq!(v1, v2);
+
+    // different seeds are expected to produce different values
+    let vs: Vec<Json> = (0 .. 10).map(|i| make_n_seeded(20, i)).collect();
+    assert!(vs.iter().skip(1).any(|v| *v != vs[0]));
+}
+
+#[test]
+fn test_generator_config() {
+    fn depth(j: &Json) -> usize {
+        match j {
+            Json::Array(v) => 1 + v.iter().map(depth).max().unwrap_or(0),
+            Json::Object(m) => 1 + m.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn all<F: Fn(&Json) -> bool + Copy>(j: &Json, f: F) -> bool {
+        f(j) && match j {
+            Json::Array(v) => v.iter().all(|e| all(e, f)),
+            Json::Object(m) => m.iter().all(|(k, e)| f(&Json::String(k.clone())) && all(e, f)),
+            _ => true,
+        }
+    }
+
+    let c = GeneratorConfig::default();
+    assert_eq!(c.depth, 3);
+    assert_eq!(c.weights, [5, 10, 15, 20, 25, 25]);
+    assert_eq!(GeneratorConfig::default().set_string_len(5, 1).string_len, (5, 5));
+
+    // only numbers
+    let c = GeneratorConfig::default().set_weights([0, 0, 1, 0, 0, 0]);
+    for seed in 0 .. 20 {
+        assert!(match make_n_seeded_with_config(10, seed, &c) {
+            Json::Number(n) => (0.0 .. 1.0).contains(&n),
+            j => panic!("unexpected value: {:?} (seed: {})", j, seed),
+        });
+    }
+    let c = c.set_numbers(Numbers::Integers);
+    for seed in 0 .. 20 {
+        assert!(match make_n_seeded_with_config(10, seed, &c) {
+            Json::Number(n) => n.fract() == 0.0 && n.abs() <= 9007199254740992.0,
+            j => panic!("unexpected value: {:?} (seed: {})", j, seed),
+        });
+    }
+
+    // mixed numbers reach negative, large and subnormal numbers
+    let c = c.set_numbers(Numbers::Mixed);
+    let ns: Vec<f64> = (0 .. 500).map(|seed| match make_n_seeded_with_config(10, seed, &c) {
+        Json::Number(n) => n,
+        j => panic!("unexpected value: {:?} (seed: {})", j, seed),
+    }).collect();
+    assert!(ns.iter().all(|n| n.is_finite()));
+    assert!(ns.iter().any(|n| *n < 0.0));
+    assert!(ns.iter().any(|n| n.abs() > 1e100));
+    assert!(ns.iter().any(|n| n.is_subnormal()));
+    assert!(ns.iter().any(|n| *n != 0.0 && n.fract() == 0.0));
+
+    // no nulls, no containers
+    let c = GeneratorConfig::default().set_weights([0, 1, 1, 1, 0, 0]);
+    for seed in 0 .. 20 {
+        let j = make_n_seeded_with_config(10, seed, &c);
+        assert!(!matches!(j, Json::Null | Json::Array(_) | Json::Object(_)), "seed: {}", seed);
+    }
+
+    // strings
+    let strings = |a: Alphabet| -> Vec<String> {
+        let c = GeneratorConfig::default()
+                .set_weights([0, 0, 0, 1, 0, 0])
+                .set_alphabet(a)
+                .set_string_len(50, 100);
+        (0 .. 50).map(|seed| match make_n_seeded_with_config(10, seed, &c) {
+            Json::String(s) => s,
+            j => panic!("unexpected value: {:?} (seed: {})", j, seed),
+        }).collect()
+    };
+    let ss = strings(Alphabet::Simple);
+    assert!(ss.iter().all(|s| (50 ..= 100).contains(&s.chars().count())));
+    assert!(ss.iter().all(|s| s.bytes().all(|b| (48 .. 90).contains(&b) && b != b'\\')));
+    let ss = strings(Alphabet::Ascii);
+    assert!(ss.iter().all(|s| s.bytes().all(|b| (0x20 ..= 0x7e).contains(&b))));
+    assert!(ss.iter().any(|s| s.contains('"') && s.contains('\\')));
+    let ss = strings(Alphabet::Escapes);
+    assert!(ss.iter().all(|s| s.is_ascii()));
+    assert!(ss.iter().any(|s| s.bytes().any(|b| b < 0x20)));
+    let ss = strings(Alphabet::Unicode);
+    assert!(ss.iter().any(|s| s.chars().any(|c| c as u32 > 0xffff)));
+    assert!(ss.iter().any(|s| s.chars().any(|c| (0x80 ..= 0xffff).contains(&(c as u32)))));
+    assert!(ss.iter().any(|s| s.bytes().any(|b| b < 0x20)));
+
+    // depth and breadth
+    let c = GeneratorConfig::default().set_depth(1).set_breadth(3, 2);
+    for seed in 0 .. 50 {
+        let j = make_n_seeded_with_config(1000, seed, &c);
+        assert!(depth(&j) <= 1, "seed: {}", seed);
+        assert!(match &j {
+            Json::Array(v) => v.len() <= 3,
+            Json::Object(m) => m.len() <= 2,
+            _ => true,
+        }, "seed: {}", seed);
+    }
+    let c = GeneratorConfig::default().set_depth(6).set_breadth(3, 3).set_weights([1, 0, 0, 0, 10, 0]);
+    let ds: Vec<usize> = (0 .. 20).map(|seed| depth(&make_n_seeded_with_config(1000, seed, &c))).collect();
+    assert!(ds.iter().all(|d| *d <= 6));
+    assert!(ds.iter().any(|d| *d > 3));
+
+    // all weights 0
+    let c = GeneratorConfig::default().set_weights([0; 6]);
+    assert_eq!(make_n_seeded_with_config(10, 0, &c), Json::Null);
+
+    // the stress configuration produces no non-finite numbers
+    let c = GeneratorConfig::stress();
+    for seed in 0 .. 20 {
+        let j = make_n_seeded_with_config(100, seed, &c);
+        assert!(all(&j, |e| !matches!(e, Json::Number(n) if !n.is_finite())), "seed: {}", seed);
+    }
+}
+
+#[test]
+fn test_stress_round_trip() {
+    let c = GeneratorConfig::stress();
+    let o = SerializeOpts::default();
+    for seed in 0 .. 50 {
+        let original = make_n_seeded_with_config(200, seed, &c);
+        for o in [o, o.set_compact(true)] {
+            let v = original.serialize_to_vec(&o);
+            assert!(match Json::from_slice(&v) {
+                Ok(j) => j == original,
+                Err(e) => panic!("unexpected error: {:?} (seed: {})", e, seed),
+            }, "seed: {}", seed);
+        }
+
+        let mut v = Vec::new();
+        assert!(original.serialize(&mut v).is_ok());
+        let mut input = EofGuard::new(Cursor::new(v));
+        let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+        assert!(match parse_complete(&mut s) {
+            Ok(j) => j == original,
+            Err(e) => panic!("unexpected error: {:?} (seed: {})", e, seed),
+        }, "seed: {}", seed);
+
+        assert_eq!(cbor::from_slice(&cbor::to_vec(&original)).unwrap(), original, "seed: {}", seed);
+        assert_eq!(msgpack::from_slice(&msgpack::to_vec(&original)).unwrap(), original, "seed: {}", seed);
+        assert_eq!(ubjson::from_slice(&ubjson::to_vec(&original)).unwrap(), original, "seed: {}", seed);
+    }
+}
+
+#[test]
+fn test_make_from_schema() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    let c = GeneratorConfig::default();
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut gen = |s: &Json| -> Vec<Json> {
+        (0 .. 50).map(|_| match make_from_schema(&mut rng, s, &c) {
+            Ok(j) => j,
+            Err(e) => panic!("unexpected error: {} for {:?}", e, s),
+        }).collect()
+    };
+    let numbers = |vs: Vec<Json>| -> Vec<f64> {
+        vs.into_iter().map(|j| match j {
+            Json::Number(n) => n,
+            j => panic!("unexpected value: {:?}", j),
+        }).collect()
+    };
+    let strings = |vs: Vec<Json>| -> Vec<String> {
+        vs.into_iter().map(|j| match j {
+            Json::String(s) => s,
+            j => panic!("unexpected value: {:?}", j),
+        }).collect()
+    };
+
+    // types
+    assert!(gen(&json!({"type": "null"})).iter().all(|j| *j == Json::Null));
+    assert!(gen(&json!({"type": "boolean"})).iter().all(|j| matches!(j, Json::Boolean(_))));
+    assert!(gen(&json!({"type": ["null", "boolean"]})).iter().all(|j| matches!(j, Json::Null | Json::Boolean(_))));
+    assert!(gen(&json!({"type": ["null", "boolean"]})).contains(&Json::Null));
+    assert!(gen(&json!({"const": {"a": [1]}})).iter().all(|j| *j == json!({"a": [1]})));
+    let vs = gen(&json!({"enum": [1, "two", null]}));
+    assert!(vs.iter().all(|j| *j == json!(1) || *j == json!("two") || *j == Json::Null));
+    let vs = gen(&json!({"anyOf": [{"type": "string"}, {"type": "integer"}]}));
+    assert!(vs.iter().all(|j| matches!(j, Json::String(_)) || matches!(j, Json::Number(n) if n.fract() == 0.0)));
+    assert!(gen(&json!(true)).len() == 50);
+    assert!(gen(&json!({})).len() == 50);
+
+    // numbers
+    let ns = numbers(gen(&json!({"type": "integer", "minimum": (Json::from(-3)), "maximum": 3})));
+    assert!(ns.iter().all(|n| n.fract() == 0.0 && (-3.0 ..= 3.0).contains(n)));
+    assert!(ns.contains(&-3.0) && ns.contains(&3.0));
+    let ns = numbers(gen(&json!({"type": "integer", "exclusiveMinimum": 1, "exclusiveMaximum": 4})));
+    assert!(ns.iter().all(|n| *n == 2.0 || *n == 3.0));
+    let ns = numbers(gen(&json!({"type": "integer", "minimum": 1, "exclusiveMinimum": true, "maximum": 3})));
+    assert!(ns.iter().all(|n| *n == 2.0 || *n == 3.0));
+    let ns = numbers(gen(&json!({"type": "integer", "minimum": 1000000})));
+    assert!(ns.iter().all(|n| n.fract() == 0.0 && *n >= 1000000.0));
+    let ns = numbers(gen(&json!({"type": "integer", "multipleOf": 7, "maximum": 0})));
+    assert!(ns.iter().all(|n| *n % 7.0 == 0.0 && *n <= 0.0));
+    let ns = numbers(gen(&json!({"type": "number", "minimum": 0.5, "exclusiveMaximum": 0.75})));
+    assert!(ns.iter().all(|n| (0.5 .. 0.75).contains(n)));
+    let ns = numbers(gen(&json!({"minimum": 2.5, "maximum": 2.5})));
+    assert!(ns.iter().all(|n| *n == 2.5));
+    let ns = numbers(gen(&json!({"type": "number", "multipleOf": 0.5, "minimum": 1, "maximum": 2})));
+    assert!(ns.iter().all(|n| *n == 1.0 || *n == 1.5 || *n == 2.0));
+    // ranges whose width is not finite
+    let ns = numbers(gen(&json!({"type": "number", "minimum": (Json::from(-1e308)), "maximum": 1e308})));
+    assert!(ns.iter().all(|n| (-1e308 ..= 1e308).contains(n)));
+    assert!(ns.iter().any(|n| *n < 0.0) && ns.iter().any(|n| *n > 0.0));
+    let ns = numbers(gen(&json!({"type": "integer", "minimum": (Json::from(-1e308)), "maximum": 1e308})));
+    assert!(ns.iter().all(|n| n.fract() == 0.0 && (-1e308 ..= 1e308).contains(n)));
+    let ns = numbers(gen(&json!({"multipleOf": 1e300, "minimum": (Json::from(-f64::MAX)), "exclusiveMaximum": (Json::from(f64::MAX))})));
+    assert!(ns.iter().all(|n| n.is_finite()));
+
+    // strings
+    let ss = strings(gen(&json!({"type": "string", "minLength": 3, "maxLength": 5})));
+    assert!(ss.iter().all(|s| (3 ..= 5).contains(&s.chars().count())));
+    let ss = strings(gen(&json!({"maxLength": 0})));
+    assert!(ss.iter().all(|s| s.is_empty()));
+    let ss = strings(gen(&json!({"type": "string", "pattern": "^[A-Z]{3}-\\d{4}$"})));
+    assert!(ss.iter().all(|s| s.len() == 8
+                             && s[.. 3].bytes().all(|b| b.is_ascii_uppercase())
+                             && &s[3 .. 4] == "-"
+                             && s[4 ..].bytes().all(|b| b.is_ascii_digit())));
+    let ss = strings(gen(&json!({"pattern": "^(ab|c)+x?\\.\\w$"})));
+    assert!(ss.iter().all(|s| s.trim_start_matches(['a', 'b', 'c']).trim_start_matches('x').starts_with('.')));
+    assert!(ss.iter().all(|s| s.chars().last().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')));
+    let ss = strings(gen(&json!({"pattern": "^(?:ab|c){2}[x-z_]*$", "minLength": 4, "maxLength": 6})));
+    assert!(ss.iter().all(|s| (4 ..= 6).contains(&s.len())));
+    assert!(ss.iter().all(|s| s.starts_with("ab") || s.starts_with("cc") || s.starts_with("ca")));
+    assert!(ss.iter().all(|s| s.trim_start_matches(['a', 'b', 'c']).chars().all(|c| "xyz_".contains(c))));
+    let ss = strings(gen(&json!({"format": "uuid"})));
+    assert!(ss.iter().all(|s| s.len() == 36 && &s[14 .. 15] == "4" && s.split('-').count() == 5));
+    let ss = strings(gen(&json!({"type": "string", "format": "date-time"})));
+    assert!(ss.iter().all(|s| s.len() == 20 && &s[10 .. 11] == "T" && s.ends_with('Z')));
+    let ss = strings(gen(&json!({"format": "email"})));
+    assert!(ss.iter().all(|s| s.split('@').count() == 2 && s.contains('.')));
+    let ss = strings(gen(&json!({"format": "ipv4"})));
+    assert!(ss.iter().all(|s| s.split('.').all(|n| n.parse::<u8>().is_ok())));
+
+    // arrays
+    let vs = gen(&json!({"type": "array", "items": {"type": "integer"}, "minItems": 2, "maxItems": 4, "uniqueItems": true}));
+    assert!(vs.iter().all(|j| match j {
+        Json::Array(v) => (2 ..= 4).contains(&v.len())
+                          && v.iter().all(|e| matches!(e, Json::Number(n) if n.fract() == 0.0))
+                          && v.iter().enumerate().all(|(i, e)| !v[i + 1 ..].contains(e)),
+        _ => false,
+    }));
+    let vs = gen(&json!({"prefixItems": [{"type": "string"}, {"type": "null"}], "items": false}));
+    assert!(vs.iter().all(|j| match j {
+        Json::Array(v) => v.len() <= 2
+                          && v.first().is_none_or(|e| matches!(e, Json::String(_)))
+                          && v.get(1).is_none_or(|e| *e == Json::Null),
+        _ => false,
+    }));
+    let vs = gen(&json!({"items": [{"const": 1}], "additionalItems": {"const": 2}, "minItems": 3, "maxItems": 3}));
+    assert!(vs.iter().all(|j| *j == json!([1, 2, 2])));
+
+    // objects
+    let vs = gen(&json!({
+        "properties": {"a": {"type": "integer"}, "b": {"type": "string"}},
+        "required": ["a"],
+        "additionalProperties": false
+    }));
+    assert!(vs.iter().all(|j| match j {
+        Json::Object(m) => matches!(m.get("a"), Some(Json::Number(_)))
+                           && m.get("b").is_none_or(|b| matches!(b, Json::String(_)))
+                           && m.len() <= 2,
+        _ => false,
+    }));
+    assert!(vs.iter().any(|j| matches!(j, Json::Object(m) if m.len() == 2)));
+    assert!(vs.iter().any(|j| matches!(j, Json::Object(m) if m.len() == 1)));
+    let vs = gen(&json!({
+        "additionalProperties": {"type": "boolean"},
+        "propertyNames": {"pattern": "^k[0-9]{3}$"},
+        "minProperties": 2,
+        "maxProperties": 4
+    }));
+    assert!(vs.iter().all(|j| match j {
+        Json::Object(m) => (2 ..= 4).contains(&m.len())
+                           && m.iter().all(|(k, v)| k.len() == 4 && k.starts_with('k') && matches!(v, Json::Boolean(_))),
+        _ => false,
+    }));
+
+    // references
+    let vs = gen(&json!({
+        "definitions": {"pos": {"type": "integer", "minimum": 1, "maximum": 9}},
+        "$defs": {"list": {"type": "array", "items": {"$ref": "#/definitions/pos"}, "maxItems": 3}},
+        "$ref": "#/$defs/list"
+    }));
+    assert!(vs.iter().all(|j| match j {
+        Json::Array(v) => v.len() <= 3 && v.iter().all(|e| matches!(e, Json::Number(n) if (1.0 ..= 9.0).contains(n))),
+        _ => false,
+    }));
+
+    // recursive schemas stop at the configured depth
+    let tree = json!({
+        "$defs": {"node": {
+            "type": "object",
+            "properties": {"children": {"type": "array", "items": {"$ref": "#/$defs/node"}, "maxItems": 3}},
+            "required": ["children"]
+        }},
+        "$ref": "#/$defs/node"
+    });
+    assert_eq!(gen(&tree).len(), 50);
+
+    // the order schema for the client
+    let order = Json::from_file("rsc/schema/order.json").unwrap();
+    for j in gen(&order) {
+        let m = match j {
+            Json::Object(m) => m,
+            j => panic!("unexpected value: {:?}", j),
+        };
+        assert!(["id", "created", "customer", "state", "items"].iter().all(|k| m.contains_key(*k)));
+        assert!(m.keys().all(|k| ["id", "created", "customer", "state", "items", "note"].contains(&k.as_str())));
+        assert!(matches!(&m["items"], Json::Array(v) if (1 ..= 5).contains(&v.len())));
+        assert!(matches!(&m["customer"], Json::Object(c) if c.contains_key("name") && c.contains_key("email")));
+    }
+
+    // errors
+    let err = |s: Json| -> SchemaError {
+        match make_from_schema(&mut StdRng::seed_from_u64(0), &s, &c) {
+            Err(e) => e,
+            Ok(j) => panic!("unexpected value: {:?} for {:?}", j, s),
+        }
+    };
+    assert_eq!(err(json!(false)).path, "#");
+    assert_eq!(err(json!({"properties": {"a": false}, "required": ["a"]})).path, "#/properties/a");
+    assert!(err(json!({"type": "integer", "minimum": 1.2, "maximum": 1.8})).message.contains("minimum and maximum"));
+    assert!(err(json!({"type": "number", "exclusiveMinimum": 1, "exclusiveMaximum": 1})).message.contains("minimum and maximum"));
+    assert!(err(json!({"type": "number", "minimum": (Json::from(f64::NEG_INFINITY)), "maximum": 1})).message.contains("finite"));
+    assert!(err(json!({"type": "string", "minLength": 3, "maxLength": 2})).message.contains("minLength"));
+    assert!(err(json!({"pattern": "^a{2}$", "minLength": 3})).message.contains("pattern"));
+    assert!(err(json!({"pattern": "(?=a)"})).message.starts_with("unsupported pattern"));
+    assert!(err(json!({"pattern": "[^a]"})).message.starts_with("unsupported pattern"));
+    assert!(err(json!({"pattern": "(a"})).message.starts_with("unsupported pattern"));
+    assert!(err(json!({"type": "array", "items": {"enum": [1, 2]}, "minItems": 3, "uniqueItems": true})).message.contains("unique"));
+    assert!(err(json!({"type": "array", "items": false, "minItems": 1})).message.contains("minItems"));
+    assert!(err(json!({"additionalProperties": false, "minProperties": 1})).message.contains("minProperties"));
+    assert!(err(json!({"allOf": [{}]})).message.contains("allOf"));
+    assert!(err(json!({"$ref": "#/$defs/missing"})).message.contains("unresolvable"));
+    assert!(err(json!({"$ref": "http://example.com/schema"})).message.contains("unsupported $ref"));
+    assert!(err(json!({"$ref": "#"})).message.contains("too deeply"));
+    assert!(err(json!({"type": "float"})).message.contains("unknown type"));
+    assert_eq!(err(json!({"enum": []})).to_string(), "schema at '#': enum must be a non-empty array");
+}
+
+#[test]
+fn test_shrink() {
+    fn size(j: &Json) -> usize {
+        match j {
+            Json::Array(v) => 1 + v.iter().map(size).sum::<usize>(),
+            Json::Object(m) => 1 + m.iter().map(|(k, v)| k.len() + size(v)).sum::<usize>(),
+            Json::String(s) => 1 + s.len(),
+            _ => 1,
+        }
+    }
+
+    assert!(shrink(&json!(null)).next().is_none());
+    assert_eq!(shrink(&json!(false)).collect::<Vec<_>>(), vec![json!(null)]);
+    assert_eq!(shrink(&json!(true)).collect::<Vec<_>>(), vec![json!(null), json!(false)]);
+    assert_eq!(shrink(&json!(0)).collect::<Vec<_>>(), vec![json!(null)]);
+    assert_eq!(shrink(&json!(1)).collect::<Vec<_>>(), vec![json!(null), json!(0)]);
+    assert_eq!(shrink(&json!(1000)).collect::<Vec<_>>(), vec![json!(null), json!(0), json!(1), json!(500)]);
+    assert_eq!(shrink(&Json::from(-7)).collect::<Vec<_>>(), vec![json!(null), json!(0), Json::from(-1), Json::from(-3), json!(7)]);
+    assert_eq!(shrink(&json!(0.25)).collect::<Vec<_>>(), vec![json!(null), json!(0), json!(1)]);
+    assert_eq!(shrink(&Json::from(-2.5)).collect::<Vec<_>>(), vec![json!(null), json!(0), Json::from(-1), Json::from(-2), json!(2.5)]);
+    assert_eq!(shrink(&json!("")).collect::<Vec<_>>(), vec![json!(null)]);
+    assert_eq!(shrink(&json!("a")).collect::<Vec<_>>(), vec![json!(null), json!("")]);
+    assert_eq!(shrink(&json!("x\n\u{1F600}y")).collect::<Vec<_>>(), vec![
+        json!(null), json!(""), json!("x\n"), json!("\n\u{1F600}y"), json!("x\n\u{1F600}"), json!("xaay"),
+    ]);
+    assert_eq!(shrink(&json!([])).collect::<Vec<_>>(), vec![json!(null)]);
+    assert_eq!(shrink(&json!([true])).collect::<Vec<_>>(), vec![json!(null), json!(true), json!([]), json!([null]), json!([false])]);
+    let v: Vec<Json> = shrink(&json!([1, 2, 3])).collect();
+    assert_eq!(v[.. 6], [json!(null), json!(1), json!(2), json!(3), json!([]), json!([1])]);
+    assert!(v.contains(&json!([1, 3])) && v.contains(&json!([1, 1, 3])));
+    let v: Vec<Json> = shrink(&json!({"a": "b", "c": [1]})).collect();
+    assert_eq!(v[.. 6], [json!(null), json!("b"), json!([1]), json!("a"), json!("c"), json!({})]);
+    assert!(v.contains(&json!({"a": "b"})) && v.contains(&json!({"a": "", "c": [1]})) && v.contains(&json!({"a": "b", "c": []})));
+    assert!(v.contains(&json!({"": "b", "c": [1]})));
+
+    // shrinking terminates and no variant is the value itself;
+    // we follow a random one of the first variants down to null
+    let c = GeneratorConfig::stress();
+    for seed in 0 .. 20 {
+        let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(seed);
+        let mut j = make_n_seeded_with_config(20, seed, &c);
+        let mut steps = 0;
+        loop {
+            let v: Vec<Json> = shrink(&j).collect();
+            assert!(v.iter().all(|x| *x != j), "seed: {}", seed);
+            let k = if v.len() > 1 { rand::Rng::gen_range(&mut rng, 1 .. v.len().min(8)) } else { 0 };
+            match v.into_iter().nth(k) {
+                Some(x) => {
+                    assert!(size(&x) <= size(&j), "seed: {}", seed);
+                    j = x;
+                },
+                None => break,
+            }
+            steps += 1;
+            assert!(steps < 10_000, "seed: {}", seed);
+        }
+        assert_eq!(j, Json::Null);
+    }
+}
+
+#[test]
+fn test_malformed() {
+    // every mutation applies to a value with strings and numbers
+    let j = json!({"name": "jsosso", "tags": [1, 2.5, true, "x\u{e9}"]});
+    for seed in 0 .. 50 {
+        let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(seed);
+        for m in Mutation::ALL {
+            let d = mutate(&mut rng, &j, m).unwrap();
+            assert_eq!(d.mutation, m);
+            assert!(d.offset <= d.text.len());
+        }
+    }
+    assert!(mutate(&mut rand::thread_rng(), &json!([true]), Mutation::InvalidUtf8).is_none());
+    assert!(mutate(&mut rand::thread_rng(), &json!([true]), Mutation::OverlongNumber).is_none());
+
+    // scalars are wrapped, so a stray comma at the end is not ignored
+    for _ in 0 .. 20 {
+        let d = mutate(&mut rand::thread_rng(), &Json::from(1), Mutation::StrayComma).unwrap();
+        assert!(Json::from_slice(&d.text).is_err());
+    }
+
+    // the parser reports the expected class of error
+    // for complete documents and for a stream with a small buffer
+    let c = GeneratorConfig::stress();
+    for seed in 0 .. 500 {
+        let d = make_malformed_seeded(20, seed, &c);
+        assert!(match Json::from_slice(&d.text) {
+            Err(e) => d.expected.matches(&e),
+            Ok(v) => panic!("unexpected value: {:?} (seed: {})", v, seed),
+        }, "{:?} (seed: {})", d, seed);
+
+        let mut input = EofGuard::new(Cursor::new(d.text.clone()));
+        let mut s = Stream::new(Opts::default()
+                   .set_buf_size(8)
+                   .set_buf_num(3),
+                   &mut input);
+        assert!(match parse_complete(&mut s) {
+            Err(e) => d.expected.matches(&error::Error::from(e)),
+            Ok(v) => panic!("unexpected value: {:?} (seed: {})", v, seed),
+        }, "{:?} (seed: {})", d, seed);
+    }
+}
+
+#[test]
+fn test_make_sized() {
+    fn depth(j: &Json) -> usize {
+        match j {
+            Json::Array(v) => 1 + v.iter().map(depth).max().unwrap_or(0),
+            Json::Object(m) => 1 + m.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    let o = SerializeOpts::default().set_compact(true);
+    for p in Profile::ALL {
+        for bytes in [SIZED_MIN, SIZED_MIN + 1, 100, 1000, 4097, 100_000, 1 << 20] {
+            let j = make_sized_seeded(bytes, bytes as u64, p);
+            let v = j.serialize_to_vec(&o);
+            assert_eq!(v.len(), bytes, "{:?}", p);
+            assert_eq!(Json::from_slice(&v).unwrap(), j);
+
+            assert!(match (p, &j) {
+                (Profile::Wide, Json::Object(m)) => depth(&j) == 1 && (bytes < 1000 || m.len() > bytes / 50),
+                (Profile::Deep, _) => depth(&j) == ((bytes - 8) / SIZED_MIN).clamp(1, DEEP_LEVELS) + 1,
+                (Profile::Numbers, Json::Array(v)) => v[.. v.len() - 1].iter().all(|x| matches!(x, Json::Number(_))),
+                (Profile::Strings, Json::Array(v)) => v.iter().all(|x| matches!(x, Json::String(_))),
+                _ => false,
+            }, "{:?}: {}", p, bytes);
+        }
+        assert!(make_sized_seeded(10, 1, p).serialize_to_vec(&o).len() <= 10);
+    }
+}
+
+#[test]
+fn test_write_corpus_reproducible() {
+    let base = std::env::temp_dir().join(format!("jsosso-corpus-test-{}", std::process::id()));
+    let a = write_corpus(base.join("a"), &[1000, 5000], &Profile::ALL, 7).unwrap();
+    let b = write_corpus(base.join("b"), &[1000, 5000], &Profile::ALL, 7).unwrap();
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert_eq!(std::fs::read(x).unwrap(), std::fs::read(y).unwrap(), "{:?}", x);
+    }
+    std::fs::remove_dir_all(&base).unwrap();
+
+    let j = json!({"b": 1, "a": {"d": [], "c": null}, "": true});
+    let o = SerializeOpts::default().set_sorted_keys(true);
+    assert_eq!(j.serialize_to_vec(&o.set_compact(true)), br#"{"":true,"a":{"c":null,"d":[]},"b":1}"#);
+    assert_eq!(Json::from_slice(&j.serialize_to_vec(&o)).unwrap(), j);
+}
+
 #[test]
 fn test_pass_round_trip() {
 
@@ -808,3 +1399,2067 @@ fn test_dsl_object() {
         ))
     );
 }
+
+#[test]
+fn test_dsl_negative_and_expressions() {
+    let x = 2.5;
+    let v = [1, 2, 3];
+    assert_eq!(json!(-1), Json::Number(-1.0));
+    assert_eq!(
+        json!([-1, -x, x * 2.0, v.len(), v[0] + v[1], "a".repeat(2), i64::MIN]),
+        Json::Array(vec![
+            Json::Number(-1.0),
+            Json::Number(-2.5),
+            Json::Number(5.0),
+            Json::Number(3.0),
+            Json::Number(3.0),
+            Json::String("aa".to_string()),
+            Json::Number(i64::MIN as f64),
+        ])
+    );
+    assert_eq!(json!([[1, 2].len(), {"a": 1}]), Json::Array(vec![Json::Number(2.0), json!({"a": 1})]));
+    assert_eq!(json!({"a": -1e3, "b": if x > 0.0 { "pos" } else { "neg" }}), json!({"a": (-1000), "b": "pos"}));
+}
+
+#[test]
+fn test_dsl_keys() {
+    let k = "key";
+    let n = 7;
+    assert_eq!(
+        json!({
+            k: 1,
+            format!("{}-{}", k, n): 2,
+            n: 3,
+            std::f64::consts::PI.floor(): 4,
+        }),
+        Json::Object(Box::new(vec![
+            ("key".to_string(), Json::Number(1.0)),
+            ("key-7".to_string(), Json::Number(2.0)),
+            ("7".to_string(), Json::Number(3.0)),
+            ("3".to_string(), Json::Number(4.0)),
+            ].into_iter().collect()
+        ))
+    );
+}
+
+#[test]
+fn test_dsl_trailing_commas_and_nesting() {
+    assert_eq!(json!([]), Json::Array(Vec::new()));
+    assert_eq!(json!({}), Json::Object(Box::default()));
+    assert_eq!(json!([1,]), Json::Array(vec![Json::Number(1.0)]));
+    assert_eq!(json!({"a": 1,}), json!({"a": 1}));
+    assert_eq!(
+        json!({
+            "a": [null, true, false, [null], {"b": null,},],
+            "c": {"d": [true, {"e": false}], "f": null},
+            "g": null,
+        }),
+        Json::Object(Box::new(vec![
+            ("a".to_string(), Json::Array(vec![
+                Json::Null, Json::Boolean(true), Json::Boolean(false),
+                Json::Array(vec![Json::Null]),
+                Json::Object(Box::new(vec![("b".to_string(), Json::Null)].into_iter().collect())),
+            ])),
+            ("c".to_string(), Json::Object(Box::new(vec![
+                ("d".to_string(), Json::Array(vec![
+                    Json::Boolean(true),
+                    Json::Object(Box::new(vec![("e".to_string(), Json::Boolean(false))].into_iter().collect())),
+                ])),
+                ("f".to_string(), Json::Null),
+                ].into_iter().collect()))),
+            ("g".to_string(), Json::Null),
+            ].into_iter().collect()
+        ))
+    );
+}
+
+#[test]
+fn test_dsl_splice() {
+    let a = json!([2, 3]);
+    let o = json!({"b": 2, "c": 3});
+    assert_eq!(json!([..a.clone()]), a);
+    assert_eq!(json!([1, ..a.clone(), 4, ..json!([]),]), json!([1, 2, 3, 4]));
+    assert_eq!(json!([..a.clone(), ..a.clone()]), json!([2, 3, 2, 3]));
+    assert_eq!(json!({"a": 1, ..o.clone(), "d": 4,}), json!({"a": 1, "b": 2, "c": 3, "d": 4}));
+    // later members win
+    assert_eq!(json!({"b": 1, ..o.clone()}), o);
+    assert_eq!(json!({..o.clone(), "b": 1}), json!({"b": 1, "c": 3}));
+    // nested
+    assert_eq!(json!({"x": [0, ..a.clone()], "y": {..o}}), json!({"x": [0, 2, 3], "y": {"b": 2, "c": 3}}));
+
+    assert!(std::panic::catch_unwind(|| json!([..json!({"a": 1})])).is_err());
+    assert!(std::panic::catch_unwind(|| json!({..json!([1])})).is_err());
+}
+
+#[test]
+fn test_match_literals_and_types() {
+    let j = json!([null, true, -1.5, "ok", 42]);
+    assert_eq!(json_match!(j, [null, true, -1.5, "ok", 42] => 1), Some(1));
+    assert_eq!(json_match!(j, [null, false, ..] => 1), None);
+    assert_eq!(json_match!(j, [_, _, -1, ..] => 1), None);
+    assert_eq!(json_match!(j, [_, _, _, "no", ..] => 1), None);
+    assert_eq!(json_match!(j, [n, b: bool, x: f64, s: &str, i: u8] => (n.clone(), b, x, s.to_string(), i)),
+               Some((Json::Null, true, -1.5, "ok".to_string(), 42)));
+    assert_eq!(json_match!(j, [_, _, x: i32, ..] => x), None);
+    assert_eq!(json_match!(j, [_, _, _, _, i: i8] => i), Some(42));
+    assert_eq!(json_match!(j, [_, _, _, s: String, ..] => s), Some("ok".to_string()));
+    assert_eq!(json_match!(json!(256), i: u8 => i), None);
+    assert_eq!(json_match!(json!(-1), i: u64 => i), None);
+    assert_eq!(json_match!(json!(-1), i: i64 => i), Some(-1));
+    assert_eq!(json_match!(json!(1e20), i: i64 => i), None);
+    assert_eq!(json_match!(json!(null), null => 1), Some(1));
+    assert_eq!(json_match!(json!(false), null => 1), None);
+}
+
+#[test]
+fn test_match_arrays_and_objects() {
+    let j = json!({"id": 7, "name": "x", "tags": ["a", "b", "c"], "pos": {"x": 1, "y": 2}, "odd key": true});
+    assert_eq!(json_match!(j, {id: u32, name: &str} => (id, name)), Some((7, "x")));
+    assert_eq!(json_match!(j, {"missing": _} => 1), None);
+    assert_eq!(json_match!(j, {"tags": [_, _]} => 1), None);
+    assert_eq!(json_match!(j, {"tags": [_, _, _, _, ..]} => 1), None);
+    assert_eq!(json_match!(j, {"tags": [first, ..rest]} => (first.clone(), rest.to_vec())),
+               Some((json!("a"), vec![json!("b"), json!("c")])));
+    assert_eq!(json_match!(j, {"tags": [_, _, _, ..rest]} => rest.len()), Some(0));
+    assert_eq!(json_match!(j, {"pos": {"x": x: f64, "y": y: f64}, "odd key": true} => x + y), Some(3.0));
+    assert_eq!(json_match!(j, {"pos": [..]} => 1), None);
+    assert_eq!(json_match!(j, {} => 1), Some(1));
+    assert_eq!(json_match!(j, [..] => 1), None);
+    assert_eq!(json_match!(json!([]), [] => 1), Some(1));
+    assert_eq!(json_match!(json!({"m": {"a": 1}}), {m: &HashMap<String, Json>} => m.len()), Some(1));
+
+    // the first matching arm wins
+    let describe = |j: &Json| json_match!(j,
+        {"type": "point", "at": [x: f64, y: f64]} => format!("point {} {}", x, y),
+        {"type": t: &str} => format!("other {}", t),
+        [_, ..] => "array".to_string(),
+        other => format!("value {}", other),
+    ).unwrap();
+    assert_eq!(describe(&json!({"type": "point", "at": [1, 2]})), "point 1 2");
+    assert_eq!(describe(&json!({"type": "point", "at": [1, 2, 3]})), "other point");
+    assert_eq!(describe(&json!([1])), "array");
+    assert_eq!(describe(&json!([])), "value []");
+
+    // nested matches
+    assert_eq!(json_match!(j, {tags} => json_match!(tags, [a: &str, ..] => a).unwrap()), Some("a"));
+}
+
+#[test]
+fn test_let() {
+    fn get(j: &Json) -> Result<(u32, String), error::MatchError> {
+        json_let!({"id": id: u32, "items": [{"name": name: String}, ..]} = j else |e| {
+            return Err(e);
+        });
+        Ok((id, name))
+    }
+
+    assert_eq!(get(&json!({"id": 1, "items": [{"name": "a"}]})), Ok((1, "a".to_string())));
+    for (j, path, message) in [
+        (json!([]), "$", "expected an object, found an array of 0 elements"),
+        (json!({"id": 1.5}), "$.id", "expected an integer of type u32, found 1.5"),
+        (json!({"id": 1}), "$", "missing member \"items\""),
+        (json!({"id": 1, "items": []}), "$.items", "expected at least 1 elements, found 0"),
+        (json!({"id": 1, "items": [{"name": null}]}), "$.items[0].name", "expected a string, found null"),
+    ] {
+        let e = get(&j).unwrap_err();
+        assert_eq!(e.path(), path);
+        assert_eq!(e.message(), message);
+    }
+
+    let mut path = String::new();
+    error::MatchError::push_key(&mut path, "a b");
+    error::MatchError::push_index(&mut path, 3);
+    error::MatchError::push_key(&mut path, "c_1");
+    assert_eq!(path, "[\"a b\"][3].c_1");
+
+    let e = json_match!(json!({"a b": [1, 2]}), x => x.clone()).unwrap();
+    let r: Result<(), error::MatchError> = (|| {
+        json_let!({"a b": [_, "two"]} = e else |e| { return Err(e) });
+        Ok(())
+    })();
+    assert_eq!(r.unwrap_err().to_string(), "Json does not match at $[\"a b\"][1]: expected \"two\", found 2");
+
+    // the value may be a temporary; else blocks may continue loops
+    let mut sum = 0.0;
+    for s in ["[1, 2]", "[3]", "{}", "[4, 5]"] {
+        json_let!([a: f64, b: f64] = Json::from_str(s).unwrap() else { continue });
+        sum += a + b;
+    }
+    assert_eq!(sum, 12.0);
+}
+
+#[test]
+fn test_builder() {
+    assert_eq!(Json::object().build(), json!({}));
+    assert_eq!(Json::array().build(), json!([]));
+
+    let tags: Vec<&str> = vec!["a", "b"];
+    let missing: Option<&str> = None;
+    let j = Json::object()
+            .insert("id", 7u8)
+            .insert(String::from("name"), String::from("x"))
+            .insert("id", -1)
+            .insert_if(true, "yes", 1.5)
+            .insert_if(false, "no", 1.5)
+            .insert_opt("some", Some(false))
+            .insert_opt("none", missing)
+            .insert("nested", Json::object().insert("a", Json::array().push(1)))
+            .push_array("tags", |a| a.extend(tags.iter().copied()).push_if(tags.is_empty(), "none"))
+            .push_object("empty", |o| o)
+            .build();
+    assert_eq!(j, json!({
+        "id": (-1),
+        "name": "x",
+        "yes": 1.5,
+        "some": false,
+        "nested": {"a": [1]},
+        "tags": ["a", "b"],
+        "empty": {}
+    }));
+
+    let j = Json::array()
+            .push(Json::Null)
+            .push_if(true, "t")
+            .push_if(false, "f")
+            .push_opt(Some(1))
+            .push_opt(None::<i32>)
+            .extend(Vec::<Json>::new())
+            .extend([json!([1]), json!({"a": null})])
+            .push_array(|a| a.push_array(|a| a))
+            .push_object(|o| o.insert_opt("x", Some(Json::array())))
+            .build();
+    assert_eq!(j, json!([null, "t", 1, [1], {"a": null}, [[]], {"x": []}]));
+}
+
+#[test]
+fn test_fail_complete_double_closed_array() {
+    let v: Vec<u8> = r#"
+        ["comma after array"]]"#
+        .to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse_complete(&mut s) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) if e.is_error_type("trailing characters") => true,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_fail_complete_garbage() {
+    let v: Vec<u8> = r#"{} garbage"#
+        .to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse_complete(&mut s) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) if e.is_error_type("trailing characters") => true,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+
+    // the offending character is shown as such
+    assert!(Json::from_slice(b"[1] 0").unwrap_err().to_string().contains("trailing characters after Json value: '0'"));
+    assert!(cbor::from_slice(b"\x01\x30").unwrap_err().to_string().contains("trailing bytes after value: 0x30"));
+}
+
+#[test]
+fn test_pass_complete_trailing_whitespace() {
+    let v: Vec<u8> = "  [1, 2, 3] \n\t \r\n"
+        .to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse_complete(&mut s) {
+        Ok(Json::Array(v)) if v.len() == 3 => true,
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_pass_many() {
+    let v: Vec<u8> = r#"{"a": 1}[true, false]null "hello" 42
+        {}  "#
+        .to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    let vs = match parse_many(&mut s).collect::<ParseResult<Vec<Json>>>() {
+        Ok(vs) => vs,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    };
+
+    assert!(match vs[..] {
+        [Json::Object(_),
+         Json::Array(_),
+         Json::Null,
+         Json::String(ref a),
+         Json::Number(b),
+         Json::Object(_)] if a == "hello" && approx_eq(b, 42.0) => true,
+        _ => panic!("unexpected value {:?}", vs),
+    });
+}
+
+#[test]
+fn test_fail_many_stops_on_error() {
+    let v: Vec<u8> = r#"[1] [2,] [3]"#
+        .to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    let vs: Vec<ParseResult<Json>> = parse_many(&mut s).collect();
+
+    assert_eq!(vs.len(), 2);
+    assert!(vs[0].is_ok());
+    assert!(match &vs[1] {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) if e.is_expected_token() => true,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_pass_many_empty() {
+    let v: Vec<u8> = "  \n ".to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert_eq!(parse_many(&mut s).count(), 0);
+}
+
+#[test]
+fn test_ndjson_reader() {
+    let v: Vec<u8> = "{\"id\": 1, \"ok\": true}\r\n  \n[1, 2, 3]\n\n\"last line without linebreak\""
+        .to_string().bytes().collect();
+
+    let vs = match Reader::new(Cursor::new(v)).collect::<Result<Vec<Json>, LineError>>() {
+        Ok(vs) => vs,
+        Err(e) => panic!("unexpected error: {}", e),
+    };
+
+    assert!(match vs[..] {
+        [Json::Object(_),
+         Json::Array(_),
+         Json::String(ref s)] if s == "last line without linebreak" => true,
+        _ => panic!("unexpected value {:?}", vs),
+    });
+}
+
+#[test]
+fn test_ndjson_reader_continues_after_error() {
+    let v: Vec<u8> = "[1]\n[2,]\n{} {}\n\n[4]\n"
+        .to_string().bytes().collect();
+
+    let mut r = Reader::new(Cursor::new(v));
+
+    assert!(matches!(r.next(), Some(Ok(Json::Array(_)))));
+    assert!(match r.next() {
+        Some(Err(e)) if e.line == 2 && e.error.is_expected_token() => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(match r.next() {
+        Some(Err(e)) if e.line == 3 && e.error.is_error_type("trailing characters") => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(matches!(r.next(), Some(Ok(Json::Array(_)))));
+    assert_eq!(r.line(), 5);
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn test_ndjson_round_trip() {
+    let mut originals = Vec::new();
+    for _ in 0 .. 10 {
+        originals.push(make_n_arbitrary(20));
+    }
+
+    let mut w = Writer::new(Vec::new());
+    match w.write_all(&originals) {
+        Ok(_) => (),
+        Err(e) => panic!("unexpected error serializing: {:?}", e),
+    }
+    let v = w.into_inner().unwrap();
+
+    assert_eq!(v.iter().filter(|b| **b == b'\n').count(), originals.len());
+
+    let copies = match Reader::new(Cursor::new(v)).collect::<Result<Vec<Json>, LineError>>() {
+        Ok(vs) => vs,
+        Err(e) => panic!("unexpected error: {}", e),
+    };
+
+    assert_eq!(originals, copies);
+}
+
+// counts the calls of flush
+struct FlushCounter {
+    v: Vec<u8>,
+    flushes: usize,
+}
+
+impl std::io::Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.v.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_ndjson_writer_flush() {
+    let mut w = Writer::new(FlushCounter { v: Vec::new(), flushes: 0 });
+    w.write_all(&[json!(1), json!([2]), json!({"a": 3})]).unwrap();
+    w.write(&json!(null)).unwrap();
+    w.flush().unwrap();
+    w.write(&json!("x")).unwrap();
+    let c = w.into_inner().unwrap();
+    assert_eq!(c.flushes, 2);
+    assert_eq!(c.v, b"1\n[2]\n{\"a\":3}\nnull\n\"x\"\n");
+}
+
+#[test]
+fn test_jsonseq_reader() {
+    let v: Vec<u8> = b"\x1e{\"id\": 1}\n\x1e\x1e[1, 2, 3]\n\x1e42\n\x1enull \x1e\"no linebreak\""
+        .to_vec();
+
+    let vs = match SeqReader::new(Cursor::new(v)).collect::<Result<Vec<Json>, RecordError>>() {
+        Ok(vs) => vs,
+        Err(e) => panic!("unexpected error: {}", e),
+    };
+
+    assert!(match vs[..] {
+        [Json::Object(_),
+         Json::Array(_),
+         Json::Number(n),
+         Json::Null,
+         Json::String(ref s)] if approx_eq(n, 42.0) && s == "no linebreak" => true,
+        _ => panic!("unexpected value {:?}", vs),
+    });
+}
+
+#[test]
+fn test_jsonseq_reader_recovers_from_truncation() {
+    let v: Vec<u8> = b"garbage\x1e{\"id\": \x1e[1]\n\x1e12\x1etru\x1e{} {}\n\x1e[4]\n"
+        .to_vec();
+
+    let mut r = SeqReader::new(Cursor::new(v));
+
+    assert!(match r.next() {
+        Some(Err(e)) if e.record == 0 && e.error.is_error_type("record separator") => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(match r.next() {
+        Some(Err(e)) if e.record == 1 && e.truncated => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(matches!(r.next(), Some(Ok(Json::Array(_)))));
+    assert!(match r.next() {
+        Some(Err(e)) if e.record == 3 && e.truncated &&
+                        e.error.is_error_type("truncated record") => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(match r.next() {
+        Some(Err(e)) if e.record == 4 && e.truncated => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(match r.next() {
+        Some(Err(e)) if e.record == 5 && !e.truncated &&
+                        e.error.is_error_type("trailing characters") => true,
+        x => panic!("unexpected result: {:?}", x),
+    });
+    assert!(matches!(r.next(), Some(Ok(Json::Array(_)))));
+    assert_eq!(r.record(), 6);
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn test_jsonseq_round_trip() {
+    let mut originals = Vec::new();
+    for _ in 0 .. 10 {
+        originals.push(make_n_arbitrary(20));
+    }
+
+    let mut w = SeqWriter::new(Vec::new());
+    match w.write_all(&originals) {
+        Ok(_) => (),
+        Err(e) => panic!("unexpected error serializing: {:?}", e),
+    }
+    let v = w.into_inner();
+
+    assert_eq!(v.iter().filter(|b| **b == RS).count(), originals.len());
+
+    let copies = match SeqReader::new(Cursor::new(v)).collect::<Result<Vec<Json>, RecordError>>() {
+        Ok(vs) => vs,
+        Err(e) => panic!("unexpected error: {}", e),
+    };
+
+    assert_eq!(originals, copies);
+}
+
+// the input ends one byte before the end of the first buffer
+#[test]
+fn test_pass_complete_at_buffer_boundary() {
+    let v: Vec<u8> = "\"four\"\n".to_string().bytes().collect();
+    let mut input = EofGuard::new(Cursor::new(v));
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse_complete(&mut s) {
+        Ok(Json::String(x)) if x == "four" => true,
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+// stale bytes beyond the end of the input must not be taken for data,
+// wherever the input ends relative to the buffers
+#[test]
+fn test_pass_many_at_any_buffer_alignment() {
+    let docs = ["[1, 2]", "{\"a\": 1}", "\"ab\"", "true", "12"];
+    for size in 8 .. 12 {
+        for lead in 0 .. 3 * size {
+            for trail in 0 .. 3 * size {
+                for d in docs {
+                    let j = Json::from_str(d).unwrap();
+                    let v = format!("{}{}{}", " ".repeat(lead), d, " ".repeat(trail)).into_bytes();
+
+                    let mut input = EofGuard::new(Cursor::new(v.clone()));
+                    let mut s = Stream::new(Opts::default()
+                               .set_buf_size(size)
+                               .set_buf_num(3),
+                               &mut input);
+                    let vs: Vec<ParseResult<Json>> = parse_many(&mut s).collect();
+                    assert!(matches!(vs[..], [Ok(ref x)] if *x == j),
+                            "{:?} with {} + {} spaces and buffer size {}", vs, lead, trail, size);
+
+                    let mut input = EofGuard::new(Cursor::new(v));
+                    let mut s = Stream::new(Opts::default()
+                               .set_buf_size(size)
+                               .set_buf_num(3),
+                               &mut input);
+                    assert!(matches!(parse_complete(&mut s), Ok(ref x) if *x == j),
+                            "{} + {} spaces and buffer size {}", lead, trail, size);
+                }
+            }
+        }
+    }
+}
+
+// a number ending the input is not extended by stale bytes
+#[test]
+fn test_pass_number_at_any_buffer_alignment() {
+    for n in 1 .. 50 {
+        let v = "1".repeat(n);
+        let mut input = EofGuard::new(Cursor::new(v.clone().into_bytes()));
+        let mut s = Stream::new(Opts::default()
+                   .set_buf_size(8)
+                   .set_buf_num(3),
+                   &mut input);
+        assert!(match parse_complete(&mut s) {
+            Ok(Json::Number(x)) => x == v.parse::<f64>().unwrap(),
+            Ok(v) => panic!("unexpected value: {:?}", v),
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }, "{} digits", n);
+    }
+}
+
+// the buffers of in-memory input never end one byte short
+#[test]
+fn test_from_slice_at_any_length() {
+    // around the smallest and the largest buffer size
+    for lead in (0 .. 16).chain(8150 .. 8200) {
+        let v = format!("{}[\"a\", [2], {{\"b\": 3}}]", " ".repeat(lead)).into_bytes();
+        assert_eq!(Json::from_slice(&v).unwrap(), json!(["a", [2], {"b": 3}]), "{} spaces", lead);
+        assert_eq!(Json::from_reader(&v[..]).unwrap(), json!(["a", [2], {"b": 3}]), "{} spaces", lead);
+        assert!(Json::from_slice(&v[.. v.len() - 1]).unwrap_err().is_eof(), "{} spaces", lead);
+        assert!(Json::from_reader(&v[.. v.len() - 1]).unwrap_err().is_eof(), "{} spaces", lead);
+    }
+    // seven bytes would end one byte short of the smallest buffer
+    assert_eq!(Json::from_slice(b"[1, 22]").unwrap(), json!([1, 22]));
+    assert!(Json::from_slice(b"[1, 22 ").unwrap_err().is_eof());
+}
+
+// a truncated document is not completed by stale bytes
+#[test]
+fn test_fail_truncated_at_any_buffer_alignment() {
+    for lead in 0 .. 48 {
+        let v = format!("{}[\"a\", [2], {{\"b\": 3}}] [1, 2", " ".repeat(lead)).into_bytes();
+        let mut input = EofGuard::new(Cursor::new(v));
+        let mut s = Stream::new(Opts::default()
+                   .set_buf_size(8)
+                   .set_buf_num(3),
+                   &mut input);
+        let vs: Vec<ParseResult<Json>> = parse_many(&mut s).collect();
+        assert!(matches!(vs[..], [Ok(_), Err(ref e)] if e.is_eof()), "{:?} with {} spaces", vs, lead);
+    }
+}
+
+#[test]
+fn test_pass_utf8_bom() {
+    let v: Vec<u8> = b"\xef\xbb\xbf {\"bom\": true}".to_vec();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse_complete(&mut s) {
+        Ok(Json::Object(_)) => true,
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_fail_invalid_utf8() {
+    let v: Vec<u8> = b"[\"caf\xe9\"]".to_vec();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse(&mut s) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) if e.is_utf8_error() => true,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_pass_invalid_utf8_lossy() {
+    let v: Vec<u8> = b"[\"caf\xe9\", \"\xf0\x9d\x84\"]".to_vec();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    let o = ParseOpts::default().set_invalid_utf8(Utf8Policy::Lossy);
+    assert!(match parse_with_opts(&mut s, &o) {
+        Ok(Json::Array(v)) => v == vec![Json::from("caf\u{fffd}"), Json::from("\u{fffd}")],
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_fail_lone_surrogate_strict() {
+    let v: Vec<u8> = r#""mus\udd1eic""#.to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    let o = ParseOpts::default().set_utf8_policy(Utf8Policy::Strict);
+    assert!(match parse_with_opts(&mut s, &o) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) if e.is_error_type("unicode error") => true,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_pass_lone_surrogate_preserve_round_trip() {
+    let t = r#"["mus\udd1eic",{"\ud834":"\udbff\ud834\udd1e\\n"}]"#;
+    let mut input = Cursor::new(t.as_bytes().to_vec());
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    let p = ParseOpts::default().set_utf8_policy(Utf8Policy::Preserve);
+    let j = match parse_with_opts(&mut s, &p) {
+        Ok(j) => j,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    };
+    assert_eq!(j, json!(["mus\\udd1eic", {"\\ud834": "\\udbff\u{1d11e}\\n"}]));
+
+    let o = SerializeOpts::default().set_compact(true).set_surrogate_escapes(true);
+    let mut v = Vec::new();
+    j.serialize_with_opts(&mut v, &o).unwrap();
+    assert_eq!(String::from_utf8(v).unwrap(), t.replace("\\ud834\\udd1e", "\u{1d11e}"));
+
+    // surrogates encoded as in WTF-8 are preserved, other invalid bytes are not
+    let mut input = Cursor::new(b"\"caf\xed\xa0\x80\"".to_vec());
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert_eq!(parse_with_opts(&mut s, &p).unwrap(), Json::from("caf\\ud800"));
+
+    let mut input = Cursor::new(b"\"caf\xe9\"".to_vec());
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert!(match parse_with_opts(&mut s, &p) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => e.is_error_type("utf8 error"),
+    });
+
+    // transcoded input
+    let mut v = encode_utf16("\"ab", true);
+    v.extend_from_slice(&[0x34, 0xd8]); // high surrogate
+    v.extend_from_slice(&encode_utf16("c\"", true));
+    let mut input = Decoder::new(Cursor::new(v), Utf8Policy::Preserve);
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert_eq!(parse_with_opts(&mut s, &p).unwrap(), Json::from("ab\\ud834c"));
+}
+
+#[test]
+fn test_pass_high_surrogate_before_escape() {
+    let v: Vec<u8> = r#""\ud834A\ud834𝄞""#.to_string().bytes().collect();
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default()
+               .set_buf_size(8)
+               .set_buf_num(3),
+               &mut input);
+
+    assert!(match parse(&mut s) {
+        Ok(Json::String(s)) => s == "\u{fffd}A\u{fffd}\u{1d11e}",
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+fn encode_utf16(t: &str, le: bool) -> Vec<u8> {
+    t.encode_utf16().flat_map(|u| if le {
+        u.to_le_bytes()
+    } else {
+        u.to_be_bytes()
+    }).collect()
+}
+
+fn encode_utf32(t: &str, le: bool) -> Vec<u8> {
+    t.chars().flat_map(|c| if le {
+        (c as u32).to_le_bytes()
+    } else {
+        (c as u32).to_be_bytes()
+    }).collect()
+}
+
+#[test]
+fn test_pass_transcoded_input() {
+    let t = r#"{"clef": "𝄞", "café": [1, 2.5, null]}"#;
+    let expected = json!({"clef": "𝄞", "café": [1, 2.5, null]});
+
+    let inputs = vec![
+        (encode_utf16(t, true), Encoding::Utf16Le),
+        (encode_utf16(t, false), Encoding::Utf16Be),
+        (encode_utf16(&format!("\u{feff}{}", t), true), Encoding::Utf16Le),
+        (encode_utf16(&format!("\u{feff}{}", t), false), Encoding::Utf16Be),
+        (encode_utf32(t, true), Encoding::Utf32Le),
+        (encode_utf32(t, false), Encoding::Utf32Be),
+        (encode_utf32(&format!("\u{feff}{}", t), true), Encoding::Utf32Le),
+        (encode_utf32(&format!("\u{feff}{}", t), false), Encoding::Utf32Be),
+        (format!("\u{feff}{}", t).into_bytes(), Encoding::Utf8),
+    ];
+
+    for (v, e) in inputs {
+        let mut input = Decoder::new(Cursor::new(v), Utf8Policy::Strict);
+        let j = {
+            let mut s = Stream::new(Opts::default()
+                       .set_buf_size(8)
+                       .set_buf_num(3),
+                       &mut input);
+            match parse_complete(&mut s) {
+                Ok(j) => j,
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+        };
+        assert_eq!(input.encoding(), Some(e));
+        assert_eq!(j, expected);
+    }
+}
+
+#[test]
+fn test_pass_transcoded_from_slice_reader_and_file() {
+    let t = "\u{feff} [\"\u{1d11e} h\u{e9}llo\", 1] ";
+    let expected = json!(["\u{1d11e} h\u{e9}llo", 1]);
+    let path = std::env::temp_dir().join(format!("jsosso-transcoded-{}.json", std::process::id()));
+
+    let inputs = [
+        encode_utf16(t, true),
+        encode_utf16(&t[3 ..], false),
+        encode_utf32(t, false),
+        encode_utf32(&t[3 ..], true),
+        t.as_bytes().to_vec(),
+    ];
+    for v in inputs {
+        assert_eq!(Json::from_slice(&v).unwrap(), expected);
+        assert_eq!(Json::from_reader(Cursor::new(v.clone())).unwrap(), expected);
+        std::fs::write(&path, &v).unwrap();
+        assert_eq!(Json::from_file(&path).unwrap(), expected);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // an unpaired surrogate is an error
+    let mut v = encode_utf16("[\"a", true);
+    v.extend_from_slice(&[0x34, 0xd8]);
+    v.extend_from_slice(&encode_utf16("\"]", true));
+    assert!(Json::from_slice(&v).is_err());
+    assert!(Json::from_reader(Cursor::new(v)).is_err());
+}
+
+#[test]
+fn test_fail_transcoded_lone_surrogate() {
+    let mut v = encode_utf16("\"ab", true);
+    v.extend_from_slice(&[0x34, 0xd8]); // high surrogate
+    v.extend_from_slice(&encode_utf16("c\"", true));
+
+    let mut input = Decoder::new(Cursor::new(v.clone()), Utf8Policy::Strict);
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert!(match parse(&mut s) {
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => e.is_io_error(),
+    });
+
+    let mut input = Decoder::new(Cursor::new(v), Utf8Policy::Lossy);
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert!(match parse(&mut s) {
+        Ok(Json::String(s)) => s == "ab\u{fffd}c",
+        Ok(v) => panic!("unexpected value: {:?}", v),
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+#[test]
+fn test_pass_numbers_exact() {
+    let inputs = [
+        "0", "-0", "1", "-1", "0.1", "0.3", "1e23", "123456789012345678901234567890",
+        "1.7976931348623157e308", "2.2250738585072014e-308", "5e-324", "4.9e-324",
+        "9007199254740993", "0.1000000000000000055511151231257827",
+        "3.14159265358979323846264338327950288", "1e400", "-1e-400", "1E+2", "12.5e-1",
+    ];
+
+    for i in inputs {
+        let mut input = EofGuard::new(Cursor::new(i.as_bytes().to_vec()));
+        let mut s = Stream::new(Opts::default()
+                   .set_buf_size(8)
+                   .set_buf_num(3),
+                   &mut input);
+        let expected = i.parse::<f64>().unwrap();
+        assert!(match parse_complete(&mut s) {
+            Ok(Json::Number(n)) => n.to_bits() == expected.to_bits(),
+            Ok(v) => panic!("unexpected value: {:?}", v),
+            Err(e) => panic!("unexpected error: {:?} for {}", e, i),
+        }, "{}", i);
+    }
+
+    // random numbers compared with the standard library
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(30);
+    // n random digits without leading zero
+    let digits = |rng: &mut rand::rngs::StdRng, n: usize| -> String {
+        (0 .. n).map(|i| char::from(b'0' + rng.gen_range(if i == 0 && n > 1 { 1 } else { 0 } .. 10))).collect()
+    };
+    for i in 0 .. 4000 {
+        let t = match i % 4 {
+            // random digits
+            0 => {
+                let m = rng.gen_range(1 ..= 20);
+                let f = rng.gen_range(0 ..= 10);
+                let e: i32 = rng.gen_range(-30 ..= 30);
+                format!("{}.{}e{}", digits(&mut rng, m), digits(&mut rng, f), e).replace(".e", "e")
+            },
+            // long mantissas
+            1 => {
+                let m = rng.gen_range(20 ..= 800);
+                format!("{}e{}", digits(&mut rng, m), rng.gen_range(-400 ..= 0))
+            },
+            // subnormal and overflowing exponents
+            2 => {
+                let e = if rng.gen() {
+                    rng.gen_range(-345 ..= -300)
+                } else {
+                    rng.gen_range(290 ..= 320)
+                };
+                format!("{}.{}e{}", digits(&mut rng, 1), digits(&mut rng, 17), e)
+            },
+            // halfway between two neighbouring doubles, (2m + 1) * 2^k,
+            // exactly and a little above or below
+            _ => {
+                let m: u128 = rng.gen_range(1 << 52 .. 1 << 53);
+                let k: i32 = rng.gen_range(-31 ..= 73);
+                let (d, e) = if k < 0 {
+                    ((2 * m + 1) * 5u128.pow(-k as u32), k)
+                } else {
+                    ((2 * m + 1) << k, 0)
+                };
+                match rng.gen_range(0 .. 3) {
+                    0 => format!("{}e{}", d, e),
+                    1 => format!("{}0000001e{}", d, e - 7),
+                    _ => format!("{}9999999e{}", d - 1, e - 7),
+                }
+            },
+        };
+        let t = if rng.gen() { format!("-{}", t) } else { t };
+        let mut input = EofGuard::new(Cursor::new(t.as_bytes().to_vec()));
+        let mut s = Stream::new(Opts::default()
+                   .set_buf_size(8)
+                   .set_buf_num(3),
+                   &mut input);
+        let expected = t.parse::<f64>().unwrap();
+        assert!(match parse_complete(&mut s) {
+            Ok(Json::Number(n)) => n.to_bits() == expected.to_bits(),
+            Ok(v) => panic!("unexpected value: {:?}", v),
+            Err(e) => panic!("unexpected error: {:?} for {}", e, t),
+        }, "{}", t);
+    }
+}
+
+#[test]
+fn test_serialize_numbers_like_ecmascript() {
+    // expected values are the results of Number.prototype.toString
+    let inputs = [
+        (0.0, "0"), (-0.0, "0"), (1.0, "1"), (-1.0, "-1"), (0.1, "0.1"),
+        (0.1 + 0.2, "0.30000000000000004"), (100.0, "100"), (123.456, "123.456"),
+        (1e21, "1e+21"), (1e20, "100000000000000000000"), (123e20, "1.23e+22"),
+        (0.000001, "0.000001"), (0.0000001, "1e-7"), (1.5e-7, "1.5e-7"),
+        (5e-324, "5e-324"), (1.7976931348623157e308, "1.7976931348623157e+308"),
+        (9007199254740993.0, "9007199254740992"), (2.5, "2.5"), (-0.00123, "-0.00123"),
+        // ties between two shortest candidates go to the even one
+        (1461121389365645.0 / 8.0, "182640173670705.62"), (2981157573541921.0 / 4.0, "745289393385480.2"),
+        (f64::NAN, "null"), (f64::INFINITY, "null"), (f64::NEG_INFINITY, "null"),
+    ];
+
+    for (n, expected) in inputs {
+        let mut v: Vec<u8> = Vec::new();
+        assert!(match Json::Number(n).serialize(&mut v) {
+            Ok(l) => l == expected.len(),
+            Err(e) => panic!("unexpected error: {:?}", e),
+        });
+        assert_eq!(str::from_utf8(&v).unwrap(), expected);
+    }
+
+    let o = SerializeOpts::default().set_exponent_thresholds(0, 3);
+    let inputs = [(1.0, "1"), (999.0, "999"), (1000.0, "1e+3"), (0.5, "5e-1"), (1234.5, "1.2345e+3")];
+    for (n, expected) in inputs {
+        let mut v: Vec<u8> = Vec::new();
+        assert!(Json::Number(n).serialize_with_opts(&mut v, &o).is_ok());
+        assert_eq!(str::from_utf8(&v).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_serialize_numbers_round_trip() {
+    let mut x: u64 = 0x9e3779b97f4a7c15;
+    for _ in 0 .. 10000 {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let n = f64::from_bits(x);
+        if !n.is_finite() {
+            continue;
+        }
+        let mut v: Vec<u8> = Vec::new();
+        assert!(Json::Number(n).serialize(&mut v).is_ok());
+        let mut input = Cursor::new(v);
+        let mut s = Stream::new(Opts::default(), &mut input);
+        assert!(match parse_complete(&mut s) {
+            Ok(Json::Number(m)) => m == n,
+            Ok(v) => panic!("unexpected value: {:?}", v),
+            Err(e) => panic!("unexpected error: {:?}", e),
+        });
+    }
+}
+
+// counts the calls to write
+struct CountingWriter {
+    v: Vec<u8>,
+    writes: usize,
+}
+
+impl std::io::Write for CountingWriter {
+    fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.v.extend_from_slice(b);
+        Ok(b.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_serialize_buffered() {
+    let mut a = Vec::new();
+    for i in 0 .. 5000 {
+        a.push(json!({"id": i, "name": "x\ty\"z", "tags": [true, null, 1.5]}));
+    }
+    let jdoc = Json::Array(a);
+
+    for o in [SerializeOpts::default(), SerializeOpts::default().set_compact(true)] {
+        let mut w = CountingWriter { v: Vec::new(), writes: 0 };
+        let n = match jdoc.serialize_with_opts(&mut w, &o) {
+            Ok(n) => n,
+            Err(e) => panic!("unexpected error: {:?}", e),
+        };
+        assert_eq!(n, w.v.len());
+        assert!(w.writes <= n / 8192 + 1);
+        assert_eq!(w.v, jdoc.serialize_to_vec(&o));
+
+        let mut input = Cursor::new(w.v);
+        let mut s = Stream::new(Opts::default(), &mut input);
+        assert!(match parse_complete(&mut s) {
+            Ok(j) => j == jdoc,
+            Err(e) => panic!("unexpected error: {:?}", e),
+        });
+    }
+}
+
+#[test]
+fn test_serialize_control_characters() {
+    let jdoc = Json::String("a\u{1}b\u{1f}/\u{7f}\u{e9}".to_string());
+    assert_eq!(jdoc.to_string(), "\"a\\u0001b\\u001f\\/\u{7f}\u{e9}\"");
+
+    let mut input = Cursor::new(jdoc.to_string().into_bytes());
+    let mut s = Stream::new(Opts::default(), &mut input);
+    assert!(match parse_complete(&mut s) {
+        Ok(j) => j == jdoc,
+        Err(e) => panic!("unexpected error: {:?}", e),
+    });
+}
+
+// always fails
+struct BrokenReader;
+
+impl std::io::Read for BrokenReader {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("broken"))
+    }
+}
+
+#[test]
+fn test_convenience_entry_points() {
+    for f in ["rsc/test/pass1.json", "rsc/test/oeis.json", "rsc/test/arbitrary.json"] {
+        let j = match Json::from_file(f) {
+            Ok(j) => j,
+            Err(e) => panic!("unexpected error: {} in {}", e, f),
+        };
+        assert_eq!(j.to_string().parse::<Json>().unwrap(), j);
+        assert_eq!(Json::from_str(&j.to_string_pretty()).unwrap(), j);
+        let v = std::fs::read(f).unwrap();
+        assert_eq!(Json::from_slice(&v).unwrap(), j);
+        assert_eq!(Json::from_reader(&v[..]).unwrap(), j);
+    }
+
+    assert!(match Json::from_str("[1, 2") {
+        Err(e) => e.is_eof() && !e.is_io_error(),
+        Ok(v) => panic!("unexpected value: {:?}", v),
+    });
+    assert!(match "{} {}".parse::<Json>() {
+        Err(error::Error::Parse(e)) => e.is_error_type("trailing characters"),
+        r => panic!("unexpected result: {:?}", r),
+    });
+    assert!(match Json::from_reader(BrokenReader) {
+        Err(error::Error::Io(e)) => e.to_string() == "broken",
+        r => panic!("unexpected result: {:?}", r),
+    });
+}
+
+// feeds 'input' in chunks of 'size' bytes and collects the results
+fn push_all(p: &mut PushParser, input: &[u8], size: usize) -> Vec<error::Result<Json>> {
+    let mut rs = Vec::new();
+    for chunk in input.chunks(size) {
+        let mut bs = chunk;
+        while !bs.is_empty() {
+            let (n, r) = p.feed(bs);
+            assert!(n > 0 || r.is_some());
+            bs = &bs[n ..];
+            if let Some(r) = r {
+                rs.push(r);
+            }
+        }
+    }
+    if let Some(r) = p.finish().transpose() {
+        rs.push(r);
+    }
+    rs
+}
+
+#[test]
+fn test_push_parser() {
+    let input = "\u{feff}{\"a\": [1, {\"b\": \"]}\\\"\"}], \"c\": null} \"x\" -1.5e3 true\n[[]]{}42";
+    let expected = vec![
+        json!({"a": [1, {"b": "]}\""}], "c": null}),
+        json!("x"),
+        Json::from(-1.5e3),
+        json!(true),
+        json!([[]]),
+        json!({}),
+        json!(42),
+    ];
+    for size in 1 ..= input.len() {
+        let rs = push_all(&mut PushParser::new(), input.as_bytes(), size);
+        let vs: Vec<Json> = rs.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(vs, expected, "chunk size {}", size);
+    }
+
+    // the consumed bytes end with the value
+    let mut p = PushParser::new();
+    assert!(matches!(p.feed(b"  [1, 2"), (7, None)));
+    assert!(!p.is_idle());
+    let (n, r) = p.feed(b"]\x03next");
+    assert_eq!(n, 1);
+    assert_eq!(r.unwrap().unwrap(), json!([1, 2]));
+    assert!(p.is_idle());
+    let (n, r) = p.feed(b"12\x03");
+    assert_eq!(n, 2);
+    assert_eq!(r.unwrap().unwrap(), json!(12));
+
+    // the parser starts over after an invalid value
+    let rs = push_all(&mut PushParser::new(), b"[1, x] [2]", 3);
+    assert_eq!(rs.len(), 2);
+    assert!(match &rs[0] {
+        Err(e @ error::Error::Parse(_)) => !e.is_eof(),
+        r => panic!("unexpected result: {:?}", r),
+    });
+    assert_eq!(*rs[1].as_ref().unwrap(), json!([2]));
+
+    // incomplete values are reported at the end of the input
+    let mut p = PushParser::with_opts(ParseOpts::default());
+    assert!(matches!(p.feed(b"{\"a\": \"unterminated"), (19, None)));
+    assert!(p.finish().unwrap_err().is_eof());
+    assert!(p.is_idle());
+    assert!(p.finish().unwrap().is_none());
+}
+
+#[test]
+fn test_push_parser_max_size() {
+    let input = b"[1, 2] [1, 2, 3, 4, 5] \"abc\" 123456789 {\"a\": \"]]]]]]]]\"} 7";
+    for size in 1 ..= input.len() {
+        let mut p = PushParser::new().set_max_size(8);
+        let rs = push_all(&mut p, input, size);
+        assert_eq!(rs.len(), 6, "chunk size {}", size);
+        for (i, r) in rs.into_iter().enumerate() {
+            match (i, r) {
+                (0, Ok(j)) => assert_eq!(j, json!([1, 2])),
+                (2, Ok(j)) => assert_eq!(j, json!("abc")),
+                (5, Ok(j)) => assert_eq!(j, json!(7)),
+                (1 | 3 | 4, Err(error::Error::TooLarge(8))) => (),
+                (i, r) => panic!("unexpected result {}: {:?} (chunk size {})", i, r, size),
+            }
+        }
+        assert!(p.is_idle());
+    }
+
+    // the error is reported before the value ends
+    let mut p = PushParser::new().set_max_size(8);
+    let (n, r) = p.feed(b"[1, 2, 3, 4, 5");
+    assert_eq!(n, 9);
+    assert!(matches!(r, Some(Err(error::Error::TooLarge(8)))));
+    assert!(matches!(p.feed(b", 6, 7"), (6, None)));
+    assert!(!p.is_idle());
+    assert!(p.finish().unwrap().is_none());
+    assert!(p.is_idle());
+}
+
+#[test]
+fn test_cbor_encode() {
+    // examples from RFC 8949, appendix A
+    let cases: Vec<(Json, &[u8])> = vec![
+        (json!(0), b"\x00"),
+        (json!(23), b"\x17"),
+        (json!(24), b"\x18\x18"),
+        (json!(1000), b"\x19\x03\xe8"),
+        (json!(1000000), b"\x1a\x00\x0f\x42\x40"),
+        (json!(1000000000000u64), b"\x1b\x00\x00\x00\xe8\xd4\xa5\x10\x00"),
+        (json!(9223372036854775808u64), b"\x1b\x80\x00\x00\x00\x00\x00\x00\x00"),
+        (Json::from(-18446744073709551616.0), b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff"),
+        (Json::from(-1), b"\x20"),
+        (Json::from(-1000), b"\x39\x03\xe7"),
+        (json!(1.1), b"\xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a"),
+        (json!(1.5), b"\xf9\x3e\x00"),
+        (json!(65504.5), b"\xfa\x47\x7f\xe0\x80"),
+        (Json::from(f32::MAX), b"\xfa\x7f\x7f\xff\xff"),
+        (json!(1.0e+300), b"\xfb\x7e\x37\xe4\x3c\x88\x00\x75\x9c"),
+        (json!(5.960464477539063e-8), b"\xf9\x00\x01"),
+        (json!(0.00006103515625), b"\xf9\x04\x00"),
+        (Json::from(-4.1), b"\xfb\xc0\x10\x66\x66\x66\x66\x66\x66"),
+        (Json::from(-0.0), b"\xf9\x80\x00"),
+        (json!(0.5), b"\xf9\x38\x00"),
+        (Json::from(f64::INFINITY), b"\xf9\x7c\x00"),
+        (Json::from(f64::NEG_INFINITY), b"\xf9\xfc\x00"),
+        (Json::from(f64::NAN), b"\xf9\x7e\x00"),
+        (json!(false), b"\xf4"),
+        (json!(true), b"\xf5"),
+        (json!(null), b"\xf6"),
+        (json!(""), b"\x60"),
+        (json!("IETF"), b"\x64IETF"),
+        (json!("\u{00fc}"), b"\x62\xc3\xbc"),
+        (json!([]), b"\x80"),
+        (json!([1, [2, 3], [4, 5]]), b"\x83\x01\x82\x02\x03\x82\x04\x05"),
+        (json!({"a": 1}), b"\xa1\x61a\x01"),
+    ];
+    for (j, expected) in cases {
+        assert_eq!(cbor::to_vec(&j), expected, "{:?}", j);
+    }
+
+    // long arrays and strings use longer heads
+    let a = Json::Array((0 .. 25).map(Json::from).collect());
+    assert_eq!(cbor::to_vec(&a)[.. 3], *b"\x98\x19\x00");
+    let s = Json::from("x".repeat(256));
+    assert_eq!(cbor::to_vec(&s)[.. 4], *b"\x79\x01\x00x");
+
+    // deterministic encoding sorts the keys
+    let j = json!({"zz": [1], "b": {"y": 2, "x": 1}, "aaa": null, "a": true});
+    let o = CborOpts::default().set_deterministic(true);
+    let expected = b"\xa4\x61a\xf5\x61b\xa2\x61x\x01\x61y\x02\x62zz\x81\x01\x63aaa\xf6";
+    assert_eq!(cbor::to_vec_with_opts(&j, &o), expected);
+    let mut v = Vec::new();
+    assert_eq!(cbor::encode_with_opts(&j, &mut v, &o).unwrap(), expected.len());
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_cbor_decode() {
+    let cases: Vec<(&[u8], Json)> = vec![
+        (b"\x19\x03\xe8", json!(1000)),
+        (b"\x39\x03\xe7", Json::from(-1000)),
+        // beyond 2^53, -1 - n must not be rounded twice
+        (b"\x3b\x00\x22\x4d\xac\xd3\xf7\x9b\x89", Json::from(-9655553894095754.0)),
+        (b"\xf9\x7b\xff", json!(65504.0)),
+        (b"\xf9\x00\x01", json!(5.960464477539063e-8)),
+        (b"\xf9\xc4\x00", Json::from(-4.0)),
+        (b"\xfa\x47\xc3\x50\x00", json!(100000.0)),
+        (b"\xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a", json!(1.1)),
+        (b"\xf9\x7c\x00", Json::from(f64::INFINITY)),
+        (b"\x62\xc3\xbc", json!("\u{00fc}")),
+        // indefinite lengths
+        (b"\x7f\x65strea\x64ming\xff", json!("streaming")),
+        (b"\x9f\xff", json!([])),
+        (b"\x9f\x01\x82\x02\x03\x9f\x04\x05\xff\xff", json!([1, [2, 3], [4, 5]])),
+        (b"\x83\x01\x9f\x02\x03\xff\x82\x04\x05", json!([1, [2, 3], [4, 5]])),
+        (b"\xbf\x61a\x01\x61b\x9f\x02\x03\xff\xff", json!({"a": 1, "b": [2, 3]})),
+        (b"\xbf\x7f\x61a\xff\xf5\xff", json!({"a": true})),
+        // the self-described CBOR tag
+        (b"\xd9\xd9\xf7\x82\xf4\xf6", json!([false, null])),
+    ];
+    for (b, expected) in cases {
+        assert_eq!(cbor::from_slice(b).unwrap(), expected, "{:?}", b);
+    }
+    assert!(match cbor::from_slice(b"\xf9\x7e\x00") {
+        Ok(Json::Number(n)) => n.is_nan(),
+        r => panic!("unexpected result: {:?}", r),
+    });
+
+    let errors: Vec<(&[u8], &str)> = vec![
+        (b"\x43abc", "unsupported byte string"),
+        (b"\xc1\x1a\x51\x4b\x67\xb0", "unsupported tag 1"),
+        (b"\xa1\x01\x02", "unsupported map key"),
+        (b"\xa2\x61a\x01\x61a\x02", "duplicated key 'a' in map"),
+        (b"\xbf\x61a\x01\x61b\x02\x61a\x03\xff", "duplicated key 'a' in map"),
+        (b"\xf7", "unsupported simple value 23"),
+        (b"\xf8\xff", "unsupported simple value 255"),
+        (b"\xff", "unexpected break"),
+        (b"\x1c", "invalid additional information"),
+        (b"\x7f\x61a\x01\xff", "invalid chunk"),
+        (b"\x62\xc3\x28", "utf8 error"),
+        (b"\x01\x02", "trailing bytes"),
+    ];
+    for (b, msg) in errors {
+        assert!(match cbor::from_slice(b) {
+            Err(error::Error::Parse(e)) => e.is_error_type(msg),
+            r => panic!("unexpected result for {:?}: {:?}", b, r),
+        }, "{:?}", b);
+    }
+    assert!(cbor::from_slice(b"\x82\x01").unwrap_err().is_eof());
+    assert!(cbor::from_slice(b"\x9f\x01").unwrap_err().is_eof());
+    assert!(cbor::from_slice(b"\x64abc").unwrap_err().is_eof());
+
+    // a sequence of data items in a stream
+    let mut input = Cursor::new(b"\x01\x61a\x80".to_vec());
+    let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+    assert_eq!(cbor::decode(&mut s).unwrap(), json!(1));
+    assert_eq!(cbor::decode(&mut s).unwrap(), json!("a"));
+    assert_eq!(cbor::decode(&mut s).unwrap(), json!([]));
+    assert!(cbor::decode(&mut s).unwrap_err().is_eof());
+}
+
+#[test]
+fn test_cbor_round_trip() {
+    for f in ["rsc/test/pass1.json", "rsc/test/oeis.json", "rsc/test/arbitrary.json"] {
+        let j = Json::from_file(f).unwrap();
+        let v = cbor::to_vec(&j);
+        assert_eq!(cbor::from_slice(&v).unwrap(), j, "{}", f);
+        assert_eq!(cbor::from_reader(&v[..]).unwrap(), j, "{}", f);
+    }
+    for _ in 0 .. 10 {
+        let j = make_arbitrary();
+        assert_eq!(cbor::from_slice(&cbor::to_vec(&j)).unwrap(), j);
+    }
+
+    // strings longer than the stream buffers
+    let j = json!({"long": (&"0123456789".repeat(2000) as &str)});
+    let v = cbor::to_vec(&j);
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default().set_buf_size(16), &mut input);
+    assert_eq!(cbor::decode(&mut s).unwrap(), j);
+}
+
+#[test]
+fn test_msgpack_encode() {
+    let cases: Vec<(Json, &[u8])> = vec![
+        (json!(0), b"\x00"),
+        (json!(127), b"\x7f"),
+        (json!(128), b"\xcc\x80"),
+        (json!(256), b"\xcd\x01\x00"),
+        (json!(65536), b"\xce\x00\x01\x00\x00"),
+        (json!(4294967296u64), b"\xcf\x00\x00\x00\x01\x00\x00\x00\x00"),
+        (Json::from(-1), b"\xff"),
+        (Json::from(-32), b"\xe0"),
+        (Json::from(-33), b"\xd0\xdf"),
+        (Json::from(-129), b"\xd1\xff\x7f"),
+        (Json::from(-32769), b"\xd2\xff\xff\x7f\xff"),
+        (Json::from(-2147483649i64), b"\xd3\xff\xff\xff\xff\x7f\xff\xff\xff"),
+        (Json::from(-9223372036854775808.0), b"\xd3\x80\x00\x00\x00\x00\x00\x00\x00"),
+        (json!(0.5), b"\xca\x3f\x00\x00\x00"),
+        (json!(1.1), b"\xcb\x3f\xf1\x99\x99\x99\x99\x99\x9a"),
+        (Json::from(-0.0), b"\xca\x80\x00\x00\x00"),
+        (json!(1e19), b"\xcf\x8a\xc7\x23\x04\x89\xe8\x00\x00"),
+        (json!(1e20), b"\xcb\x44\x15\xaf\x1d\x78\xb5\x8c\x40"),
+        (Json::from(2f64.powi(70)), b"\xca\x62\x80\x00\x00"),
+        (json!(false), b"\xc2"),
+        (json!(true), b"\xc3"),
+        (json!(null), b"\xc0"),
+        (json!(""), b"\xa0"),
+        (json!("IETF"), b"\xa4IETF"),
+        (json!([]), b"\x90"),
+        (json!([1, [2, 3]]), b"\x92\x01\x92\x02\x03"),
+        (json!({"a": 1}), b"\x81\xa1a\x01"),
+    ];
+    for (j, expected) in cases {
+        assert_eq!(msgpack::to_vec(&j), expected, "{:?}", j);
+    }
+
+    // longer strings, arrays and maps use length fields
+    let s = Json::from("x".repeat(32));
+    assert_eq!(msgpack::to_vec(&s)[.. 3], *b"\xd9\x20x");
+    let s = Json::from("x".repeat(256));
+    assert_eq!(msgpack::to_vec(&s)[.. 4], *b"\xda\x01\x00x");
+    let s = Json::from("x".repeat(65536));
+    assert_eq!(msgpack::to_vec(&s)[.. 6], *b"\xdb\x00\x01\x00\x00x");
+    let a = Json::Array((0 .. 16).map(Json::from).collect());
+    assert_eq!(msgpack::to_vec(&a)[.. 4], *b"\xdc\x00\x10\x00");
+    let a = Json::Array((0 .. 65536).map(|_| Json::Null).collect());
+    assert_eq!(msgpack::to_vec(&a)[.. 6], *b"\xdd\x00\x01\x00\x00\xc0");
+    let m = Json::Object(Box::new((0 .. 16).map(|i| (i.to_string(), Json::Null)).collect()));
+    assert_eq!(msgpack::to_vec(&m)[.. 3], *b"\xde\x00\x10");
+
+    let mut v = Vec::new();
+    assert_eq!(msgpack::encode(&json!([1, 2]), &mut v).unwrap(), 3);
+    assert_eq!(v, b"\x92\x01\x02");
+}
+
+#[test]
+fn test_msgpack_decode() {
+    let cases: Vec<(&[u8], Json)> = vec![
+        (b"\xcc\xff", json!(255)),
+        (b"\xcd\xff\xff", json!(65535)),
+        (b"\xce\xff\xff\xff\xff", json!(4294967295u32)),
+        (b"\xcf\x00\x00\x00\x01\x00\x00\x00\x00", json!(4294967296u64)),
+        (b"\xd0\x80", Json::from(-128)),
+        (b"\xd1\x80\x00", Json::from(-32768)),
+        (b"\xd2\x80\x00\x00\x00", Json::from(-2147483648i64)),
+        (b"\xd3\xff\xff\xff\xff\xff\xff\xff\xff", Json::from(-1)),
+        (b"\xca\x3f\xc0\x00\x00", json!(1.5)),
+        (b"\xcb\x3f\xf1\x99\x99\x99\x99\x99\x9a", json!(1.1)),
+        (b"\xd9\x01a", json!("a")),
+        (b"\xda\x00\x01a", json!("a")),
+        (b"\xdb\x00\x00\x00\x01a", json!("a")),
+        (b"\xdc\x00\x01\xc3", json!([true])),
+        (b"\xdd\x00\x00\x00\x01\xc2", json!([false])),
+        (b"\xde\x00\x01\xa1a\xc0", json!({"a": null})),
+        (b"\xdf\x00\x00\x00\x01\xa1a\x90", json!({"a": []})),
+    ];
+    for (b, expected) in cases {
+        assert_eq!(msgpack::from_slice(b).unwrap(), expected, "{:?}", b);
+    }
+
+    let errors: Vec<(&[u8], &str)> = vec![
+        (b"\xc4\x01a", "unsupported binary data"),
+        (b"\xd4\x01\x00", "unsupported extension type 1"),
+        (b"\xc7\x01\xfe\x00", "unsupported extension type -2"),
+        (b"\x81\x01\x02", "unsupported map key"),
+        (b"\x82\xa1a\x01\xa1a\x02", "duplicated key 'a' in map"),
+        (b"\xc1", "invalid format"),
+        (b"\xa2\xc3\x28", "utf8 error"),
+        (b"\x01\x02", "trailing bytes"),
+    ];
+    for (b, msg) in errors {
+        assert!(match msgpack::from_slice(b) {
+            Err(error::Error::Parse(e)) => e.is_error_type(msg),
+            r => panic!("unexpected result for {:?}: {:?}", b, r),
+        }, "{:?}", b);
+    }
+    assert!(msgpack::from_slice(b"\x92\x01").unwrap_err().is_eof());
+    assert!(msgpack::from_slice(b"\xa3ab").unwrap_err().is_eof());
+
+    // extension types as objects
+    let o = MsgpackOpts::default().set_ext_policy(ExtPolicy::Object);
+    let cases: Vec<(&[u8], Json)> = vec![
+        (b"\xd4\x01\x00", json!({"type": 1, "data": "AA=="})),
+        (b"\xd5\x02\x00\x01", json!({"type": 2, "data": "AAE="})),
+        (b"\xd7\xff\x00\x00\x00\x00\x00\x00\x00\x01", json!({"type": (-1), "data": "AAAAAAAAAAE="})),
+        (b"\xd8\x05\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f",
+         json!({"type": 5, "data": "AAECAwQFBgcICQoLDA0ODw=="})),
+        (b"\xc7\x03\x07abc", json!({"type": 7, "data": "YWJj"})),
+        (b"\xc8\x00\x00\x07", json!({"type": 7, "data": ""})),
+        (b"\x91\xc9\x00\x00\x00\x02\x07ab", json!([{"type": 7, "data": "YWI="}])),
+    ];
+    for (b, expected) in cases {
+        assert_eq!(msgpack::from_slice_with_opts(b, &o).unwrap(), expected, "{:?}", b);
+    }
+    assert_eq!(msgpack::from_reader_with_opts(&b"\xd4\x01\x00"[..], &o).unwrap(),
+               json!({"type": 1, "data": "AA=="}));
+
+    // a sequence of objects in a stream
+    let mut input = Cursor::new(b"\x01\xa1a\x90".to_vec());
+    let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+    assert_eq!(msgpack::decode(&mut s).unwrap(), json!(1));
+    assert_eq!(msgpack::decode(&mut s).unwrap(), json!("a"));
+    assert_eq!(msgpack::decode(&mut s).unwrap(), json!([]));
+    assert!(msgpack::decode(&mut s).unwrap_err().is_eof());
+}
+
+#[test]
+fn test_msgpack_round_trip() {
+    for f in ["rsc/test/pass1.json", "rsc/test/oeis.json", "rsc/test/arbitrary.json"] {
+        let j = Json::from_file(f).unwrap();
+        let v = msgpack::to_vec(&j);
+        assert_eq!(msgpack::from_slice(&v).unwrap(), j, "{}", f);
+        assert_eq!(msgpack::from_reader(&v[..]).unwrap(), j, "{}", f);
+    }
+    for _ in 0 .. 10 {
+        let j = make_arbitrary();
+        assert_eq!(msgpack::from_slice(&msgpack::to_vec(&j)).unwrap(), j);
+    }
+}
+
+#[test]
+fn test_bson_encode() {
+    // the examples from bsonspec.org
+    let j = json!({"hello": "world"});
+    assert_eq!(bson::to_vec(&j).unwrap(),
+               b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00");
+    let j = json!({"BSON": ["awesome", 5.05, 1986]});
+    assert_eq!(bson::to_vec(&j).unwrap(),
+               b"\x31\x00\x00\x00\x04BSON\x00\x26\x00\x00\x00\x020\x00\x08\x00\x00\x00awesome\x00\
+                 \x011\x00\x33\x33\x33\x33\x33\x33\x14\x40\x102\x00\xc2\x07\x00\x00\x00\x00");
+
+    // the type of each element
+    let cases: Vec<(Json, u8)> = vec![
+        (json!(1), 0x10),
+        (Json::from(-2147483648i64), 0x10),
+        (json!(2147483648u64), 0x12),
+        (Json::from(-9223372036854775808.0), 0x12),
+        (json!(9223372036854775808u64), 0x01),
+        (json!(1.5), 0x01),
+        (Json::from(-0.0), 0x01),
+        (json!(true), 0x08),
+        (json!(null), 0x0a),
+        (json!({"a": 1, "b": 2}), 0x03),
+        (json!({"$unknown": 1}), 0x03),
+        (json!({"$oid": "5f3e1a2b3c4d5e6f70819203"}), 0x07),
+        (json!({"$date": "2012-12-24T12:15:30.501Z"}), 0x09),
+        (json!({"$date": {"$numberLong": "-1"}}), 0x09),
+        (json!({"$binary": {"base64": "", "subType": "00"}}), 0x05),
+        (json!({"$numberInt": "7"}), 0x10),
+        (json!({"$numberLong": "7"}), 0x12),
+        (json!({"$numberDouble": "7"}), 0x01),
+        (json!({"$numberDouble": "NaN"}), 0x01),
+        (json!({"$timestamp": {"t": 1, "i": 2}}), 0x11),
+        (json!({"$regularExpression": {"pattern": "^a", "options": ""}}), 0x0b),
+        (json!({"$symbol": "s"}), 0x0e),
+        (json!({"$code": "f()"}), 0x0d),
+        (json!({"$minKey": 1}), 0xff),
+        (json!({"$maxKey": 1}), 0x7f),
+        (json!({"$undefined": true}), 0x06),
+    ];
+    for (j, t) in cases {
+        let doc = Json::Object(Box::new(vec![("x".to_string(), j)].into_iter().collect()));
+        let v = bson::to_vec(&doc).unwrap();
+        assert_eq!(v[4], t, "{:?}", doc);
+        assert_eq!(v[0] as usize, v.len());
+    }
+
+    // dates with offsets and fractions
+    let date = |s: &str| -> Vec<u8> {
+        let v = bson::to_vec(&json!({"d": {"$date": s}})).unwrap();
+        v[7 .. 15].to_vec()
+    };
+    assert_eq!(date("2012-12-24T12:15:30.501Z"), 1356351330501i64.to_le_bytes());
+    assert_eq!(date("2012-12-24T13:15:30.501+01:00"), 1356351330501i64.to_le_bytes());
+    assert_eq!(date("2012-12-24T11:45:30.5012-0030"), 1356351330501i64.to_le_bytes());
+    assert_eq!(date("1960-01-01T00:00:00Z"), (-315619200000i64).to_le_bytes());
+
+    // regular expression options are sorted
+    let v = bson::to_vec(&json!({"r": {"$regularExpression": {"pattern": "a", "options": "xmi"}}})).unwrap();
+    assert_eq!(v[7 ..], *b"a\x00imx\x00\x00");
+
+    let errors = vec![
+        json!([1, 2]),
+        json!("no document"),
+        json!({"a\u{0}b": 1}),
+        json!({"x": {"$oid": "123"}}),
+        json!({"x": {"$oid": 1}}),
+        json!({"x": {"$date": "2012-12-24"}}),
+        json!({"x": {"$date": "2012-13-24T00:00:00Z"}}),
+        json!({"x": {"$binary": {"base64": "!", "subType": "00"}}}),
+        json!({"x": {"$binary": {"base64": "AA=="}}}),
+        json!({"x": {"$numberInt": "2147483648"}}),
+        json!({"x": {"$numberLong": "1.5"}}),
+        json!({"x": {"$numberDouble": "one"}}),
+        json!({"x": {"$timestamp": {"t": (-1), "i": 0}}}),
+        json!({"x": {"$regularExpression": {"pattern": "a"}}}),
+        json!({"x": {"$minKey": 0}}),
+    ];
+    for j in errors {
+        assert!(match bson::to_vec(&j) {
+            Err(e) => e.kind() == std::io::ErrorKind::InvalidInput,
+            Ok(v) => panic!("unexpected result for {:?}: {:?}", j, v),
+        });
+    }
+
+    let mut v = Vec::new();
+    assert_eq!(bson::encode(&json!({}), &mut v).unwrap(), 5);
+    assert_eq!(v, b"\x05\x00\x00\x00\x00");
+}
+
+#[test]
+fn test_bson_decode() {
+    let canonical = json!({
+        "i": {"$numberInt": "-7"},
+        "l": {"$numberLong": "9007199254740993"},
+        "d": {"$numberDouble": "1.5"},
+        "e": {"$numberDouble": "1e+300"},
+        "z": {"$numberDouble": "-0.0"},
+        "one": {"$numberDouble": "1.0"},
+        "inf": {"$numberDouble": "-Infinity"},
+        "nan": {"$numberDouble": "NaN"},
+        "oid": {"$oid": "5f3e1a2b3c4d5e6f70819203"},
+        "date": {"$date": {"$numberLong": "1356351330501"}},
+        "old": {"$date": {"$numberLong": "-315619200000"}},
+        "bin": {"$binary": {"base64": "AQIDBA==", "subType": "80"}},
+        "bin2": {"$binary": {"base64": "AQID", "subType": "02"}},
+        "ts": {"$timestamp": {"t": 4294967295u32, "i": 1}},
+        "re": {"$regularExpression": {"pattern": "^a.*", "options": "im"}},
+        "sym": {"$symbol": "s"},
+        "code": {"$code": "f()"},
+        "min": {"$minKey": 1},
+        "max": {"$maxKey": 1},
+        "undef": {"$undefined": true},
+        "arr": [{"$numberInt": "1"}, "two", [null]],
+        "doc": {"t": true, "f": false},
+        "s": "caf\u{e9}"
+    });
+    let v = bson::to_vec(&canonical).unwrap();
+    let o = BsonOpts::default().set_canonical(true);
+    assert_eq!(bson::from_slice_with_opts(&v, &o).unwrap(), canonical);
+    assert_eq!(bson::from_reader_with_opts(&v[..], &o).unwrap(), canonical);
+
+    // relaxed mode
+    let j = bson::from_slice(&v).unwrap();
+    assert!(match &j {
+        Json::Object(m) => {
+            m["i"] == Json::from(-7) &&
+            m["l"] == json!(9007199254740992u64) &&
+            m["z"] == Json::Number(-0.0) &&
+            m["one"] == json!(1) &&
+            m["inf"] == json!({"$numberDouble": "-Infinity"}) &&
+            m["date"] == json!({"$date": "2012-12-24T12:15:30.501Z"}) &&
+            m["old"] == json!({"$date": {"$numberLong": "-315619200000"}}) &&
+            m["arr"] == json!([1, "two", [null]])
+        },
+        _ => false,
+    }, "{:?}", j);
+    assert_eq!(bson::from_slice(&bson::to_vec(&j).unwrap()).unwrap(), j);
+
+    // dates at the edge of the relaxed range
+    let date = |ms: i64| -> Json {
+        let v = bson::to_vec(&json!({"d": {"$date": {"$numberLong": (&ms.to_string() as &str)}}})).unwrap();
+        bson::from_slice(&v).unwrap()
+    };
+    assert_eq!(date(0), json!({"d": {"$date": "1970-01-01T00:00:00Z"}}));
+    assert_eq!(date(253402300799999), json!({"d": {"$date": "9999-12-31T23:59:59.999Z"}}));
+    assert_eq!(date(253402300800000), json!({"d": {"$date": {"$numberLong": "253402300800000"}}}));
+    assert_eq!(date(-1), json!({"d": {"$date": {"$numberLong": "-1"}}}));
+    assert_eq!(date(951782400000), json!({"d": {"$date": "2000-02-29T00:00:00Z"}}));
+
+    let errors: Vec<(&[u8], &str)> = vec![
+        (b"\x10\x00\x00\x00\x13x\x00\x00\x00\x00\x00\x00\x00\x00\x00", "unsupported element type 0x13"),
+        (b"\x08\x00\x00\x00\x14x\x00\x00", "invalid element type 0x14"),
+        (b"\x09\x00\x00\x00\x08x\x00\x02\x00", "invalid boolean"),
+        (b"\x0d\x00\x00\x00\x02x\x00\x01\x00\x00\x00a\x00", "string not terminated"),
+        (b"\x0d\x00\x00\x00\x02x\x00\x00\x00\x00\x00\x00", "invalid string length"),
+        (b"\x06\x00\x00\x00\x00\x00", "document length mismatch"),
+        (b"\xff\xff\xff\xff\x00", "invalid length"),
+        (b"\x0e\x00\x00\x00\x02x\x00\x02\x00\x00\x00\xff\x00\x00", "utf8 error"),
+        (b"\x05\x00\x00\x00\x00\x00", "trailing bytes"),
+    ];
+    for (b, msg) in errors {
+        assert!(match bson::from_slice(b) {
+            Err(error::Error::Parse(e)) => e.is_error_type(msg),
+            r => panic!("unexpected result for {:?}: {:?}", b, r),
+        }, "{:?}", b);
+    }
+    assert!(bson::from_slice(b"\x0c\x00\x00\x00\x10x\x00\x01").unwrap_err().is_eof());
+
+    // a dump of several documents
+    let mut v = Vec::new();
+    for i in 0 .. 3 {
+        bson::encode(&json!({"n": i}), &mut v).unwrap();
+    }
+    let mut input = Cursor::new(v);
+    let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+    for i in 0 .. 3 {
+        assert_eq!(bson::decode(&mut s).unwrap(), json!({"n": i}));
+    }
+    assert!(bson::decode(&mut s).unwrap_err().is_eof());
+}
+
+#[test]
+fn test_bson_round_trip() {
+    for f in ["rsc/test/pass1.json", "rsc/test/oeis.json", "rsc/test/arbitrary.json"] {
+        let j = json!({"doc": (Json::from_file(f).unwrap())});
+        let v = bson::to_vec(&j).unwrap();
+        assert_eq!(bson::from_slice(&v).unwrap(), j, "{}", f);
+    }
+    for _ in 0 .. 10 {
+        let j = json!({"doc": (make_arbitrary())});
+        assert_eq!(bson::from_slice(&bson::to_vec(&j).unwrap()).unwrap(), j);
+    }
+}
+
+#[test]
+fn test_ubjson_encode() {
+    let cases: Vec<(Json, &[u8])> = vec![
+        (json!(null), b"Z"),
+        (json!(true), b"T"),
+        (json!(false), b"F"),
+        (json!(0), b"U\x00"),
+        (json!(255), b"U\xff"),
+        (Json::from(-1), b"i\xff"),
+        (Json::from(-128), b"i\x80"),
+        (json!(256), b"I\x01\x00"),
+        (Json::from(-129), b"I\xff\x7f"),
+        (json!(32768), b"l\x00\x00\x80\x00"),
+        (json!(2147483648u64), b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
+        (json!(1.5), b"d\x3f\xc0\x00\x00"),
+        (json!(1.1), b"D\x3f\xf1\x99\x99\x99\x99\x99\x9a"),
+        (Json::from(-0.0), b"d\x80\x00\x00\x00"),
+        (json!(1e19), b"D\x43\xe1\x58\xe4\x60\x91\x3d\x00"),
+        (json!(1180591620717411303424.0), b"d\x62\x80\x00\x00"),
+        (Json::from(f64::NAN), b"Z"),
+        (Json::from(f64::INFINITY), b"Z"),
+        (json!(""), b"SU\x00"),
+        (json!("ubjson"), b"SU\x06ubjson"),
+        (json!([]), b"[#U\x00"),
+        (json!([1, "a"]), b"[#U\x02U\x01SU\x01a"),
+        (json!({"a": 1}), b"{#U\x01U\x01aU\x01"),
+        // optimized containers
+        (json!([1, 2, 3]), b"[$U#U\x03\x01\x02\x03"),
+        (Json::Array(vec![Json::from(-1), json!(1), json!(200)]), b"[$I#U\x03\xff\xff\x00\x01\x00\xc8"),
+        (json!([1, 0.5, 2]), b"[$d#U\x03\x3f\x80\x00\x00\x3f\x00\x00\x00\x40\x00\x00\x00"),
+        (json!([16777217, 0.5, 2]), b"[$D#U\x03\x41\x70\x00\x00\x10\x00\x00\x00\x3f\xe0\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x00\x00\x00\x00"),
+        (json!([null, null, null]), b"[#U\x03ZZZ"),
+        (Json::Array(vec![Json::Null, Json::from(f64::NAN), Json::Null]), b"[#U\x03ZZZ"),
+        (json!([true, true, true]), b"[#U\x03TTT"),
+        (json!([true, false, true]), b"[#U\x03TFT"),
+        (json!(["a", "b", "c"]), b"[$S#U\x03U\x01aU\x01bU\x01c"),
+        (json!([[], [], []]), b"[$[#U\x03#U\x00#U\x00#U\x00"),
+        (json!([1, "a", 2]), b"[#U\x03U\x01SU\x01aU\x02"),
+    ];
+    for (j, expected) in cases {
+        assert_eq!(ubjson::to_vec(&j), expected, "{:?}", j);
+    }
+
+    // long strings and containers have longer counts
+    let s = Json::from("x".repeat(256));
+    assert_eq!(ubjson::to_vec(&s)[.. 5], *b"SI\x01\x00x");
+    let a = Json::Array((0 .. 300).map(|_| json!("x")).collect());
+    assert_eq!(ubjson::to_vec(&a)[.. 8], *b"[$S#I\x01\x2cU");
+
+    let mut v = Vec::new();
+    assert_eq!(ubjson::encode(&json!([1, 2]), &mut v).unwrap(), 8);
+    assert_eq!(v, b"[#U\x02U\x01U\x02");
+}
+
+#[test]
+fn test_ubjson_decode() {
+    let cases: Vec<(&[u8], Json)> = vec![
+        (b"NNZ", json!(null)),
+        (b"L\xff\xff\xff\xff\xff\xff\xff\xfe", Json::from(-2)),
+        (b"l\xff\xff\xff\xfe", Json::from(-2)),
+        (b"HU\x0512.50", json!(12.5)),
+        (b"Cx", json!("x")),
+        (b"[]", json!([])),
+        (b"[U\x01N[Z]]", json!([1, [null]])),
+        (b"[#U\x02U\x01Z", json!([1, null])),
+        (b"[$i#U\x02\xff\x01", Json::Array(vec![Json::from(-1), json!(1)])),
+        (b"{}", json!({})),
+        (b"{U\x01aTU\x01b{}}", json!({"a": true, "b": {}})),
+        (b"{#U\x01U\x01aSU\x01x", json!({"a": "x"})),
+        (b"{$d#U\x01U\x01a\x3f\xc0\x00\x00", json!({"a": 1.5})),
+        (b"{$[#U\x01U\x01a$U#U\x02\x01\x02", json!({"a": [1, 2]})),
+    ];
+    for (b, expected) in cases {
+        assert_eq!(ubjson::from_slice(b).unwrap(), expected, "{:?}", b);
+    }
+
+    let errors: Vec<(&[u8], &str)> = vec![
+        (b"X", "invalid marker"),
+        (b"SZ", "integer expected"),
+        (b"Si\xff", "invalid length"),
+        (b"SU\x02\xc3\x28", "utf8 error"),
+        (b"HU\x03abc", "invalid high-precision number"),
+        (b"C\xff", "invalid char"),
+        (b"[$U\x01", "count expected"),
+        // elements without payload would not consume any input
+        (b"[$Z#l\x01\x00\x00\x00", "invalid type of container"),
+        (b"[$Z#L\x7f\xff\xff\xff\xff\xff\xff\xff", "invalid type of container"),
+        (b"[$N#L\x7f\xff\xff\xff\xff\xff\xff\xff", "invalid type of container"),
+        (b"[$T#U\x02", "invalid type of container"),
+        (b"{$F#L\x7f\xff\xff\xff\xff\xff\xff\xffU\x01a", "invalid type of container"),
+        (b"ZZ", "trailing bytes"),
+    ];
+    for (b, msg) in errors {
+        assert!(match ubjson::from_slice(b) {
+            Err(error::Error::Parse(e)) => e.is_error_type(msg),
+            r => panic!("unexpected result for {:?}: {:?}", b, r),
+        }, "{:?}", b);
+    }
+    assert!(ubjson::from_slice(b"[U\x01").unwrap_err().is_eof());
+    assert!(ubjson::from_slice(b"[#U\x02Z").unwrap_err().is_eof());
+
+    // a sequence of values in a stream
+    let mut input = Cursor::new(b"U\x01SU\x01a[]".to_vec());
+    let mut s = Stream::new(Opts::default().set_buf_size(8), &mut input);
+    assert_eq!(ubjson::decode(&mut s).unwrap(), json!(1));
+    assert_eq!(ubjson::decode(&mut s).unwrap(), json!("a"));
+    assert_eq!(ubjson::decode(&mut s).unwrap(), json!([]));
+    assert!(ubjson::decode(&mut s).unwrap_err().is_eof());
+}
+
+#[test]
+fn test_ubjson_round_trip() {
+    for f in ["rsc/test/pass1.json", "rsc/test/oeis.json", "rsc/test/oeis2.json", "rsc/test/arbitrary.json"] {
+        let j = Json::from_file(f).unwrap();
+        let v = ubjson::to_vec(&j);
+        assert_eq!(ubjson::from_slice(&v).unwrap(), j, "{}", f);
+        assert_eq!(ubjson::from_reader(&v[..]).unwrap(), j, "{}", f);
+    }
+    for _ in 0 .. 10 {
+        let j = make_arbitrary();
+        assert_eq!(ubjson::from_slice(&ubjson::to_vec(&j)).unwrap(), j);
+    }
+}
+
+#[test]
+fn test_binary_strings_across_buffers() {
+    type Encode = fn(&Json) -> Vec<u8>;
+    type Decode = fn(&mut Stream<Cursor<Vec<u8>>>) -> ParseResult<Json>;
+    type FromSlice = fn(&[u8]) -> error::Result<Json>;
+    let codecs: [(&str, Encode, Decode, FromSlice); 4] = [
+        ("cbor", cbor::to_vec, cbor::decode, cbor::from_slice),
+        ("msgpack", msgpack::to_vec, msgpack::decode, msgpack::from_slice),
+        ("bson", |j| bson::to_vec(j).unwrap(), bson::decode, bson::from_slice),
+        ("ubjson", ubjson::to_vec, ubjson::decode, ubjson::from_slice),
+    ];
+    for (name, encode, decode, from_slice) in codecs {
+        for size in [8, 13] {
+            for n in 0 .. 40 {
+                let j = json!({"s": (&"é0123456".repeat(n) as &str)});
+                let v = encode(&j);
+                let mut input = Cursor::new(v.clone());
+                let mut s = Stream::new(Opts::default().set_buf_size(size).set_buf_num(3), &mut input);
+                assert_eq!(decode(&mut s).unwrap(), j, "{} {} {}", name, size, n);
+
+                // a stream over truncated binary input may yield a stale byte
+                // at a buffer boundary (see EofGuard), from_slice does not
+                assert!(from_slice(&v[.. v.len() - 1]).is_err(), "{} {} {}", name, n, v.len());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod async_tests {
+    use super::*;
+    use crate::async_io::*;
+    use bytes::BytesMut;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    fn documents() -> Vec<Json> {
+        vec![
+            json!({"id": 1, "name": "a \"quoted\" {string}", "list": [1, [2, [3]], {}]}),
+            json!([]),
+            json!("top-level string with \\ and ]"),
+            Json::from(-12.5e3),
+            json!(true),
+            json!(null),
+            json!([{"x": "}"}, {"y": "]"}]),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_async_round_trip() {
+        let (mut w, r) = duplex(16); // small pipe to force partial reads
+        let mut r = BufReader::new(r);
+        let docs = documents();
+
+        let writer = async {
+            for j in &docs {
+                serialize_async(j, &mut w).await.unwrap();
+                w.write_all(b" ").await.unwrap(); // separates top-level scalars
+                serialize_async_with_opts(j, &mut w, &SerializeOpts::default().set_compact(true)).await.unwrap();
+                w.write_all(b" ").await.unwrap();
+            }
+            drop(w);
+        };
+
+        let reader = async {
+            let mut vs = Vec::new();
+            loop {
+                match parse_async(&mut r).await {
+                    Ok(j) => vs.push(j),
+                    Err(e) if e.is_eof() => break,
+                    Err(e) => panic!("unexpected error: {}", e),
+                }
+            }
+            vs
+        };
+
+        let ((), vs) = tokio::join!(writer, reader);
+        assert_eq!(vs.len(), 2 * docs.len());
+        for (i, j) in vs.iter().enumerate() {
+            assert_eq!(*j, docs[i / 2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_does_not_wait_for_more() {
+        let (mut w, r) = duplex(64);
+        let mut r = BufReader::new(r);
+
+        // the writer stays open: the parser must not wait for more input
+        w.write_all(b"{\"a\": [1, 2]} [true]").await.unwrap();
+        assert_eq!(parse_async(&mut r).await.unwrap(), json!({"a": [1, 2]}));
+        assert_eq!(parse_async(&mut r).await.unwrap(), json!([true]));
+
+        w.write_all(b"[1, 2").await.unwrap();
+        drop(w);
+        assert!(parse_async(&mut r).await.unwrap_err().is_eof());
+    }
+
+    #[tokio::test]
+    async fn test_etx_codec() {
+        let (mut w, mut r) = duplex(16);
+        let docs = documents();
+
+        let writer = async {
+            let mut codec = EtxCodec;
+            let mut buf = BytesMut::new();
+            for (i, j) in docs.iter().enumerate() {
+                let payload = Json::from_str(&j.to_string()).unwrap();
+                codec.encode(Command { name: format!("Cmd{}", i), payload }, &mut buf).unwrap();
+            }
+            w.write_all(&buf).await.unwrap();
+            w.write_all(b"Broken\n[1]\x03").await.unwrap();
+            drop(w);
+        };
+
+        let reader = async {
+            let mut codec = EtxCodec;
+            let mut buf = BytesMut::new();
+            let mut cmds = Vec::new();
+            loop {
+                while let Some(c) = codec.decode(&mut buf).unwrap_or_else(|e| {
+                    cmds.push(Err(e));
+                    None
+                }) {
+                    cmds.push(Ok(c));
+                }
+                if r.read_buf(&mut buf).await.unwrap() == 0 {
+                    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+                    break;
+                }
+            }
+            cmds
+        };
+
+        let ((), cmds) = tokio::join!(writer, reader);
+        assert_eq!(cmds.len(), docs.len() + 1);
+        for (i, c) in cmds[.. docs.len()].iter().enumerate() {
+            assert!(match c {
+                Ok(c) => c.name == format!("Cmd{}", i) && c.payload == docs[i],
+                Err(e) => panic!("unexpected error: {}", e),
+            });
+        }
+        assert!(match &cmds[docs.len()] {
+            Err(error::Error::Parse(e)) => e.is_error_type("command expected"),
+            r => panic!("unexpected result: {:?}", r),
+        });
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_tests {
+    use super::*;
+    use crate::property::*;
+    use proptest::prelude::*;
+    use proptest::test_runner::{Config, TestError, TestRunner};
+
+    fn runner() -> TestRunner {
+        TestRunner::new(Config {
+            cases: 100,
+            // the automatic limit is too low for the stress values
+            max_shrink_iters: 100_000,
+            failure_persistence: None,
+            ..Config::default()
+        })
+    }
+
+    fn any_number(j: &Json, f: fn(f64) -> bool) -> bool {
+        match j {
+            Json::Number(n) => f(*n),
+            Json::Array(v) => v.iter().any(|e| any_number(e, f)),
+            Json::Object(m) => m.values().any(|e| any_number(e, f)),
+            _ => false,
+        }
+    }
+
+    fn any_string(j: &Json, f: fn(&str) -> bool) -> bool {
+        match j {
+            Json::String(s) => f(s),
+            Json::Array(v) => v.iter().any(|e| any_string(e, f)),
+            Json::Object(m) => m.iter().any(|(k, e)| f(k) || any_string(e, f)),
+            _ => false,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(j in any::<Json>()) {
+            let v = j.serialize_to_vec(&SerializeOpts::default().set_compact(true));
+            prop_assert_eq!(Json::from_slice(&v).unwrap(), j.clone());
+            prop_assert_eq!(cbor::from_slice(&cbor::to_vec(&j)).unwrap(), j);
+        }
+    }
+
+    #[test]
+    fn shrinks_to_minimal_number() {
+        let r = runner().run(&JsonStrategy::default(), |j| {
+            prop_assert!(!any_number(&j, |n| n > 0.5));
+            Ok(())
+        });
+        assert!(match r {
+            Err(TestError::Fail(_, j)) => j == json!(1),
+            r => panic!("unexpected result: {:?}", r),
+        });
+    }
+
+    #[test]
+    fn shrinks_to_minimal_string() {
+        let r = runner().run(&JsonStrategy::default(), |j| {
+            prop_assert!(!any_string(&j, |s| s.contains('"')));
+            Ok(())
+        });
+        assert!(match r {
+            Err(TestError::Fail(_, j)) => j == json!("\""),
+            r => panic!("unexpected result: {:?}", r),
+        });
+    }
+
+    #[test]
+    fn strategy_with_config() {
+        let c = GeneratorConfig::default().set_weights([0, 0, 0, 0, 1, 0]).set_depth(1);
+        let r = runner().run(&JsonStrategy::new(c, 5), |j| {
+            prop_assert!(matches!(&j, Json::Array(v) if v.len() <= 5 && v.iter().all(|e| *e == Json::Null)));
+            Ok(())
+        });
+        assert!(r.is_ok());
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod quickcheck_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
+
+    thread_local! {
+        // the last value that failed
+        static FAILED: RefCell<Option<Json>> = const { RefCell::new(None) };
+    }
+
+    fn no_long_array(j: Json) -> TestResult {
+        fn long(j: &Json) -> bool {
+            match j {
+                Json::Array(v) => v.len() > 2 || v.iter().any(long),
+                Json::Object(m) => m.values().any(long),
+                _ => false,
+            }
+        }
+        if long(&j) {
+            FAILED.with(|f| *f.borrow_mut() = Some(j));
+            return TestResult::failed();
+        }
+        TestResult::passed()
+    }
+
+    #[test]
+    fn round_trip() {
+        fn prop(j: Json) -> bool {
+            let v = j.serialize_to_vec(&SerializeOpts::default());
+            Json::from_slice(&v).ok() == Some(j)
+        }
+        QuickCheck::new().tests(50).quickcheck(prop as fn(Json) -> bool);
+    }
+
+    #[test]
+    fn shrinks_to_minimal_array() {
+        let r = QuickCheck::new()
+                .rng(Gen::new(30))
+                .tests(200)
+                .quicktest(no_long_array as fn(Json) -> TestResult);
+        assert!(r.is_err());
+        assert_eq!(FAILED.with(|f| f.borrow_mut().take()), Some(json!([null, null, null])));
+    }
+
+    #[test]
+    fn arbitrary_uses_gen_size() {
+        let mut g = Gen::new(0);
+        for _ in 0 .. 20 {
+            assert!(match Json::arbitrary(&mut g) {
+                Json::Array(v) => v.is_empty(),
+                Json::Object(m) => m.is_empty(),
+                _ => true,
+            });
+        }
+        assert!(Arbitrary::shrink(&json!([1])).any(|j| j == json!([])));
+    }
+}
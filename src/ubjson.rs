@@ -0,0 +1,413 @@
+use std::io::{self, Read, Write};
+use pacosso::{Stream, ParseResult};
+use super::*;
+use super::error;
+use super::parsing::{parse_buffer_with, end_of_data};
+use super::binary::{uint, get_bytes};
+
+// type markers
+const NULL: u8 = b'Z';
+const NOOP: u8 = b'N';
+const TRUE: u8 = b'T';
+const FALSE: u8 = b'F';
+const INT8: u8 = b'i';
+const UINT8: u8 = b'U';
+const INT16: u8 = b'I';
+const INT32: u8 = b'l';
+const INT64: u8 = b'L';
+const FLOAT32: u8 = b'd';
+const FLOAT64: u8 = b'D';
+const HIGH_PRECISION: u8 = b'H';
+const CHAR: u8 = b'C';
+const STRING: u8 = b'S';
+const ARRAY_START: u8 = b'[';
+const ARRAY_END: u8 = b']';
+const OBJECT_START: u8 = b'{';
+const OBJECT_END: u8 = b'}';
+
+// markers of optimized containers
+const TYPE: u8 = b'$';
+const COUNT: u8 = b'#';
+
+/// Encodes Json value 'j' as UBJSON (draft 12) into writer 'w'
+/// and returns the number of bytes written.
+///
+/// Numbers without fraction are written as integers of the smallest type,
+/// others as float32 if that preserves their value and as float64 otherwise;
+/// NaN and infinity are written as null.
+/// Arrays and objects are written as optimized containers with a count;
+/// if all their elements have the same type, the type is written once
+/// for the container instead of once per element,
+/// except for null, true and false, which have no payload.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::ubjson::encode;
+///
+///    let mut v: Vec<u8> = Vec::new();
+///    encode(&json!({"a": [1, 2, 300], "b": null}), &mut v).unwrap();
+///    assert!(v == b"{#U\x02U\x01a[$I#U\x03\x00\x01\x00\x02\x01\x2cU\x01bZ" ||
+///            v == b"{#U\x02U\x01bZU\x01a[$I#U\x03\x00\x01\x00\x02\x01\x2c");
+/// ```
+pub fn encode<W: Write>(j: &Json, w: &mut W) -> io::Result<usize> {
+    let v = to_vec(j);
+    w.write_all(&v)?;
+    w.flush()?;
+    Ok(v.len())
+}
+
+/// Encodes Json value 'j' as UBJSON into a new byte vector.
+pub fn to_vec(j: &Json) -> Vec<u8> {
+    let mut v = Vec::new();
+    write_value(&mut v, j);
+    v
+}
+
+fn write_value(v: &mut Vec<u8>, j: &Json) {
+    let m = marker(j);
+    v.push(m);
+    write_payload(v, j, m);
+}
+
+// the type marker of a value
+fn marker(j: &Json) -> u8 {
+    match j {
+        Json::Null => NULL,
+        Json::Boolean(true) => TRUE,
+        Json::Boolean(false) => FALSE,
+        Json::Number(n) if !n.is_finite() => NULL,
+        Json::Number(n) if is_integer(*n) => int_marker(*n as i64, *n as i64),
+        Json::Number(n) if (*n as f32) as f64 == *n => FLOAT32,
+        Json::Number(_) => FLOAT64,
+        Json::String(_) => STRING,
+        Json::Array(_) => ARRAY_START,
+        Json::Object(_) => OBJECT_START,
+    }
+}
+
+fn is_integer(n: f64) -> bool {
+    const LIMIT: f64 = 9223372036854775808.0; // 2^63
+
+    // -0 is a float, NaN and infinity have no integral part
+    n.fract() == 0.0 && !(n == 0.0 && n.is_sign_negative()) && (-LIMIT .. LIMIT).contains(&n)
+}
+
+// the smallest integer type for values from 'min' to 'max'
+fn int_marker(min: i64, max: i64) -> u8 {
+    if min >= 0 && max <= u8::MAX as i64 {
+        UINT8
+    } else if min >= i8::MIN as i64 && max <= i8::MAX as i64 {
+        INT8
+    } else if min >= i16::MIN as i64 && max <= i16::MAX as i64 {
+        INT16
+    } else if min >= i32::MIN as i64 && max <= i32::MAX as i64 {
+        INT32
+    } else {
+        INT64
+    }
+}
+
+// The type marker that all elements can be written with, if any.
+// Integers of different size share the smallest type that fits all,
+// numbers with and without fraction share a float type.
+// Types without payload are not shared: the decoder rejects them,
+// as their count could be anything without reading any input.
+fn common_marker<'j, I: Iterator<Item = &'j Json>>(es: I) -> Option<u8> {
+    let mut first = None;
+    let mut same = true;
+    let (mut numbers, mut ints, mut f32s) = (true, true, true);
+    let (mut min, mut max) = (i64::MAX, i64::MIN);
+    for e in es {
+        let m = marker(e);
+        match first {
+            None => first = Some(m),
+            Some(f) => same = same && f == m,
+        }
+        match e {
+            Json::Number(n) if n.is_finite() => {
+                if is_integer(*n) {
+                    (min, max) = (min.min(*n as i64), max.max(*n as i64));
+                } else {
+                    ints = false;
+                }
+                f32s = f32s && (*n as f32) as f64 == *n;
+            },
+            _ => numbers = false,
+        }
+    }
+    match first {
+        Some(_) if numbers && ints => Some(int_marker(min, max)),
+        Some(_) if numbers && f32s => Some(FLOAT32),
+        Some(_) if numbers => Some(FLOAT64),
+        Some(NULL | TRUE | FALSE) => None,
+        Some(f) if same => Some(f),
+        _ => None,
+    }
+}
+
+// writes the value without its type marker as type 'm'
+fn write_payload(v: &mut Vec<u8>, j: &Json, m: u8) {
+    match j {
+        Json::Number(n) => match m {
+            UINT8 => v.push(*n as u8),
+            INT8 => v.push(*n as i8 as u8),
+            INT16 => v.extend_from_slice(&(*n as i16).to_be_bytes()),
+            INT32 => v.extend_from_slice(&(*n as i32).to_be_bytes()),
+            INT64 => v.extend_from_slice(&(*n as i64).to_be_bytes()),
+            FLOAT32 => v.extend_from_slice(&(*n as f32).to_be_bytes()),
+            FLOAT64 => v.extend_from_slice(&n.to_be_bytes()),
+            _ => (), // null
+        },
+        Json::String(s) => write_string(v, s),
+        Json::Array(a) => {
+            let t = write_header(v, a.len(), a.iter());
+            for e in a.iter() {
+                match t {
+                    Some(t) => write_payload(v, e, t),
+                    None => write_value(v, e),
+                }
+            }
+        },
+        Json::Object(o) => {
+            let t = write_header(v, o.len(), o.values());
+            for (k, e) in o.iter() {
+                write_string(v, k);
+                match t {
+                    Some(t) => write_payload(v, e, t),
+                    None => write_value(v, e),
+                }
+            }
+        },
+        _ => (), // null, true and false have no payload
+    }
+}
+
+// Writes the type and count of an optimized container
+// and returns the type of the elements, if it is written.
+fn write_header<'j, I: Iterator<Item = &'j Json>>(v: &mut Vec<u8>, n: usize, es: I) -> Option<u8> {
+    // the type costs two bytes and saves one per element
+    let t = if n > 2 { common_marker(es) } else { None };
+    if let Some(t) = t {
+        v.push(TYPE);
+        v.push(t);
+    }
+    v.push(COUNT);
+    write_length(v, n);
+    t
+}
+
+fn write_length(v: &mut Vec<u8>, n: usize) {
+    let m = int_marker(0, n as i64);
+    v.push(m);
+    write_payload(v, &Json::Number(n as f64), m);
+}
+
+fn write_string(v: &mut Vec<u8>, s: &str) {
+    write_length(v, s.len());
+    v.extend_from_slice(s.as_bytes());
+}
+
+/// Decodes one UBJSON value from stream 's' into a Json value.
+/// The stream is left after the value,
+/// so that `decode` can be called again for the next value.
+///
+/// Optimized containers and no-op markers are accepted,
+/// but not optimized containers typed null, no-op, true or false,
+/// whose elements would take no input at all.
+/// Characters become strings, high-precision numbers become numbers.
+///
+/// Example:
+///
+/// ```
+///    use std::io::Cursor;
+///    use jsosso::json;
+///    use jsosso::ubjson::decode;
+///    use pacosso::{Stream, Opts};
+///
+///    let mut input = Cursor::new(b"{U\x01a[$i#U\x02\x01\x02U\x01bZ}N[CaT]".to_vec());
+///    let mut s = Stream::new(Opts::default(), &mut input);
+///
+///    assert_eq!(decode(&mut s).unwrap(), json!({"a": [1, 2], "b": null}));
+///    assert_eq!(decode(&mut s).unwrap(), json!(["a", true]));
+///    assert!(decode(&mut s).unwrap_err().is_eof());
+/// ```
+pub fn decode<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let m = next_marker(s)?;
+    value(s, m)
+}
+
+/// Decodes a complete UBJSON value from byte slice 'b'.
+/// Nothing may follow the value.
+///
+/// Example:
+///
+/// ```
+///    use jsosso::json;
+///    use jsosso::ubjson;
+///
+///    let j = json!({"list": [1, 2.5, "three"]});
+///    assert_eq!(ubjson::from_slice(&ubjson::to_vec(&j)).unwrap(), j);
+/// ```
+pub fn from_slice(b: &[u8]) -> error::Result<Json> {
+    Ok(parse_buffer_with(b, decode_complete)?)
+}
+
+/// Decodes a complete UBJSON value from reader 'r'.
+/// Nothing may follow the value.
+pub fn from_reader<R: Read>(mut r: R) -> error::Result<Json> {
+    // the input is read completely, so that the stream buffers
+    // can be sized to the input (see parsing::EofGuard)
+    let mut v = Vec::new();
+    r.read_to_end(&mut v)?;
+    from_slice(&v)
+}
+
+fn decode_complete<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let j = decode(s)?;
+    end_of_data(s)?;
+    Ok(j)
+}
+
+// reads the next marker that is not a no-op
+fn next_marker<R: Read>(s: &mut Stream<R>) -> ParseResult<u8> {
+    loop {
+        match s.any_byte()? {
+            NOOP => continue,
+            m => return Ok(m),
+        }
+    }
+}
+
+// reads the value of type 'm', whose marker was already read
+fn value<R: Read>(s: &mut Stream<R>, m: u8) -> ParseResult<Json> {
+    match m {
+        NULL => Ok(Json::Null),
+        TRUE => Ok(Json::Boolean(true)),
+        FALSE => Ok(Json::Boolean(false)),
+        INT8 | UINT8 | INT16 | INT32 | INT64 => Ok(Json::Number(integer(s, m)? as f64)),
+        FLOAT32 => Ok(Json::Number(f32::from_bits(uint(s, 4)? as u32) as f64)),
+        FLOAT64 => Ok(Json::Number(f64::from_bits(uint(s, 8)?))),
+        HIGH_PRECISION => {
+            let t = string(s)?;
+            match Json::from_slice(t.as_bytes()) {
+                Ok(Json::Number(n)) => Ok(Json::Number(n)),
+                _ => s.fail(&format!("invalid high-precision number '{}'", t), Json::Null),
+            }
+        },
+        CHAR => match s.any_byte()? {
+            c if c.is_ascii() => Ok(Json::String((c as char).to_string())),
+            c => s.fail(&format!("invalid char {}", c), Json::Null),
+        },
+        STRING => Ok(Json::String(string(s)?)),
+        ARRAY_START => array(s),
+        OBJECT_START => object(s),
+        _ => s.fail(&format!("invalid marker {}", m), Json::Null),
+    }
+}
+
+fn integer<R: Read>(s: &mut Stream<R>, m: u8) -> ParseResult<i64> {
+    match m {
+        INT8 => Ok(uint(s, 1)? as i8 as i64),
+        UINT8 => Ok(uint(s, 1)? as i64),
+        INT16 => Ok(uint(s, 2)? as i16 as i64),
+        INT32 => Ok(uint(s, 4)? as i32 as i64),
+        INT64 => Ok(uint(s, 8)? as i64),
+        _ => s.fail(&format!("integer expected, found marker {}", m), 0),
+    }
+}
+
+fn length<R: Read>(s: &mut Stream<R>) -> ParseResult<usize> {
+    let m = next_marker(s)?;
+    let n = integer(s, m)?;
+    match usize::try_from(n) {
+        Ok(n) => Ok(n),
+        Err(_) => s.fail(&format!("invalid length {}", n), 0),
+    }
+}
+
+fn string<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+    let n = length(s)?;
+    let mut v = Vec::new();
+    get_bytes(s, &mut v, n as u64)?;
+    match String::from_utf8(v) {
+        Ok(t) => Ok(t),
+        Err(_) => s.fail("utf8 error in string", String::new()),
+    }
+}
+
+// reads the type and count of an optimized container
+fn header<R: Read>(s: &mut Stream<R>) -> ParseResult<(Option<u8>, Option<usize>)> {
+    let t = match s.peek_byte() {
+        Ok(TYPE) => {
+            s.any_byte()?;
+            match s.any_byte()? {
+                t @ (NULL | NOOP | TRUE | FALSE) => {
+                    return s.fail(&format!("invalid type of container {}", t), (None, None));
+                },
+                t => Some(t),
+            }
+        },
+        Ok(_) => None,
+        Err(e) => return Err(e),
+    };
+    match s.peek_byte()? {
+        COUNT => {
+            s.any_byte()?;
+            Ok((t, Some(length(s)?)))
+        },
+        _ if t.is_some() => s.fail("count expected after type of container", (None, None)),
+        _ => Ok((None, None)),
+    }
+}
+
+fn array<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let mut a = Vec::new();
+    match header(s)? {
+        (t, Some(n)) => {
+            a.reserve(n.min(1024)); // do not trust the length
+            for _ in 0 .. n {
+                let m = match t {
+                    Some(t) => t,
+                    None => next_marker(s)?,
+                };
+                a.push(value(s, m)?);
+            }
+        },
+        (_, None) => loop {
+            let m = next_marker(s)?;
+            if m == ARRAY_END {
+                break;
+            }
+            a.push(value(s, m)?);
+        },
+    }
+    Ok(Json::Array(a))
+}
+
+fn object<R: Read>(s: &mut Stream<R>) -> ParseResult<Json> {
+    let mut o = HashMap::new();
+    match header(s)? {
+        (t, Some(n)) => {
+            for _ in 0 .. n {
+                let k = string(s)?;
+                let m = match t {
+                    Some(t) => t,
+                    None => next_marker(s)?,
+                };
+                o.insert(k, value(s, m)?);
+            }
+        },
+        (_, None) => loop {
+            if s.peek_byte()? == OBJECT_END {
+                s.any_byte()?;
+                break;
+            }
+            let k = string(s)?;
+            let m = next_marker(s)?;
+            o.insert(k, value(s, m)?);
+        },
+    }
+    Ok(Json::Object(Box::new(o)))
+}